@@ -14,10 +14,25 @@ CREATE TABLE public.daily_work_span (
     total_span_minutes BIGINT NOT NULL,
     total_span TEXT NOT NULL, -- The H.MM formatted string (e.g., '9.06')
 
+    -- Manual corrections made with `avadhi-collector adjust`
+    adjusted BOOLEAN NOT NULL DEFAULT false,
+    note TEXT,
+
+    -- Work calendar classification ('workday', 'weekend' or 'holiday') and scheduled minutes
+    day_type TEXT NOT NULL DEFAULT 'workday',
+    expected_minutes INTEGER NOT NULL DEFAULT 0,
+
+    -- Overtime (+) / undertime (-) for the day and running balances for its ISO week and month
+    deviation_minutes INTEGER NOT NULL DEFAULT 0,
+    week_balance_minutes INTEGER NOT NULL DEFAULT 0,
+    month_balance_minutes INTEGER NOT NULL DEFAULT 0,
+
     -- Optional: Record when the entry was last updated by the Rust service
     updated_at TIMESTAMPTZ DEFAULT NOW()
 );
 
+-- Existing installations: run daily_work_span_upgrade.sql to add the columns above.
+
 -- Apply indexes for faster querying
 CREATE INDEX idx_daily_work_span_timestamp ON public.daily_work_span (timestamp);
 
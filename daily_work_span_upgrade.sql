@@ -0,0 +1,22 @@
+-- Upgrades a 'daily_work_span' table created before the manual corrections, work calendar and overtime
+-- balance columns existed. Collectors that post these columns fail with HTTP 400 ("Could not find the
+-- '...' column") until this has run. Safe to run more than once.
+BEGIN;
+
+-- Manual corrections made with `avadhi-collector adjust`
+ALTER TABLE public.daily_work_span ADD COLUMN IF NOT EXISTS adjusted BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE public.daily_work_span ADD COLUMN IF NOT EXISTS note TEXT;
+
+-- Work calendar classification and scheduled minutes
+ALTER TABLE public.daily_work_span ADD COLUMN IF NOT EXISTS day_type TEXT NOT NULL DEFAULT 'workday';
+ALTER TABLE public.daily_work_span ADD COLUMN IF NOT EXISTS expected_minutes INTEGER NOT NULL DEFAULT 0;
+
+-- Overtime (+) / undertime (-) and running balances
+ALTER TABLE public.daily_work_span ADD COLUMN IF NOT EXISTS deviation_minutes INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE public.daily_work_span ADD COLUMN IF NOT EXISTS week_balance_minutes INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE public.daily_work_span ADD COLUMN IF NOT EXISTS month_balance_minutes INTEGER NOT NULL DEFAULT 0;
+
+COMMIT;
+
+-- PostgREST caches the table schema; make it pick up the new columns right away.
+NOTIFY pgrst, 'reload schema';
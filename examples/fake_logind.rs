@@ -0,0 +1,114 @@
+//! Minimal logind stand-in for exercising idle and lock detection without a real system bus.
+//!
+//! Start a private bus and point the collector at it:
+//!
+//! ```bash
+//! dbus-daemon --session --print-address --fork      # prints ADDRESS
+//! cargo run --example fake_logind -- ADDRESS true 1700000000000000
+//! # Config.toml: [idle] dbus_address = "ADDRESS"
+//! ./avadhi-collector idle-sample
+//! ```
+//!
+//! Arguments: bus address, IdleHint (true/false), IdleSinceHint (µs since epoch).
+//!
+//! One seated session ("1", user "tester") is exported. Drive it like the real logind:
+//!
+//! ```bash
+//! busctl --address=ADDRESS call org.freedesktop.login1 /org/freedesktop/login1/session/_31 \
+//!     org.freedesktop.login1.Session SetLockedHint b true
+//! busctl --address=ADDRESS call org.freedesktop.login1 /org/freedesktop/login1/session/_31 \
+//!     org.freedesktop.login1.Session Unlock
+//! ```
+
+use std::env;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::OwnedObjectPath;
+
+const SESSION_PATH: &str = "/org/freedesktop/login1/session/_31";
+
+struct FakeManager {
+    idle_hint: bool,
+    idle_since_hint: u64,
+}
+
+#[zbus::interface(name = "org.freedesktop.login1.Manager")]
+impl FakeManager {
+    #[zbus(property)]
+    fn idle_hint(&self) -> bool {
+        self.idle_hint
+    }
+
+    #[zbus(property)]
+    fn idle_since_hint(&self) -> u64 {
+        self.idle_since_hint
+    }
+
+    fn list_sessions(&self) -> Vec<(String, u32, String, String, OwnedObjectPath)> {
+        vec![(
+            "1".to_string(),
+            1000,
+            "tester".to_string(),
+            "seat0".to_string(),
+            OwnedObjectPath::try_from(SESSION_PATH).unwrap(),
+        )]
+    }
+}
+
+struct FakeSession {
+    locked_hint: bool,
+}
+
+#[zbus::interface(name = "org.freedesktop.login1.Session")]
+impl FakeSession {
+    #[zbus(property)]
+    fn locked_hint(&self) -> bool {
+        self.locked_hint
+    }
+
+    async fn set_locked_hint(&mut self, locked: bool, #[zbus(signal_emitter)] emitter: SignalEmitter<'_>) -> zbus::fdo::Result<()> {
+        self.locked_hint = locked;
+        self.locked_hint_changed(&emitter).await?;
+        Ok(())
+    }
+
+    async fn lock(&self, #[zbus(signal_emitter)] emitter: SignalEmitter<'_>) -> zbus::fdo::Result<()> {
+        Self::lock_signal(&emitter).await?;
+        Ok(())
+    }
+
+    async fn unlock(&self, #[zbus(signal_emitter)] emitter: SignalEmitter<'_>) -> zbus::fdo::Result<()> {
+        Self::unlock_signal(&emitter).await?;
+        Ok(())
+    }
+
+    #[zbus(signal, name = "Lock")]
+    async fn lock_signal(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal, name = "Unlock")]
+    async fn unlock_signal(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+}
+
+#[tokio::main]
+async fn main() -> zbus::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 4 {
+        eprintln!("Usage: fake_logind <bus-address> <idle-hint true|false> <idle-since-usec>");
+        std::process::exit(2);
+    }
+
+    let manager = FakeManager {
+        idle_hint: args[2].parse().unwrap_or(false),
+        idle_since_hint: args[3].parse().unwrap_or(0),
+    };
+
+    let _connection = zbus::connection::Builder::address(args[1].as_str())?
+        .name("org.freedesktop.login1")?
+        .serve_at("/org/freedesktop/login1", manager)?
+        .serve_at(SESSION_PATH, FakeSession { locked_hint: false })?
+        .build()
+        .await?;
+
+    println!("Fake logind serving on {} (IdleHint={}, IdleSinceHint={}).", args[1], args[2], args[3]);
+    std::future::pending::<()>().await;
+    Ok(())
+}
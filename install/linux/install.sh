@@ -22,6 +22,8 @@ echo "  - avadhi-collector"
 echo "  - Config.toml.example"
 echo "  - avadhi@.service"
 echo "  - avadhi.timer"
+echo "  - avadhi-idle.service / avadhi-idle.timer"
+echo "  - avadhi-locks.service"
 echo "--------------------------------------------------"
 
 # ---------------- Step 0: System User ----------------
@@ -61,8 +63,11 @@ sudo chown -R "$SERVICE_USER":"$SERVICE_USER" "$INSTALL_DIR"
 echo "4. Installing systemd units..."
 TEMPLATE_UNIT="avadhi@.service"
 TIMER_UNIT="avadhi.timer"
+IDLE_SERVICE_UNIT="avadhi-idle.service"
+IDLE_TIMER_UNIT="avadhi-idle.timer"
+LOCKS_UNIT="avadhi-locks.service"
 
-for UNIT in "$TEMPLATE_UNIT" "$TIMER_UNIT"; do
+for UNIT in "$TEMPLATE_UNIT" "$TIMER_UNIT" "$IDLE_SERVICE_UNIT" "$IDLE_TIMER_UNIT" "$LOCKS_UNIT"; do
     [ -f "$SCRIPT_SOURCE_DIR/$UNIT" ] || { echo "FATAL: Required unit file '$UNIT' not found."; exit 1; }
     sudo cp -f "$SCRIPT_SOURCE_DIR/$UNIT" "/etc/systemd/system/$UNIT"
     echo "   Installed $UNIT"
@@ -71,6 +76,10 @@ done
 sudo systemctl daemon-reload
 sudo systemctl enable "$TIMER_UNIT"
 sudo systemctl start "$TIMER_UNIT"
+sudo systemctl enable "$IDLE_TIMER_UNIT"
+sudo systemctl start "$IDLE_TIMER_UNIT"
+sudo systemctl enable "$LOCKS_UNIT"
+sudo systemctl start "$LOCKS_UNIT"
 
 # ---------------- Step 5: Setup Confirmation ----------------
 echo
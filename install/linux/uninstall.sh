@@ -5,17 +5,27 @@ INSTALL_DIR="/opt/avadhi-collector"
 SERVICE_USER="avadhi"
 TEMPLATE_UNIT="avadhi@.service"
 TIMER_UNIT="avadhi.timer"
+IDLE_SERVICE_UNIT="avadhi-idle.service"
+IDLE_TIMER_UNIT="avadhi-idle.timer"
+LOCKS_UNIT="avadhi-locks.service"
 
 echo "Stopping Avadhi Collector timer and service..."
 sudo systemctl stop "$TIMER_UNIT" 2>/dev/null || true
+sudo systemctl stop "$IDLE_TIMER_UNIT" 2>/dev/null || true
+sudo systemctl stop "$LOCKS_UNIT" 2>/dev/null || true
 sudo systemctl stop "${TEMPLATE_UNIT%.*}@default.service" 2>/dev/null || true
 
 echo "Disabling timer..."
 sudo systemctl disable "$TIMER_UNIT" 2>/dev/null || true
+sudo systemctl disable "$IDLE_TIMER_UNIT" 2>/dev/null || true
+sudo systemctl disable "$LOCKS_UNIT" 2>/dev/null || true
 
 echo "Removing systemd unit files..."
 sudo rm -f "/etc/systemd/system/$TIMER_UNIT"
 sudo rm -f "/etc/systemd/system/$TEMPLATE_UNIT"
+sudo rm -f "/etc/systemd/system/$IDLE_TIMER_UNIT"
+sudo rm -f "/etc/systemd/system/$IDLE_SERVICE_UNIT"
+sudo rm -f "/etc/systemd/system/$LOCKS_UNIT"
 sudo systemctl daemon-reload
 
 echo "Removing installation directory..."
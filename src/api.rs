@@ -1,110 +1,396 @@
 use reqwest::{Client, header, StatusCode};
+use serde::Deserialize;
 use serde_json::{json, Value};
 use anyhow::{anyhow, Result};
+use crate::calendar::DayType;
 use crate::config::{AdminConfig, UserConfig, save_user_config, initial_setup_and_login};
+use crate::error::CollectorError;
+use crate::http::build_http_client;
+use crate::metrics::{run_metrics, RunMetrics};
+use crate::retry::{next_delay, retry_after, RunDeadline};
 use std::fmt::Debug;
-use tokio::time::{sleep, Duration};
+use std::io::IsTerminal;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
 
 // === CONSTANTS ===
 const TABLE_NAME: &str = "daily_work_span";
-const MAX_RETRIES: u8 = 3;
+
+/// Set (to any value) to never fall back to the interactive setup prompts, even on a terminal.
+pub const NONINTERACTIVE_ENV: &str = "AVADHI_NONINTERACTIVE";
 
 // === DATA STRUCTURE ===
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct WorkSpanData {
     pub date: String,
     pub total_span_minutes: i32,
     pub total_span: String,
     pub first_boot: String,
     pub last_shutdown: String,
+    /// Minutes of idle/locked time already subtracted from total_span_minutes (local only, not posted).
+    pub excluded_minutes: i32,
+    /// The day was changed by a manual override (`adjust`).
+    pub adjusted: bool,
+    /// Annotation from the override, if any.
+    pub note: Option<String>,
+    /// Workday, weekend or holiday according to the [schedule].
+    pub day_type: DayType,
+    /// Minutes expected on this day by the schedule (0 on weekends and holidays).
+    pub expected_minutes: i32,
+    /// Actual minus expected minutes after the [balance] rounding and caps.
+    pub deviation_minutes: i32,
+    /// Running sum of deviations since Monday of this ISO week (inclusive).
+    pub week_balance_minutes: i32,
+    /// Running sum of deviations since the 1st of this month (inclusive).
+    pub month_balance_minutes: i32,
 }
 
-// === API CALLS ===
+/// A non-retryable HTTP failure from Supabase. Kept typed so callers can record the status code.
+#[derive(Debug, thiserror::Error)]
+#[error("Supabase API failed with status {status}: {body}")]
+pub struct ApiStatusError {
+    pub status: StatusCode,
+    pub body: String,
+    /// The `Retry-After` header, if the server sent one.
+    pub retry_after: Option<std::time::Duration>,
+}
 
-/// Refreshes the Access Token using the stored Refresh Token.
-async fn refresh_access_token(admin_config: &AdminConfig, user_config: &mut UserConfig) -> Result<()> {
-    println!("\nAttempting to refresh expired access token...");
+/// A row of the work span table as returned by PostgREST (only the compared columns).
+#[derive(Debug, Clone, Deserialize)]
+pub struct RemoteWorkSpan {
+    pub date: String,
+    pub first_boot: String,
+    pub last_shutdown: String,
+    pub total_span_minutes: i64,
+    pub total_span: String,
+    #[serde(default)]
+    pub adjusted: bool,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub day_type: Option<String>,
+    #[serde(default)]
+    pub expected_minutes: Option<i64>,
+    #[serde(default)]
+    pub deviation_minutes: Option<i64>,
+    #[serde(default)]
+    pub week_balance_minutes: Option<i64>,
+    #[serde(default)]
+    pub month_balance_minutes: Option<i64>,
+}
 
-    let refresh_token = user_config.refresh_token.as_ref()
-        .ok_or_else(|| anyhow!("Refresh token is missing. Cannot refresh."))?;
-    let supabase_url = admin_config.supabase_url.as_ref()
-        .ok_or_else(|| anyhow!("Admin config error: Supabase URL is missing."))?;
-    let supabase_anon_key = admin_config.supabase_anon_key.as_ref()
-        .ok_or_else(|| anyhow!("Admin config error: Supabase Anon Key is missing."))?;
+/// Builds the JSON body sent to the work span table for one day.
+pub fn build_payload(user_id: &str, data: &WorkSpanData) -> Value {
+    json!({
+        "user_id": user_id,
+        "date": data.date,
+        "total_span_minutes": data.total_span_minutes,
+        "total_span": data.total_span,
+        "first_boot": data.first_boot,
+        "last_shutdown": data.last_shutdown,
+        "adjusted": data.adjusted,
+        "note": data.note,
+        "day_type": data.day_type.as_str(),
+        "expected_minutes": data.expected_minutes,
+        "deviation_minutes": data.deviation_minutes,
+        "week_balance_minutes": data.week_balance_minutes,
+        "month_balance_minutes": data.month_balance_minutes,
+    })
+}
 
-    let url = format!(
-        "{}/auth/v1/token?grant_type=refresh_token&apikey={}",
-        supabase_url,
-        supabase_anon_key
-    );
+// === CLIENT ===
 
-    let client = Client::new();
+/// A fresh access/refresh token pair returned by the GoTrue token endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+}
 
-    let payload = json!({ "refresh_token": refresh_token, });
+/// Thin client for the Supabase backend: the work span table (PostgREST) and the token endpoint (GoTrue).
+///
+/// Every method performs exactly one request. Non-success statuses are returned as `ApiStatusError`,
+/// unreachable backends as `CollectorError::Network`.
+/// Retries, token refresh and persisting tokens are left to the caller (see `post_work_span`).
+#[derive(Debug, Clone)]
+pub struct SupabaseClient {
+    url: String,
+    anon_key: String,
+    http: Client,
+    /// Deadline of the run this client serves. Retry waits never cross it.
+    deadline: Option<RunDeadline>,
+}
 
-    let res = client.post(&url)
-        .header(header::CONTENT_TYPE, "application/json")
-        .json(&payload)
-        .send()
-        .await?;
+async fn status_error(res: reqwest::Response) -> anyhow::Error {
+    let status = res.status();
+    let retry_after = retry_after(res.headers());
+    let body = res.text().await.unwrap_or_else(|_| String::from("No response body"));
+    ApiStatusError { status, body, retry_after }.into()
+}
 
-    if res.status().is_success() {
-        let body_result: Result<Value, _> = res.json().await;
+fn network_error(error: reqwest::Error) -> CollectorError {
+    CollectorError::Network(error.to_string())
+}
 
-        let body = match body_result {
-            Ok(b) => b,
-            Err(e) => return Err(anyhow!("Failed to parse successful refresh response body: {}", e)),
-        };
+impl SupabaseClient {
+    pub fn new(url: impl Into<String>, anon_key: impl Into<String>, http: Client) -> Self {
+        SupabaseClient {
+            url: url.into().trim_end_matches('/').to_string(),
+            anon_key: anon_key.into(),
+            http,
+            deadline: None,
+        }
+    }
+
+    /// Bounds the retry waits of `post_work_span` by the run's deadline.
+    pub fn with_deadline(mut self, deadline: Option<RunDeadline>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Builds a client from `supabase_url`, `supabase_anon_key` and `[http]` in Config.toml.
+    /// Build it once per run and pass it around; the connection pool is reused across requests.
+    pub fn from_admin_config(admin_config: &AdminConfig) -> Result<Self> {
+        let supabase_url = admin_config.supabase_url.as_ref()
+            .ok_or_else(|| anyhow!("Admin config error: Supabase URL is missing."))?;
+        let supabase_anon_key = admin_config.supabase_anon_key.as_ref()
+            .ok_or_else(|| anyhow!("Admin config error: Supabase Anon Key is missing."))?;
+        Ok(SupabaseClient::new(supabase_url.as_str(), supabase_anon_key.as_str(), build_http_client(&admin_config.http)?))
+    }
+
+    fn table_url(&self) -> String {
+        format!("{}/rest/v1/{}", self.url, TABLE_NAME)
+    }
+
+    /// GETs `/auth/v1/settings` (public GoTrue endpoint). Any status is returned as-is.
+    pub async fn auth_settings(&self) -> Result<(StatusCode, String)> {
+        let res = self.http
+            .get(format!("{}/auth/v1/settings", self.url))
+            .header("apikey", self.anon_key.as_str())
+            .send()
+            .await
+            .map_err(network_error)?;
+
+        let status = res.status();
+        let body = res.text().await.unwrap_or_default();
+        Ok((status, body))
+    }
+
+    /// Authenticated, read-only GET of at most one work span row. Any status is returned as-is.
+    pub async fn probe_work_span_read(&self, access_token: &str) -> Result<(StatusCode, String)> {
+        let res = self.http
+            .get(format!("{}?select=date&limit=1", self.table_url()))
+            .header("apikey", self.anon_key.as_str())
+            .header(header::AUTHORIZATION, format!("Bearer {}", access_token))
+            .send()
+            .await
+            .map_err(network_error)?;
+
+        let status = res.status();
+        let body = res.text().await.unwrap_or_default();
+        Ok((status, body))
+    }
+
+    /// GETs the user's work span rows with `from <= date <= to` (YYYY-MM-DD), ordered by date.
+    pub async fn fetch_work_spans(&self, access_token: &str, user_id: &str, from: &str, to: &str) -> Result<Vec<RemoteWorkSpan>> {
+        let query = [
+            ("select", "date,first_boot,last_shutdown,total_span_minutes,total_span,adjusted,note,day_type,expected_minutes,deviation_minutes,week_balance_minutes,month_balance_minutes".to_string()),
+            ("user_id", format!("eq.{}", user_id)),
+            ("date", format!("gte.{}", from)),
+            ("date", format!("lte.{}", to)),
+            ("order", "date.asc".to_string()),
+        ];
+
+        let res = self.http
+            .get(self.table_url())
+            .query(&query)
+            .header("apikey", self.anon_key.as_str())
+            .header(header::AUTHORIZATION, format!("Bearer {}", access_token))
+            .send()
+            .await
+            .map_err(network_error)?;
+
+        if !res.status().is_success() {
+            return Err(status_error(res).await);
+        }
+        res.json::<Vec<RemoteWorkSpan>>().await
+            .map_err(|e| anyhow!("Failed to parse work span rows: {}", e))
+    }
+
+    /// Upserts one day (`Prefer: resolution=merge-duplicates`). Returns the success status.
+    pub async fn upsert_work_span(&self, access_token: &str, user_id: &str, data: &WorkSpanData) -> Result<StatusCode> {
+        let res = self.http
+            .post(self.table_url())
+            .header(header::AUTHORIZATION, format!("Bearer {}", access_token))
+            .header("apikey", self.anon_key.as_str())
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("Prefer", "resolution=merge-duplicates")
+            .json(&build_payload(user_id, data))
+            .send()
+            .await
+            .map_err(network_error)?;
+
+        if !res.status().is_success() {
+            return Err(status_error(res).await);
+        }
+        Ok(res.status())
+    }
+
+    /// Exchanges a refresh token for a new token pair. Refresh tokens are single-use.
+    pub async fn refresh_session(&self, refresh_token: &str) -> Result<SessionTokens> {
+        let res = self.http
+            .post(format!("{}/auth/v1/token?grant_type=refresh_token", self.url))
+            .header("apikey", self.anon_key.as_str())
+            .header(header::CONTENT_TYPE, "application/json")
+            .json(&json!({ "refresh_token": refresh_token }))
+            .send()
+            .await
+            .map_err(network_error)?;
+
+        if !res.status().is_success() {
+            return Err(status_error(res).await);
+        }
 
-        // Ensure both tokens are present before updating config
-        let new_access_token = body["access_token"].as_str().map(|s| s.to_string());
-        let new_refresh_token = body["refresh_token"].as_str().map(|s| s.to_string());
+        let body: Value = res.json().await
+            .map_err(|e| anyhow!("Failed to parse successful refresh response body: {}", e))?;
 
-        if let (Some(new_access), Some(new_refresh)) = (new_access_token, new_refresh_token) {
+        // Ensure both tokens are present before anyone replaces the stored ones
+        match (body["access_token"].as_str(), body["refresh_token"].as_str()) {
+            (Some(access_token), Some(refresh_token)) => Ok(SessionTokens {
+                access_token: access_token.to_string(),
+                refresh_token: refresh_token.to_string(),
+            }),
+            // This is a successful status but a bad payload (e.g., tokens missing)
+            _ => Err(anyhow!("Token refresh failed: successful response was malformed (missing tokens). Body: {:?}", body)),
+        }
+    }
+}
+
+// === API CALLS ===
+
+/// GETs `/auth/v1/settings` (public GoTrue endpoint). Used by `doctor` to check reachability.
+pub async fn fetch_auth_settings(admin_config: &AdminConfig) -> Result<(StatusCode, String)> {
+    SupabaseClient::from_admin_config(admin_config)?.auth_settings().await
+}
+
+/// Performs an authenticated, read-only GET against the work span table (one row at most).
+/// Used by `doctor` to verify the access token and the SELECT policy without writing anything.
+pub async fn probe_work_span_read(admin_config: &AdminConfig, user_config: &UserConfig) -> Result<(StatusCode, String)> {
+    let client = SupabaseClient::from_admin_config(admin_config)?;
+    let access_token = user_config.access_token.as_ref()
+        .ok_or_else(|| anyhow!("Access token is missing."))?;
+    client.probe_work_span_read(access_token).await
+}
+
+/// GETs the user's work span rows with `from <= date <= to` via PostgREST filters.
+/// An expired access token is refreshed once; this never falls back to interactive setup.
+pub async fn fetch_work_spans(client: &SupabaseClient, user_config: &mut UserConfig, from: &str, to: &str) -> Result<Vec<RemoteWorkSpan>> {
+    let user_id = user_config.user_id.clone()
+        .ok_or_else(|| anyhow!("User ID is missing."))?;
+
+    let mut refreshed = false;
+    loop {
+        let access_token = user_config.access_token.clone()
+            .ok_or_else(|| anyhow!("Access token is missing."))?;
+
+        match client.fetch_work_spans(&access_token, &user_id, from, to).await {
+            Err(e) if !refreshed && api_status(&e) == Some(StatusCode::UNAUTHORIZED) => {
+                warn!("API Error: Token unauthorized or expired (401). Attempting refresh.");
+                refresh_access_token(client, user_config).await?;
+                refreshed = true;
+            },
+            result => return result.map_err(classify_error),
+        }
+    }
+}
+
+/// Turns a raw `ApiStatusError` into the matching `CollectorError`. Other errors pass through.
+fn classify_error(error: anyhow::Error) -> anyhow::Error {
+    match error.downcast::<ApiStatusError>() {
+        Ok(e) if e.status == StatusCode::UNAUTHORIZED => CollectorError::AuthExpired(e.to_string()).into(),
+        Ok(e) => CollectorError::from_status(e).into(),
+        Err(e) => e,
+    }
+}
+
+/// The HTTP status of a failed request, if the server answered at all.
+pub fn api_status(error: &anyhow::Error) -> Option<StatusCode> {
+    error.chain().find_map(|e| e.downcast_ref::<ApiStatusError>()
+        .or_else(|| e.downcast_ref::<CollectorError>().and_then(CollectorError::api_error)))
+        .map(|e| e.status)
+}
+
+/// Refreshes the Access Token using the stored Refresh Token and saves the new pair immediately.
+async fn refresh_access_token(client: &SupabaseClient, user_config: &mut UserConfig) -> Result<()> {
+    info!("Attempting to refresh expired access token...");
+    RunMetrics::inc(&run_metrics().token_refreshes);
+
+    let refresh_token = user_config.refresh_token.clone()
+        .ok_or_else(|| anyhow!("Refresh token is missing. Cannot refresh."))?;
+
+    match client.refresh_session(&refresh_token).await {
+        Ok(tokens) => {
             // CRITICAL: Update in-memory config
-            user_config.access_token = Some(new_access);
-            user_config.refresh_token = Some(new_refresh);
+            user_config.access_token = Some(tokens.access_token);
+            user_config.refresh_token = Some(tokens.refresh_token);
 
             // CRITICAL: Immediately save new tokens to disk (single-use token protection)
             save_user_config(user_config);
-            println!("Tokens successfully refreshed and saved.");
+            info!("Tokens successfully refreshed and saved.");
             Ok(())
-        } else {
-            // This is a successful status but a bad payload (e.g., tokens missing)
-            Err(anyhow!("Token refresh failed: successful response was malformed (missing tokens). Body: {:?}", body))
-        }
-    } else {
-        let status = res.status();
-        let body = res.text().await.unwrap_or_else(|_| String::from("No response body"));
-        eprintln!("Refresh API Error: Status {} - Response: {}", status, body);
-
-        // Check for the specific fatal error
-        if body.contains("refresh_token_already_used") {
-            // If the single-use token was consumed, automatic recovery is impossible.
-            eprintln!("FATAL: Refresh token consumed. Manual re-authentication is required.");
-            // We return an error, which will be caught in post_work_span, leading to initial_setup_and_login.
-            Err(anyhow!("Refresh token consumed (Already Used)."))
-        } else {
-            Err(anyhow!("Failed to refresh token: Status {}", status))
-        }
+        },
+        Err(e) => match e.downcast_ref::<ApiStatusError>() {
+            Some(ApiStatusError { status, body, .. }) => {
+                error!(status = status.as_u16(), "Refresh API Error: Status {} - Response: {}", status, body);
+
+                // Check for the specific fatal error
+                if body.contains("refresh_token_already_used") {
+                    // If the single-use token was consumed, automatic recovery is impossible.
+                    error!("Refresh token consumed. Manual re-authentication is required.");
+                    // We return an error, which will be caught in post_work_span, leading to initial_setup_and_login.
+                    Err(CollectorError::RefreshTokenConsumed.into())
+                } else if status.is_client_error() {
+                    Err(CollectorError::AuthExpired(format!("Failed to refresh token: Status {}", status)).into())
+                } else {
+                    Err(CollectorError::from_status(ApiStatusError { status: *status, body: body.clone(), retry_after: None }).into())
+                }
+            },
+            None => Err(e),
+        },
     }
 }
 
 
+/// Falls back to the interactive setup prompts, but only if someone can answer them.
+/// Under systemd stdin is not a terminal (and scripts or tests may set AVADHI_NONINTERACTIVE),
+/// so `error` is returned instead of saving empty tokens.
+fn interactive_setup(admin_config: &AdminConfig, user_config: &mut UserConfig, error: CollectorError) -> Result<()> {
+    if !std::io::stdin().is_terminal() || std::env::var_os(NONINTERACTIVE_ENV).is_some() {
+        return Err(error.into());
+    }
+    initial_setup_and_login(admin_config, user_config, None);
+    Ok(())
+}
+
 /// Posts the work span data, handling token expiration with a refresh attempt.
-pub async fn post_work_span(data: WorkSpanData, admin_config: &AdminConfig, user_config: &mut UserConfig) -> Result<()> {
+/// Returns the HTTP status of the successful response.
+#[tracing::instrument(skip_all, fields(date = %data.date))]
+pub async fn post_work_span(data: WorkSpanData, client: &SupabaseClient, admin_config: &AdminConfig, user_config: &mut UserConfig) -> Result<StatusCode> {
 
     let data_to_post = data;
-    let mut retries = 0;
+    let mut attempts = 0;
+    // One token refresh (or re-authentication) per post. A second 401 means the new token is rejected as
+    // well (revoked user, wrong project keys), and refreshing again would only burn refresh tokens.
+    let mut refreshed = false;
 
     loop {
         // ... (1. Authentication Check & Config Retrieval - Unchanged)
         let access_token = match user_config.access_token.as_ref() {
             Some(token) => token,
             None => {
-                eprintln!("Access token missing. Cannot proceed with posting data. Running initial user setup.");
+                warn!("Access token missing. Cannot proceed with posting data. Running initial user setup.");
                 // === FIX 1: Pass None for the date when tokens are missing ===
-                initial_setup_and_login(admin_config, user_config, None);
+                interactive_setup(admin_config, user_config, CollectorError::ConfigMissing("access token is missing".to_string()))?;
                 // ============================================================
                 if user_config.access_token.is_none() {
                     return Err(anyhow!("Authentication failed and tokens are still missing after setup."));
@@ -116,107 +402,70 @@ pub async fn post_work_span(data: WorkSpanData, admin_config: &AdminConfig, user
         let user_id = match user_config.user_id.as_ref() {
             Some(id) => id,
             None => {
-                eprintln!("User ID missing in config. Running initial user setup to collect User ID.");
+                warn!("User ID missing in config. Running initial user setup to collect User ID.");
                 // === FIX 2: Pass None for the date when User ID is missing ===
-                initial_setup_and_login(admin_config, user_config, None);
+                interactive_setup(admin_config, user_config, CollectorError::ConfigMissing("user ID is missing".to_string()))?;
                 // ============================================================
                 continue;
             }
         };
 
-        let supabase_url = match admin_config.supabase_url.as_ref() {
-            Some(url) => url,
-            None => return Err(anyhow!("Admin config error: Supabase URL is missing. Cannot post data."))
-        };
-        let supabase_anon_key = match admin_config.supabase_anon_key.as_ref() {
-            Some(key) => key,
-            None => return Err(anyhow!("Admin config error: Supabase Anon Key is missing. Cannot post data."))
+        info!(attempt = attempts + 1, "Attempting to post data to Supabase (Attempt {})...", attempts + 1);
+
+        // 2. Send Request
+        let error = match client.upsert_work_span(access_token, user_id, &data_to_post).await {
+            Ok(s) => {
+                info!(status = s.as_u16(), "Successfully posted data. Status: {}", s);
+                return Ok(s);
+            },
+            Err(e) => e,
         };
-        // ------------------------------------------
-
-        // 2. Prepare Request (UNCHANGED)
-        let client = Client::new();
-        let url = format!("{}/rest/v1/{}", supabase_url, TABLE_NAME);
-
-        let auth_value = format!("Bearer {}", access_token);
-
-        let mut headers = header::HeaderMap::new();
-
-        headers.insert(
-            header::AUTHORIZATION,
-            header::HeaderValue::from_str(&auth_value).map_err(|e| anyhow!("Invalid token header: {}", e))?,
-        );
-        headers.insert(
-            "apikey",
-            header::HeaderValue::from_str(supabase_anon_key).map_err(|e| anyhow!("Invalid apikey header: {}", e))?,
-        );
-        headers.insert(
-            header::CONTENT_TYPE,
-            header::HeaderValue::from_static("application/json"),
-        );
-        headers.insert(
-            "Prefer",
-            header::HeaderValue::from_static("resolution=merge-duplicates"),
-        );
-
-        // --- Payload with user_id ---
-        let payload = json!({
-            "user_id": user_id,
-            "date": data_to_post.date,
-            "total_span_minutes": data_to_post.total_span_minutes,
-            "total_span": data_to_post.total_span,
-            "first_boot": data_to_post.first_boot,
-            "last_shutdown": data_to_post.last_shutdown,
-        });
-
-        println!("Attempting to post data to Supabase (Attempt {})...", retries + 1);
-
-        // 3. Send Request (UNCHANGED)
-        let res = match client.post(&url)
-            .headers(headers)
-            .json(&payload)
-            .send()
-            .await
-        {
-            Ok(r) => r,
+
+        // 3. Process Failure (Retry logic)
+        match error.downcast::<ApiStatusError>() {
+            // Handle network error (e.g., DNS failure, connection reset)
             Err(e) => {
-                // Handle network error (e.g., DNS failure, connection reset)
-                eprintln!("Network Error: {}. Retrying...", e);
-                retries += 1;
-                if retries >= MAX_RETRIES {
-                    return Err(anyhow!("Failed to post data after {} network retries: {}", MAX_RETRIES, e));
+                attempts += 1;
+                match next_delay(&admin_config.retry, attempts, None, client.deadline) {
+                    Ok(delay) => {
+                        warn!("Network Error: {}. Retrying in {:.1}s...", e, delay.as_secs_f64());
+                        RunMetrics::inc(&run_metrics().http_retries);
+                        sleep(delay).await;
+                        continue;
+                    },
+                    Err(reason) => {
+                        error!("Failed to post data {}: {}", reason, e);
+                        return Err(e.context(format!("Failed to post data {}", reason)));
+                    }
                 }
-                sleep(Duration::from_secs(2u64.pow(retries as u32))).await;
-                continue;
-            }
-        };
+            },
 
-        // 4. Process Response (Retry logic)
-        match res.status() {
-            s if s.is_success() => {
-                println!("Successfully posted data. Status: {}", s);
-                return Ok(());
+            Ok(e) if e.status == StatusCode::UNAUTHORIZED && refreshed => {
+                error!("API Error: The refreshed access token was rejected as well (401). Giving up.");
+                return Err(CollectorError::AuthExpired(format!("the refreshed access token was rejected: {}", e.body)).into());
             },
 
-            StatusCode::UNAUTHORIZED => {
-                eprintln!("API Error: Token unauthorized or expired (401). Attempting refresh.");
-                retries = 0; // Reset retries after attempting auth resolution
+            Ok(ApiStatusError { status: StatusCode::UNAUTHORIZED, .. }) => {
+                warn!("API Error: Token unauthorized or expired (401). Attempting refresh.");
+                refreshed = true;
 
-                match refresh_access_token(admin_config, user_config).await {
+                match refresh_access_token(client, user_config).await {
                     Ok(_) => {
-                        println!("Token refreshed successfully. Retrying data post...");
+                        info!("Token refreshed successfully. Retrying data post...");
                         continue;
                     },
                     Err(e) => {
-                        eprintln!("Automatic token refresh failed: {}. Attempting manual re-authentication.", e);
+                        warn!("Automatic token refresh failed: {}. Attempting manual re-authentication.", e);
 
                         // Only run manual setup if the auto-refresh failed.
+                        let error = e.downcast::<CollectorError>()
+                            .unwrap_or_else(|e| CollectorError::AuthExpired(e.to_string()));
                         // === FIX 3: Pass None for the date during manual re-authentication ===
-                        initial_setup_and_login(admin_config, user_config, None);
+                        interactive_setup(admin_config, user_config, error)?;
                         // ===================================================================
 
                         if user_config.access_token.is_some() {
-                            println!("Manual re-authentication succeeded. Retrying...");
+                            info!("Manual re-authentication succeeded. Retrying...");
                             continue;
                         } else {
                             // If the user fails to provide new tokens, we must exit.
@@ -226,27 +475,39 @@ pub async fn post_work_span(data: WorkSpanData, admin_config: &AdminConfig, user
                 }
             },
 
-            // Handle transient server errors (5xx)
-            s if s.is_server_error() => {
-                let body = res.text().await.unwrap_or_else(|_| String::from("No response body"));
-                eprintln!("Server Error: Status {} - Response: {}. Retrying...", s, body);
-                retries += 1;
-                if retries >= MAX_RETRIES {
-                    return Err(anyhow!("Failed to post data after {} server retries: Status {}", MAX_RETRIES, s));
+            // Handle transient server errors (5xx) and rate limiting (429).
+            // 429 and 503 may say when to come back; that replaces the backoff.
+            Ok(e) if e.status.is_server_error() || e.status == StatusCode::TOO_MANY_REQUESTS => {
+                attempts += 1;
+                let retry_after = e.retry_after
+                    .filter(|_| matches!(e.status, StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE));
+                match next_delay(&admin_config.retry, attempts, retry_after, client.deadline) {
+                    Ok(delay) => {
+                        warn!(status = e.status.as_u16(), "Server Error: Status {} - Response: {}. Retrying in {:.1}s{}...",
+                            e.status, e.body, delay.as_secs_f64(), if retry_after.is_some() { " (Retry-After)" } else { "" });
+                        RunMetrics::inc(&run_metrics().http_retries);
+                        sleep(delay).await;
+                        continue;
+                    },
+                    Err(reason) => {
+                        error!(status = e.status.as_u16(), "Failed to post data {}", reason);
+                        return Err(anyhow::Error::new(CollectorError::from_status(e))
+                            .context(format!("Failed to post data {}", reason)));
+                    }
                 }
-                sleep(Duration::from_secs(2u64.pow(retries as u32))).await;
-                continue;
             }
 
             // Handle other client/permanent errors (4xx, excluding 401)
-            s => {
-                let body = res.text().await.unwrap_or_else(|_| String::from("No response body"));
-                eprintln!("API Error: Status {} - Response: {}", s, body);
-                if body.contains("policy") || body.contains("permission") {
-                     eprintln!("HINT: This 4xx error (Status {}) strongly suggests a Row Level Security (RLS) policy issue on the '{}' table. Please ensure authenticated users have INSERT permission, and the `user_id` in the payload matches `auth.uid()`.", s, TABLE_NAME);
+            Ok(e) => {
+                error!(status = e.status.as_u16(), "API Error: Status {} - Response: {}", e.status, e.body);
+                if e.body.contains("policy") || e.body.contains("permission") {
+                     warn!("HINT: This 4xx error (Status {}) strongly suggests a Row Level Security (RLS) policy issue on the '{}' table. Please ensure authenticated users have INSERT permission, and the `user_id` in the payload matches `auth.uid()`.", e.status, TABLE_NAME);
+                }
+                if e.body.contains("Could not find the") && e.body.contains("column") {
+                    warn!("HINT: The '{}' table lacks a column this collector posts. Run daily_work_span_upgrade.sql on the database (see README, Upgrading the database).", TABLE_NAME);
                 }
-                return Err(anyhow!("Supabase API failed with status {}: {}", s, body));
+                return Err(CollectorError::from_status(e).into());
             }
         }
     }
-}
\ No newline at end of file
+}
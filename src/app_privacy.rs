@@ -0,0 +1,170 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use anyhow::{anyhow, Result};
+
+use crate::app_usage::{get_all_active_apps, AppProcess};
+use crate::config::{AppNameMode, AppUsagePrivacyConfig};
+
+/// Category used for processes that are not listed in any `[app_usage.categories]` entry.
+const UNCATEGORIZED: &str = "other";
+
+/// One aggregated app-usage row, exactly as it would be uploaded.
+#[derive(Debug, Clone, Serialize)]
+pub struct AppUsageRecord {
+    /// Process name, salted hash of it, or None in categories-only mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    pub category: String,
+    pub process_count: u32,
+    pub cpu_seconds: f64,
+}
+
+/// Returns the configured category for a process name, or "other". Categories are searched in name order.
+fn categorize(command: &str, privacy: &AppUsagePrivacyConfig) -> String {
+    privacy.categories.iter()
+        .find(|(_, names)| names.iter().any(|n| n.eq_ignore_ascii_case(command)))
+        .map(|(category, _)| category.clone())
+        .unwrap_or_else(|| UNCATEGORIZED.to_string())
+}
+
+/// Applies the allowlist first, then the denylist. Matching is case-insensitive on the process name.
+fn is_reportable(command: &str, privacy: &AppUsagePrivacyConfig) -> bool {
+    let allowed = privacy.allowlist.is_empty()
+        || privacy.allowlist.iter().any(|n| n.eq_ignore_ascii_case(command));
+    let denied = privacy.denylist.iter().any(|n| n.eq_ignore_ascii_case(command));
+    allowed && !denied
+}
+
+/// Hex-encoded SHA-256 of `salt || name`.
+fn hash_name(name: &str, salt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(name.as_bytes());
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Filters, categorizes and anonymizes raw processes according to the privacy config.
+/// Rows are aggregated per (name, category) so individual PIDs are never exposed.
+pub fn apply_privacy(processes: &[AppProcess], privacy: &AppUsagePrivacyConfig) -> Result<Vec<AppUsageRecord>> {
+    let salt = match privacy.name_mode {
+        AppNameMode::Hashed => Some(privacy.hash_salt.as_deref()
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| anyhow!("app_usage.name_mode is 'hashed' but app_usage.hash_salt is not set in Config.toml."))?),
+        _ => None,
+    };
+
+    // Key: (reported name, category) | Value: (process count, cpu seconds)
+    let mut aggregated: BTreeMap<(Option<String>, String), (u32, f64)> = BTreeMap::new();
+
+    for process in processes.iter().filter(|p| is_reportable(&p.command, privacy)) {
+        let category = categorize(&process.command, privacy);
+        let app = match privacy.name_mode {
+            AppNameMode::CategoriesOnly => None,
+            AppNameMode::Hashed => salt.map(|s| hash_name(&process.command, s)),
+            AppNameMode::Names => Some(process.command.clone()),
+        };
+
+        let entry = aggregated.entry((app, category)).or_insert((0, 0.0));
+        entry.0 += 1;
+        entry.1 += process.total_cpu_time_seconds();
+    }
+
+    Ok(aggregated.into_iter()
+        .map(|((app, category), (process_count, cpu_seconds))| AppUsageRecord {
+            app,
+            category,
+            process_count,
+            cpu_seconds,
+        })
+        .collect())
+}
+
+/// Collects app usage from /proc and returns only what the privacy rules allow to be uploaded.
+pub fn collect_app_usage(privacy: &AppUsagePrivacyConfig) -> Result<Vec<AppUsageRecord>> {
+    apply_privacy(&get_all_active_apps(), privacy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlapping_categories_resolve_to_the_alphabetically_first() {
+        let mut privacy = AppUsagePrivacyConfig::default();
+        privacy.categories.insert("editor".to_string(), vec!["code".to_string(), "vim".to_string()]);
+        privacy.categories.insert("browser".to_string(), vec!["Firefox".to_string(), "code".to_string()]);
+
+        assert_eq!(categorize("code", &privacy), "browser");
+        assert_eq!(categorize("firefox", &privacy), "browser");
+        assert_eq!(categorize("vim", &privacy), "editor");
+        assert_eq!(categorize("bash", &privacy), UNCATEGORIZED);
+    }
+
+    fn process(pid: u32, command: &str, utime: u64) -> AppProcess {
+        AppProcess { pid, command: command.to_string(), utime, stime: 0, start_time_jiffies: 0 }
+    }
+
+    fn processes() -> Vec<AppProcess> {
+        vec![process(1, "firefox", 300), process(2, "Firefox", 100), process(3, "code", 50), process(4, "ssh-agent", 10)]
+    }
+
+    fn privacy(name_mode: AppNameMode) -> AppUsagePrivacyConfig {
+        let mut privacy = AppUsagePrivacyConfig { name_mode, ..AppUsagePrivacyConfig::default() };
+        privacy.categories.insert("browser".to_string(), vec!["firefox".to_string()]);
+        privacy.categories.insert("editor".to_string(), vec!["code".to_string()]);
+        privacy
+    }
+
+    fn rows(records: &[AppUsageRecord]) -> Vec<(Option<&str>, &str, u32)> {
+        records.iter().map(|r| (r.app.as_deref(), r.category.as_str(), r.process_count)).collect()
+    }
+
+    #[test]
+    fn allowlist_reports_only_listed_names() {
+        let privacy = AppUsagePrivacyConfig { allowlist: vec!["FIREFOX".to_string()], ..privacy(AppNameMode::Names) };
+
+        let records = apply_privacy(&processes(), &privacy).unwrap();
+
+        // Names are reported as seen; matching ignores case.
+        assert_eq!(rows(&records), [(Some("Firefox"), "browser", 1), (Some("firefox"), "browser", 1)]);
+    }
+
+    #[test]
+    fn denylist_applies_after_the_allowlist() {
+        let privacy = AppUsagePrivacyConfig {
+            allowlist: vec!["code".to_string(), "ssh-agent".to_string()],
+            denylist: vec!["ssh-agent".to_string()],
+            ..privacy(AppNameMode::Names)
+        };
+
+        let records = apply_privacy(&processes(), &privacy).unwrap();
+
+        assert_eq!(rows(&records), [(Some("code"), "editor", 1)]);
+    }
+
+    #[test]
+    fn hashed_mode_replaces_names_with_a_salted_hash() {
+        let privacy = AppUsagePrivacyConfig { hash_salt: Some("pepper".to_string()), ..privacy(AppNameMode::Hashed) };
+
+        let records = apply_privacy(&processes(), &privacy).unwrap();
+
+        assert_eq!(records.len(), 4);
+        // sha256("pepper" + "firefox")
+        assert!(records.iter().any(|r| r.app.as_deref() == Some("685fd5626030f356134f8495ca50ac830d41dd83d08a2ec536432e1c12d23b19")));
+        assert!(records.iter().all(|r| r.app.as_deref().is_some_and(|app| app.len() == 64 && app.chars().all(|c| c.is_ascii_hexdigit()))));
+
+        let without_salt = AppUsagePrivacyConfig { hash_salt: Some(String::new()), ..privacy };
+        assert!(apply_privacy(&processes(), &without_salt).is_err(), "hashing needs a salt");
+    }
+
+    #[test]
+    fn categories_only_mode_aggregates_without_names() {
+        let records = apply_privacy(&processes(), &privacy(AppNameMode::CategoriesOnly)).unwrap();
+
+        assert_eq!(rows(&records), [(None, "browser", 2), (None, "editor", 1), (None, "other", 1)]);
+        let browser = &records[0];
+        assert_eq!(browser.cpu_seconds, process(0, "", 400).total_cpu_time_seconds());
+        assert!(!serde_json::to_string(&records).unwrap().contains("\"app\""), "no app field is serialized");
+    }
+}
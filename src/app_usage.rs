@@ -28,7 +28,7 @@ impl AppProcess {
 
     /// Calculates the process uptime (since boot) in seconds.
     /// This is an estimate based on the system boot time (not measured here) and start_time_jiffies.
-    pub fn uptime_seconds(&self, boot_time_jiffies: u64) -> f64 {
+    pub fn uptime_seconds(&self, _boot_time_jiffies: u64) -> f64 {
         // start_time_jiffies is time since system boot.
         // We use it directly as the uptime for simplicity.
         (self.start_time_jiffies as f64) / (CLK_TCK as f64)
@@ -80,7 +80,7 @@ pub fn get_all_active_apps() -> Vec<AppProcess> {
 
     // Check if we are on a system that supports /proc (i.e., Linux)
     if !proc_path.exists() {
-        eprintln!("Error: /proc filesystem not found. App usage tracking not supported on this OS.");
+        tracing::warn!("/proc filesystem not found. App usage tracking not supported on this OS.");
         return processes;
     }
 
@@ -0,0 +1,149 @@
+use chrono::{Datelike, NaiveDate};
+
+use crate::api::WorkSpanData;
+use crate::config::{BalanceConfig, RoundingMode};
+
+/// "+1h 5m" / "-0h 30m" for balances and deviations.
+pub fn format_signed_minutes(minutes: i64) -> String {
+    let sign = if minutes < 0 { '-' } else { '+' };
+    let abs = minutes.abs();
+    format!("{}{}h {}m", sign, abs / 60, abs % 60)
+}
+
+fn round_to(minutes: i64, step: i64, mode: RoundingMode) -> i64 {
+    if step <= 1 {
+        return minutes;
+    }
+    let down = minutes.div_euclid(step) * step;
+    match mode {
+        RoundingMode::Down => down,
+        RoundingMode::Up if down == minutes => down,
+        RoundingMode::Up => down + step,
+        RoundingMode::Nearest if minutes - down >= step - (minutes - down) => down + step,
+        RoundingMode::Nearest => down,
+    }
+}
+
+/// Actual minus expected minutes for one day, after rounding and the daily caps.
+pub fn daily_deviation(actual_minutes: i64, expected_minutes: i64, config: &BalanceConfig) -> i64 {
+    let mut deviation = round_to(actual_minutes - expected_minutes, config.rounding_minutes, config.rounding_mode);
+    if let Some(cap) = config.max_daily_overtime_minutes {
+        deviation = deviation.min(cap.max(0));
+    }
+    if let Some(cap) = config.max_daily_undertime_minutes {
+        deviation = deviation.max(-cap.max(0));
+    }
+    deviation
+}
+
+/// Fills deviation and the running week (ISO, from Monday) and month balances of every span.
+///
+/// Only days with a span are counted: a workday without any recorded activity adds no undertime.
+/// Balances depend on earlier days, so `spans` should contain the whole history, not just one range.
+pub fn apply_balance(spans: &mut [WorkSpanData], config: &BalanceConfig) {
+    spans.sort_by(|a, b| a.date.cmp(&b.date));
+
+    let mut week: Option<(i32, u32)> = None;
+    let mut month: Option<(i32, u32)> = None;
+    let (mut week_balance, mut month_balance) = (0i64, 0i64);
+
+    for span in spans.iter_mut() {
+        let Ok(date) = NaiveDate::parse_from_str(&span.date, "%Y-%m-%d") else {
+            continue;
+        };
+
+        let iso = date.iso_week();
+        if week != Some((iso.year(), iso.week())) {
+            week = Some((iso.year(), iso.week()));
+            week_balance = 0;
+        }
+        if month != Some((date.year(), date.month())) {
+            month = Some((date.year(), date.month()));
+            month_balance = 0;
+        }
+
+        let deviation = daily_deviation(span.total_span_minutes as i64, span.expected_minutes as i64, config);
+        week_balance += deviation;
+        month_balance += deviation;
+
+        span.deviation_minutes = deviation as i32;
+        span.week_balance_minutes = week_balance as i32;
+        span.month_balance_minutes = month_balance as i32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(date: &str, actual: i32, expected: i32) -> WorkSpanData {
+        WorkSpanData {
+            date: date.to_string(),
+            total_span_minutes: actual,
+            total_span: String::new(),
+            first_boot: "09:00:00".to_string(),
+            last_shutdown: "17:00:00".to_string(),
+            excluded_minutes: 0,
+            adjusted: false,
+            note: None,
+            day_type: Default::default(),
+            expected_minutes: expected,
+            deviation_minutes: 0,
+            week_balance_minutes: 0,
+            month_balance_minutes: 0,
+        }
+    }
+
+    fn balances(spans: &[WorkSpanData]) -> Vec<(i32, i32, i32)> {
+        spans.iter().map(|s| (s.deviation_minutes, s.week_balance_minutes, s.month_balance_minutes)).collect()
+    }
+
+    #[test]
+    fn round_to_handles_negative_minutes() {
+        assert_eq!(round_to(-7, 15, RoundingMode::Nearest), 0);
+        assert_eq!(round_to(-8, 15, RoundingMode::Nearest), -15);
+        assert_eq!(round_to(-1, 15, RoundingMode::Down), -15);
+        assert_eq!(round_to(-14, 15, RoundingMode::Up), 0);
+        assert_eq!(round_to(-30, 15, RoundingMode::Up), -30);
+        assert_eq!(round_to(22, 15, RoundingMode::Nearest), 15);
+        assert_eq!(round_to(23, 15, RoundingMode::Nearest), 30);
+        assert_eq!(round_to(-7, 1, RoundingMode::Down), -7);
+    }
+
+    #[test]
+    fn daily_caps_clamp_after_rounding() {
+        let config = BalanceConfig {
+            rounding_minutes: 15,
+            rounding_mode: RoundingMode::Nearest,
+            max_daily_overtime_minutes: Some(60),
+            max_daily_undertime_minutes: Some(120),
+        };
+        assert_eq!(daily_deviation(480 + 170, 480, &config), 60);
+        assert_eq!(daily_deviation(480 + 52, 480, &config), 45);
+        assert_eq!(daily_deviation(100, 480, &config), -120);
+        assert_eq!(daily_deviation(480 - 7, 480, &config), 0);
+    }
+
+    #[test]
+    fn week_balance_restarts_on_monday() {
+        // Fri Oct 9, Sun Oct 11, Mon Oct 12 and Tue Oct 13 2026; a Sunday without work expects nothing.
+        let mut spans = vec![
+            day("2026-10-13", 420, 480),
+            day("2026-10-09", 510, 480),
+            day("2026-10-11", 60, 0),
+            day("2026-10-12", 540, 480),
+        ];
+        apply_balance(&mut spans, &BalanceConfig::default());
+
+        assert_eq!(balances(&spans), [(30, 30, 30), (60, 90, 90), (60, 60, 150), (-60, 0, 90)]);
+    }
+
+    #[test]
+    fn month_balance_restarts_on_the_first_even_mid_week() {
+        // Wed Sep 30 and Thu Oct 1 2026 share an ISO week but not a month.
+        let mut spans = vec![day("2026-09-30", 540, 480), day("2026-10-01", 450, 480)];
+        apply_balance(&mut spans, &BalanceConfig::default());
+
+        assert_eq!(balances(&spans), [(60, 60, 60), (-30, 30, -30)]);
+    }
+}
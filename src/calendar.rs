@@ -0,0 +1,244 @@
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+use crate::config::ScheduleConfig;
+
+/// How a day is classified by the work schedule.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DayType {
+    #[default]
+    Workday,
+    /// Any day that is not in working_days.
+    Weekend,
+    Holiday,
+}
+
+impl DayType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DayType::Workday => "workday",
+            DayType::Weekend => "weekend",
+            DayType::Holiday => "holiday",
+        }
+    }
+}
+
+/// The weekly schedule plus holidays, resolved from `[schedule]`.
+#[derive(Debug, Clone, Default)]
+pub struct WorkCalendar {
+    working_days: HashSet<Weekday>,
+    expected_minutes: HashMap<Weekday, i32>,
+    /// Holiday date -> name (empty if unknown).
+    holidays: BTreeMap<NaiveDate, String>,
+}
+
+fn parse_weekday(value: &str) -> Result<Weekday> {
+    value.trim().parse::<Weekday>().map_err(|_| anyhow!("Invalid weekday '{}' in [schedule] (use mon..sun)", value))
+}
+
+fn hours_to_minutes(hours: f64) -> i32 {
+    (hours * 60.0).round() as i32
+}
+
+/// Holidays from an iCalendar file: every date covered by a VEVENT. An all-day DTEND (or one at
+/// midnight) is exclusive; a timed DTEND covers its own day. DTSTARTs of VTIMEZONE rules and other
+/// components are ignored, and recurring events (RRULE) only count once.
+fn parse_ics_holidays(contents: &str) -> Result<BTreeMap<NaiveDate, String>> {
+    // Unfold continuation lines (RFC 5545 section 3.1) first.
+    let unfolded = contents.replace("\r\n ", "").replace("\r\n\t", "").replace("\n ", "").replace("\n\t", "");
+
+    // The date of a DATE or DATE-TIME value, and whether it has a time after midnight.
+    let parse_value = |value: &str| -> Result<(NaiveDate, bool)> {
+        let digits = value.get(..8).ok_or_else(|| anyhow!("Invalid date '{}' in holiday calendar", value))?;
+        let date = NaiveDate::parse_from_str(digits, "%Y%m%d").map_err(|e| anyhow!("Invalid date '{}' in holiday calendar: {}", value, e))?;
+        let after_midnight = value.get(9..15).is_some_and(|time| time != "000000");
+        Ok((date, after_midnight))
+    };
+
+    let mut holidays = BTreeMap::new();
+    let mut in_event = false;
+    let (mut start, mut end, mut name, mut recurring): (Option<NaiveDate>, Option<(NaiveDate, bool)>, String, bool) = (None, None, String::new(), false);
+    for line in unfolded.lines().map(str::trim_end) {
+        if line == "BEGIN:VEVENT" {
+            in_event = true;
+            (start, end, name, recurring) = (None, None, String::new(), false);
+            continue;
+        }
+        if !in_event {
+            continue;
+        }
+        // NAME;PARAM=...:VALUE
+        let (property, value) = line.split_once(':').unwrap_or((line, ""));
+        let property = property.split(';').next().unwrap_or_default();
+        match property {
+            "DTSTART" => start = Some(parse_value(value.trim())?.0),
+            "DTEND" => end = Some(parse_value(value.trim())?),
+            "SUMMARY" => name = value.replace("\\,", ",").replace("\\;", ";"),
+            "RRULE" => recurring = true,
+            "END" if value == "VEVENT" => {
+                in_event = false;
+                let Some(first) = start else {
+                    continue;
+                };
+                if recurring {
+                    warn!("Holiday '{}' on {} repeats (RRULE); only that first date is used. Export the calendar with expanded occurrences.", name, first);
+                }
+                let last = match end {
+                    Some((end, true)) => end,
+                    Some((end, false)) => end - Duration::days(1),
+                    None => first,
+                };
+                let mut day = first;
+                while day <= last.max(first) {
+                    holidays.insert(day, name.clone());
+                    day += Duration::days(1);
+                }
+            },
+            _ => {},
+        }
+    }
+    Ok(holidays)
+}
+
+/// Holidays from a TOML file: `holidays = ["YYYY-MM-DD", ...]`.
+fn parse_toml_holidays(contents: &str) -> Result<BTreeMap<NaiveDate, String>> {
+    #[derive(Deserialize)]
+    struct HolidayFile {
+        holidays: Vec<String>,
+    }
+    let file: HolidayFile = toml::from_str(contents)?;
+    file.holidays.iter()
+        .map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").map(|date| (date, String::new()))
+            .map_err(|e| anyhow!("Invalid holiday '{}': {}", d, e)))
+        .collect()
+}
+
+fn load_holidays_file(path: &str) -> Result<BTreeMap<NaiveDate, String>> {
+    let contents = fs::read_to_string(path).map_err(|e| anyhow!("Error reading holidays file {}: {}", path, e))?;
+    let is_ics = Path::new(path).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ics"));
+    if is_ics {
+        parse_ics_holidays(&contents)
+    } else {
+        parse_toml_holidays(&contents)
+    }
+    .map_err(|e| anyhow!("Error parsing holidays file {}: {}", path, e))
+}
+
+impl WorkCalendar {
+    pub fn from_config(config: &ScheduleConfig) -> Result<Self> {
+        let working_days = config.working_days.iter().map(|d| parse_weekday(d)).collect::<Result<HashSet<_>>>()?;
+
+        let mut expected_minutes = HashMap::new();
+        for day in &working_days {
+            expected_minutes.insert(*day, hours_to_minutes(config.expected_hours));
+        }
+        for (day, hours) in &config.expected_hours_by_day {
+            expected_minutes.insert(parse_weekday(day)?, hours_to_minutes(*hours));
+        }
+
+        let mut holidays = BTreeMap::new();
+        for date in &config.holidays {
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|e| anyhow!("Invalid holiday '{}' in [schedule]: {}", date, e))?;
+            holidays.insert(date, String::new());
+        }
+        if let Some(path) = &config.holidays_file {
+            for (date, name) in load_holidays_file(path)? {
+                let entry = holidays.entry(date).or_default();
+                if entry.is_empty() {
+                    *entry = name;
+                }
+            }
+        }
+
+        Ok(WorkCalendar { working_days, expected_minutes, holidays })
+    }
+
+    /// Like `from_config`, but a broken schedule is reported and the default schedule is used instead.
+    pub fn from_config_or_warn(config: &ScheduleConfig) -> Self {
+        WorkCalendar::from_config(config).unwrap_or_else(|e| {
+            warn!("{}. Using the default Mon-Fri, 8h schedule without holidays.", e);
+            WorkCalendar::from_config(&ScheduleConfig::default()).unwrap_or_default()
+        })
+    }
+
+    /// The day's type and the minutes expected on it (0 on weekends and holidays).
+    pub fn classify(&self, date: NaiveDate) -> (DayType, i32) {
+        if self.holidays.contains_key(&date) {
+            (DayType::Holiday, 0)
+        } else if self.working_days.contains(&date.weekday()) {
+            (DayType::Workday, self.expected_minutes.get(&date.weekday()).copied().unwrap_or(0))
+        } else {
+            (DayType::Weekend, 0)
+        }
+    }
+
+    /// The holiday's name, if `date` is a named holiday.
+    pub fn holiday_name(&self, date: NaiveDate) -> Option<&str> {
+        self.holidays.get(&date).map(String::as_str).filter(|n| !n.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, month, day).unwrap()
+    }
+
+    fn days(ics: &str) -> Vec<(NaiveDate, String)> {
+        parse_ics_holidays(ics).unwrap().into_iter().collect()
+    }
+
+    #[test]
+    fn all_day_events_end_before_dtend() {
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nDTSTART;VALUE=DATE:20261003\r\nDTEND;VALUE=DATE:20261004\r\n\
+            SUMMARY:Tag der Deutschen Einheit\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\nDTSTART;VALUE=DATE:20261224\r\n\
+            DTEND;VALUE=DATE:20261227\r\nSUMMARY:Christmas\\, Boxing Day\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+
+        assert_eq!(days(ics), [
+            (date(10, 3), "Tag der Deutschen Einheit".to_string()),
+            (date(12, 24), "Christmas, Boxing Day".to_string()),
+            (date(12, 25), "Christmas, Boxing Day".to_string()),
+            (date(12, 26), "Christmas, Boxing Day".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn timed_events_cover_the_days_they_touch() {
+        let ics = "BEGIN:VEVENT\nDTSTART;TZID=Europe/Berlin:20261102T090000\nDTEND;TZID=Europe/Berlin:20261103T120000\n\
+            SUMMARY;LANGUAGE=en:Offsite\nEND:VEVENT\nBEGIN:VEVENT\nDTSTART;TZID=Europe/Berlin:20261110T000000\n\
+            DTEND;TZID=Europe/Berlin:20261111T000000\nSUMMARY:Midnight to midnight\nEND:VEVENT\n";
+
+        let found: Vec<NaiveDate> = days(ics).into_iter().map(|(day, _)| day).collect();
+        assert_eq!(found, [date(11, 2), date(11, 3), date(11, 10)]);
+    }
+
+    #[test]
+    fn dtstart_outside_events_is_ignored() {
+        let ics = "BEGIN:VCALENDAR\nBEGIN:VTIMEZONE\nTZID:Europe/Berlin\nBEGIN:STANDARD\nDTSTART:19701025T030000\n\
+            RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\nEND:STANDARD\nEND:VTIMEZONE\nBEGIN:VEVENT\nDTSTART;VALUE=DATE:20260501\n\
+            SUMMARY:Labour Day\nEND:VEVENT\nEND:VCALENDAR\n";
+
+        assert_eq!(days(ics), [(date(5, 1), "Labour Day".to_string())]);
+    }
+
+    #[test]
+    fn recurring_events_count_once() {
+        let ics = "BEGIN:VEVENT\nDTSTART;VALUE=DATE:20260101\nRRULE:FREQ=YEARLY\nSUMMARY:New Year\nEND:VEVENT\n";
+        assert_eq!(days(ics), [(date(1, 1), "New Year".to_string())]);
+    }
+
+    #[test]
+    fn folded_lines_are_unfolded() {
+        let ics = "BEGIN:VEVENT\r\nDTSTART;VALUE=DATE:2026\r\n 1231\r\nSUMMARY:New Year's\r\n  Eve\r\nEND:VEVENT\r\n";
+        assert_eq!(days(ics), [(date(12, 31), "New Year's Eve".to_string())]);
+    }
+}
@@ -0,0 +1,700 @@
+//! One collector run end to end: sessions and gaps in, spans computed, posted and recorded.
+
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use anyhow::{anyhow, Result};
+use std::future::Future;
+use std::ops::RangeInclusive;
+use tracing::{error, info, warn, Instrument};
+
+use crate::api::{api_status, build_payload, post_work_span, SupabaseClient, WorkSpanData};
+use crate::balance::apply_balance;
+use crate::calendar::WorkCalendar;
+use crate::config::{load_user_config, save_user_config, AdminConfig, UserConfig};
+use crate::error::CollectorError;
+use crate::idle::{collect_idle_periods, idle_gaps, load_idle_log};
+use crate::locks::{collect_locked_gaps, load_lock_log};
+use crate::metrics::{run_metrics, write_run_metrics, RunMetrics};
+use crate::overrides::{load_overrides, load_overrides_or_warn, overrides_path, parse_span, save_overrides, DayOverride, Overrides};
+use crate::reconcile::{compare_spans, print_drift, reconcile_window};
+use crate::retry::RunDeadline;
+use crate::report::spans_in_range;
+use crate::sessions::{fetch_last_logs, SessionRecord, SESSION_SOURCE};
+use crate::spans::{calculate_spans, filter_data_for_posting};
+use crate::state::record_run;
+use crate::store::{history_path, merge_sessions, HistoryStore};
+
+/// Already-posted days this recent are re-posted when their recomputed span differs from what was
+/// last posted. Kept below the idle/lock log retention so old days never lose their exclusions.
+pub const REPOST_WINDOW_DAYS: i64 = 90;
+
+/// Replays the span calculation on `sessions` (e.g. from `read_sessions_file`), with the [schedule],
+/// [balance] and [span_policy] of `admin_config`. Idle/lock gaps and overrides of this machine are not
+/// applied, nothing is posted or saved, so the result depends only on the inputs.
+pub fn replay_spans(admin_config: &AdminConfig, sessions: Vec<SessionRecord>) -> Result<Vec<WorkSpanData>> {
+    let calendar = WorkCalendar::from_config_or_warn(&admin_config.schedule);
+    let mut spans = calculate_spans(sessions, &[], &Overrides::default(), &calendar, &admin_config.span_policy)?;
+    apply_balance(&mut spans, &admin_config.balance);
+    Ok(spans)
+}
+
+/// Opens the local history of `profile` and registers a run of `kind` ("run", "backfill", "reconcile")
+/// started by `trigger`. Returns None (with a warning) if the database is unusable.
+pub fn open_history(started_at: DateTime<Local>, kind: &str, trigger: &str, profile: Option<&str>) -> Option<(HistoryStore, i64)> {
+    match HistoryStore::open_profile(profile) {
+        Ok(store) => match store.begin_run(started_at, kind, trigger) {
+            Ok(run_id) => {
+                // Ties the current run/backfill/reconcile span to its `runs` row.
+                tracing::Span::current().record("run_id", run_id);
+                Some((store, run_id))
+            },
+            Err(e) => {
+                warn!("Failed to record run in local history: {}. Continuing without it.", e);
+                None
+            }
+        },
+        Err(e) => {
+            warn!("{}. Continuing without local history.", e);
+            None
+        }
+    }
+}
+
+/// Records the run's outcome and the last_posted_date it leaves behind.
+pub fn finish_history(history: Option<&(HistoryStore, i64)>, result: &Result<()>, user_config: &UserConfig) {
+    if let Some((store, run_id)) = history {
+        let error = result.as_ref().err().map(|e| format!("{:#}", e));
+        if let Err(e) = store.finish_run(*run_id, error.as_deref(), user_config.last_posted_date.as_deref()) {
+            warn!("Failed to record run outcome in local history: {}", e);
+        }
+    }
+}
+
+/// Records the token refreshes attempted since `refreshes_before`. A refresh succeeded if the access
+/// token changed; otherwise it failed and `error` (the failure of the surrounding request) explains why.
+fn record_token_refreshes(
+    history: Option<&(HistoryStore, i64)>,
+    refreshes_before: u64,
+    token_before: Option<&str>,
+    user_config: &UserConfig,
+    error: Option<&anyhow::Error>,
+) {
+    let Some((store, run_id)) = history else {
+        return;
+    };
+    let attempts = RunMetrics::get(&run_metrics().token_refreshes).saturating_sub(refreshes_before);
+    if attempts == 0 {
+        return;
+    }
+    let refreshed = user_config.access_token.as_deref() != token_before;
+    let error = error.map(|e| format!("{:#}", e));
+    let error = if refreshed { None } else { Some(error.as_deref().unwrap_or("access token unchanged")) };
+    if let Err(e) = store.record_token_refresh(*run_id, error) {
+        warn!("Failed to record token refresh in local history: {}", e);
+    }
+}
+
+/// What computing spans may change besides reading local data.
+pub enum SpanMode<'a> {
+    /// `run`, `backfill`, `reconcile`: samples idle and lock state now, records sessions and spans in the
+    /// run's history (if it is usable) and counts them in the run metrics.
+    Run(&'a mut Option<(HistoryStore, i64)>),
+    /// `status`, `report`, `export`: uses the recorded idle/lock gaps and the history as they are. Nothing is
+    /// sampled or saved.
+    Preview,
+}
+
+/// Every day's span for `profile`: boot sessions from `last` merged with the sessions in the profile's
+/// local history, minus the idle/lock gaps, with the profile's adjustments and balances. Previews and runs
+/// share this, so `status`, `report` and `export` show what a run posts. Also returns how many sessions
+/// `last` reported.
+pub async fn compute_spans(admin_config: &AdminConfig, profile: Option<&str>, mut mode: SpanMode<'_>, now: DateTime<Local>) -> Result<(usize, Vec<WorkSpanData>)> {
+    let sessions = match fetch_last_logs().await {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Failed to retrieve boot sessions: {}. Cannot compute any span.", e);
+            return Err(e);
+        }
+    };
+    let reported = sessions.len();
+    if matches!(mode, SpanMode::Run(_)) {
+        RunMetrics::add(&run_metrics().sessions_parsed, reported);
+    }
+
+    // Merge with every session seen by earlier runs, so days survive `last` rolling over.
+    let historical = match &mut mode {
+        SpanMode::Run(history) => match history.as_mut() {
+            Some((store, run_id)) => {
+                if let Err(e) = store.record_session_source(*run_id, SESSION_SOURCE, reported) {
+                    warn!("Failed to record session source in local history: {}", e);
+                }
+                if let Err(e) = store.record_sessions(*run_id, &sessions) {
+                    warn!("Failed to store boot sessions in local history: {}", e);
+                }
+                Some(store.historical_sessions())
+            },
+            None => None,
+        },
+        SpanMode::Preview if std::path::Path::new(&history_path(profile)).exists() => {
+            Some(HistoryStore::open_profile(profile).and_then(|store| store.historical_sessions()))
+        },
+        SpanMode::Preview => None,
+    };
+    let sessions = match historical {
+        Some(Ok(historical)) => merge_sessions(sessions, historical),
+        Some(Err(e)) => {
+            warn!("Failed to read boot sessions from local history {}: {}", history_path(profile), e);
+            sessions
+        },
+        None => sessions,
+    };
+
+    let gaps = match mode {
+        SpanMode::Run(_) => {
+            let mut gaps = collect_idle_periods(&admin_config.idle).await;
+            gaps.extend(collect_locked_gaps(&admin_config.lock, &sessions).await);
+            gaps
+        },
+        SpanMode::Preview => {
+            let mut gaps = idle_gaps(&load_idle_log(), &admin_config.idle, now);
+            if admin_config.lock.enabled {
+                gaps.extend(load_lock_log().locked_gaps(&sessions, now));
+            }
+            gaps
+        },
+    };
+
+    let calendar = WorkCalendar::from_config_or_warn(&admin_config.schedule);
+    let mut spans = calculate_spans(sessions, &gaps, &load_overrides_or_warn(profile), &calendar, &admin_config.span_policy)?;
+    apply_balance(&mut spans, &admin_config.balance);
+
+    if let SpanMode::Run(history) = mode {
+        RunMetrics::add(&run_metrics().days_computed, spans.len());
+        if let Some((store, run_id)) = history.as_mut() {
+            if let Err(e) = store.record_spans(*run_id, &spans, now) {
+                warn!("Failed to store computed spans in local history: {}", e);
+            }
+        }
+    }
+
+    Ok((reported, spans))
+}
+
+/// Posts one day and records the attempt (payload, HTTP status, error) in the local history.
+#[tracing::instrument(name = "post", skip_all, fields(date = %data.date))]
+pub async fn post_and_record(
+    data: &WorkSpanData,
+    client: &SupabaseClient,
+    admin_config: &AdminConfig,
+    user_config: &mut UserConfig,
+    history: Option<&(HistoryStore, i64)>,
+) -> Result<reqwest::StatusCode> {
+    info!("Processing data for date: {}", data.date);
+    let refreshes_before = RunMetrics::get(&run_metrics().token_refreshes);
+    let token_before = user_config.access_token.clone();
+    let result = post_work_span(data.clone(), client, admin_config, user_config).await;
+    record_token_refreshes(history, refreshes_before, token_before.as_deref(), user_config, result.as_ref().err());
+
+    if let Some((store, run_id)) = history {
+        let payload = build_payload(user_config.user_id.as_deref().unwrap_or_default(), data);
+        let recorded = match &result {
+            Ok(status) => store.record_post(*run_id, &data.date, &payload, Some(status.as_u16()), None),
+            Err(e) => {
+                let status = api_status(e).map(|s| s.as_u16());
+                store.record_post(*run_id, &data.date, &payload, status, Some(&e.to_string()))
+            }
+        };
+        if let Err(e) = recorded {
+            warn!("Failed to record post for {} in local history: {}", data.date, e);
+        }
+    }
+
+    result
+}
+
+/// Main entry point for the data collector logic: retrieves data and posts it asynchronously.
+/// The run, its trigger and its outcome are recorded in the local history.
+/// The run is stopped at `deadline` (see `run_once`); retries never wait past it.
+pub async fn run_collector_logic(admin_config: &AdminConfig, user_config: &mut UserConfig, trigger: &str, deadline: Option<RunDeadline>) -> Result<()> {
+    let run_started_at = Local::now();
+    info!("Collector running on day: {}", run_started_at.date_naive().format("%Y-%m-%d"));
+
+    // The local history is best-effort: a broken database must never stop posting.
+    let mut history = open_history(run_started_at, "run", trigger, user_config.profile.as_deref());
+    let result = within_deadline(deadline, collect_and_post(admin_config, user_config, &mut history, run_started_at, deadline)).await;
+    finish_history(history.as_ref(), &result, user_config);
+    result
+}
+
+/// Awaits `run`, stopping it once `deadline` has passed. Its unfinished days are left for the next run.
+async fn within_deadline(deadline: Option<RunDeadline>, run: impl Future<Output = Result<()>>) -> Result<()> {
+    let Some(deadline) = deadline else {
+        return run.await;
+    };
+    match tokio::time::timeout_at(deadline.instant().into(), run).await {
+        Ok(result) => result,
+        Err(_) => {
+            error!("Run deadline of {}s exceeded. Stopping; unfinished days are posted by the next run.", deadline.seconds());
+            Err(CollectorError::DeadlineExceeded(deadline.seconds()).into())
+        }
+    }
+}
+
+async fn collect_and_post(
+    admin_config: &AdminConfig,
+    user_config: &mut UserConfig,
+    history: &mut Option<(HistoryStore, i64)>,
+    run_started_at: DateTime<Local>,
+    deadline: Option<RunDeadline>,
+) -> Result<()> {
+    let current_day_naive = run_started_at.date_naive();
+    let client = SupabaseClient::from_admin_config(admin_config)?.with_deadline(deadline);
+    let (_, all_historical_data) = compute_spans(admin_config, user_config.profile.as_deref(), SpanMode::Run(history), run_started_at).await?;
+
+    // 1. FILTER: Only process data newer than or equal to the last successful post,
+    // plus recent finalized days whose recomputed span no longer matches what was posted.
+    let changed_days = match history.as_ref() {
+        Some((store, _)) => changed_posted_days(store, &all_historical_data, user_config, current_day_naive),
+        None => Vec::new(),
+    };
+    for span in &changed_days {
+        info!("{} changed since it was last posted (now {}). Scheduling re-post.", span.date, span.total_span);
+    }
+    let reconciled_days = if admin_config.reconcile.enabled {
+        drifted_days(&client, user_config, history.as_ref(), &all_historical_data, admin_config.reconcile.window_days, current_day_naive).await
+    } else {
+        Vec::new()
+    };
+
+    let data_to_post = days_to_post(all_historical_data, user_config, changed_days, reconciled_days);
+
+    let total_entries = data_to_post.len();
+
+    if total_entries == 0 {
+        info!("No new work span data found to post since last run.");
+        return Ok(());
+    }
+
+    info!("Starting posting process for {} historical day(s).", total_entries);
+
+    let mut last_successful_date_posted: Option<String> = user_config.last_posted_date.clone();
+    let mut post_error = None;
+
+    for data in data_to_post {
+        if data.excluded_minutes > 0 {
+            info!("{} idle/locked minute(s) excluded from {}'s active time.", data.excluded_minutes, data.date);
+        }
+
+        // 2. POST: Post the data
+        let result = post_and_record(&data, &client, admin_config, user_config, history.as_ref()).await;
+
+        match result {
+            Ok(_) => {
+                RunMetrics::inc(&run_metrics().days_posted);
+                // Determine if this successfully posted date is "Today"
+                let posted_date_naive = match NaiveDate::parse_from_str(&data.date, "%Y-%m-%d") {
+                    Ok(d) => d,
+                    Err(_) => {
+                        error!("Failed to parse posted date string '{}'. Skipping config update for safety.", data.date);
+                        continue;
+                    }
+                };
+
+                // Conditional Config Update
+                // We only update the config date if the successfully posted data is NOT today.
+                // This ensures "Today" is re-posted tomorrow for finalization.
+                let already_finalized = last_successful_date_posted.as_deref()
+                    .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+                    .is_some_and(|last| posted_date_naive < last);
+                if already_finalized {
+                    info!("Re-posted corrected data for {}.", data.date);
+                } else if posted_date_naive < current_day_naive {
+                    last_successful_date_posted = Some(data.date);
+                } else {
+                    info!("Posted data for today ({}). Will NOT update config to this date to ensure finalization tomorrow.", data.date);
+                }
+            },
+            Err(e) => {
+                RunMetrics::inc(&run_metrics().days_failed);
+                error!("Failed to post data for date {}: {}. Aborting remaining posts.", data.date, e);
+                // If posting fails, we stop the iteration. The error is returned once progress is saved.
+                post_error = Some(e.context(format!("Failed to post data for date {}", data.date)));
+                break;
+            }
+        }
+    }
+
+    // 3. UPDATE CONFIG: Persist the last successfully finalized date (which is yesterday or older).
+    if user_config.last_posted_date != last_successful_date_posted {
+        user_config.last_posted_date = last_successful_date_posted.clone();
+
+        save_user_config(user_config);
+
+        if let Some(date) = last_successful_date_posted {
+             info!("User configuration updated. Last FINALIZED post date is now: {}", date);
+        } else {
+             info!("No finalized posts were made in this run, or all posts failed.");
+        }
+    } else {
+        info!("Last finalized date remains unchanged.");
+    }
+
+    if let Some(e) = post_error {
+        return Err(e);
+    }
+    info!("Collector run finished successfully.");
+    Ok(())
+}
+
+/// Finalized days (before last_posted_date, within REPOST_WINDOW_DAYS) whose payload differs from
+/// the last successful post recorded in the local history. Days never posted from this machine are left alone.
+pub fn changed_posted_days(store: &HistoryStore, spans: &[WorkSpanData], user_config: &UserConfig, today: NaiveDate) -> Vec<WorkSpanData> {
+    let (Some(user_id), Some(last_posted)) = (
+        user_config.user_id.as_deref(),
+        user_config.last_posted_date.as_deref().and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()),
+    ) else {
+        return Vec::new();
+    };
+    let window_start = today - Duration::days(REPOST_WINDOW_DAYS);
+
+    let mut changed: Vec<WorkSpanData> = spans.iter()
+        .filter(|span| NaiveDate::parse_from_str(&span.date, "%Y-%m-%d")
+            .is_ok_and(|date| date >= window_start && date < last_posted))
+        .filter(|span| match store.last_successful_payload(&span.date) {
+            Ok(Some(posted)) => posted != build_payload(user_id, span),
+            Ok(None) => false,
+            Err(e) => {
+                warn!("{}", e);
+                false
+            }
+        })
+        .cloned()
+        .collect();
+
+    changed.sort_by(|a, b| a.date.cmp(&b.date));
+    changed
+}
+
+/// The days a run posts, in date order: every day since last_posted_date plus the `changed`
+/// (`changed_posted_days`) and `drifted` (reconcile) ones.
+pub fn days_to_post(spans: Vec<WorkSpanData>, user_config: &UserConfig, changed: Vec<WorkSpanData>, drifted: Vec<WorkSpanData>) -> Vec<WorkSpanData> {
+    let mut data_to_post = changed;
+    data_to_post.extend(drifted);
+    data_to_post.extend(filter_data_for_posting(spans, user_config));
+    data_to_post.sort_by(|a, b| a.date.cmp(&b.date));
+    data_to_post.dedup_by(|a, b| a.date == b.date);
+    data_to_post
+}
+
+/// Recomputes and upserts every day in `from..=to`.
+///
+/// Days whose identical payload was already posted successfully are skipped unless `force` is set.
+/// last_posted_date only moves forward, never past yesterday, and only when the range connects to it,
+/// so a backfill never hides days that have not been posted yet. Bounded by `[retry] run_deadline_seconds`.
+#[tracing::instrument(name = "backfill", skip_all, fields(%from, %to, force = force, run_id = tracing::field::Empty))]
+pub async fn run_backfill(admin_config: &AdminConfig, user_config: &mut UserConfig, from: NaiveDate, to: NaiveDate, force: bool) -> Result<()> {
+    let run_started_at = Local::now();
+    let today = run_started_at.date_naive();
+    if from > to {
+        return Err(anyhow!("--from {} is after --to {}.", from, to));
+    }
+    if to > today {
+        return Err(anyhow!("--to {} is in the future.", to));
+    }
+    info!("Backfilling {} .. {}{}.", from, to, if force { " (forced)" } else { "" });
+
+    let mut history = open_history(run_started_at, "backfill", "manual", user_config.profile.as_deref());
+    let deadline = RunDeadline::start(&admin_config.retry);
+    let result = within_deadline(deadline, backfill_range(admin_config, user_config, &mut history, run_started_at, from..=to, force, deadline)).await;
+    finish_history(history.as_ref(), &result, user_config);
+    result
+}
+
+async fn backfill_range(
+    admin_config: &AdminConfig,
+    user_config: &mut UserConfig,
+    history: &mut Option<(HistoryStore, i64)>,
+    run_started_at: DateTime<Local>,
+    range: RangeInclusive<NaiveDate>,
+    force: bool,
+    deadline: Option<RunDeadline>,
+) -> Result<()> {
+    let (from, to) = (*range.start(), *range.end());
+    let today = run_started_at.date_naive();
+    let client = SupabaseClient::from_admin_config(admin_config)?.with_deadline(deadline);
+    let (_, all_historical_data) = compute_spans(admin_config, user_config.profile.as_deref(), SpanMode::Run(history), run_started_at).await?;
+    let in_range = spans_in_range(all_historical_data, from, to);
+
+    let user_id = user_config.user_id.clone().unwrap_or_default();
+    let data_to_post: Vec<WorkSpanData> = in_range.into_iter()
+        .filter(|data| {
+            if force {
+                return true;
+            }
+            let unchanged = history.as_ref()
+                .and_then(|(store, _)| store.last_successful_payload(&data.date).ok().flatten())
+                .is_some_and(|posted| posted == build_payload(&user_id, data));
+            if unchanged {
+                info!("{} is unchanged since its last successful post. Skipping (use --force to re-post).", data.date);
+            }
+            !unchanged
+        })
+        .collect();
+
+    if data_to_post.is_empty() {
+        info!("Nothing to post in {} .. {}.", from, to);
+        return Ok(());
+    }
+    info!("Posting {} day(s).", data_to_post.len());
+
+    let mut posted = 0;
+    for data in &data_to_post {
+        if let Err(e) = post_and_record(data, &client, admin_config, user_config, history.as_ref()).await {
+            error!("Failed to post data for date {}: {}. Aborting backfill after {} of {} day(s).", data.date, e, posted, data_to_post.len());
+            return Err(e);
+        }
+        posted += 1;
+    }
+
+    // Every day from the current last_posted_date up to `to` is now posted (or was skipped as unchanged).
+    let last_posted = user_config.last_posted_date.as_deref()
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+    let finalized_to = to.min(today - Duration::days(1));
+    match last_posted {
+        Some(last) if from <= last && finalized_to > last => {
+            user_config.last_posted_date = Some(finalized_to.format("%Y-%m-%d").to_string());
+            save_user_config(user_config);
+            info!("Backfill posted {} day(s). Last FINALIZED post date is now: {}", posted, finalized_to);
+        },
+        _ => info!("Backfill posted {} day(s). Last finalized date remains unchanged.", posted),
+    }
+    Ok(())
+}
+
+/// Compares the finalized days of the last `window_days` with the server and returns the local spans
+/// that are missing there or differ. Reconciling is best-effort: errors are reported and nothing is returned.
+async fn drifted_days(
+    client: &SupabaseClient,
+    user_config: &mut UserConfig,
+    history: Option<&(HistoryStore, i64)>,
+    spans: &[WorkSpanData],
+    window_days: i64,
+    today: NaiveDate,
+) -> Vec<WorkSpanData> {
+    let from = (today - Duration::days(window_days)).format("%Y-%m-%d").to_string();
+    let to = (today - Duration::days(1)).format("%Y-%m-%d").to_string();
+
+    let refreshes_before = RunMetrics::get(&run_metrics().token_refreshes);
+    let token_before = user_config.access_token.clone();
+    let result = reconcile_window(client, user_config, spans, &from, &to).await;
+    record_token_refreshes(history, refreshes_before, token_before.as_deref(), user_config, result.as_ref().err());
+
+    match result {
+        Ok(drift) => {
+            print_drift(&drift);
+            drift.iter()
+                .filter(|day| day.needs_post())
+                .filter_map(|day| spans.iter().find(|s| s.date == day.date).cloned())
+                .collect()
+        },
+        Err(e) => {
+            warn!("Reconcile with the server failed: {}. Relying on last_posted_date only.", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Like `drifted_days`, for looking only: uses the current access token as is (a 401 is returned as an
+/// error instead of refreshing it) and neither logs nor records the drift.
+pub async fn server_drifted_days(admin_config: &AdminConfig, user_config: &UserConfig, spans: &[WorkSpanData], today: NaiveDate) -> Result<Vec<WorkSpanData>> {
+    let client = SupabaseClient::from_admin_config(admin_config)?;
+    let (Some(access_token), Some(user_id)) = (user_config.access_token.as_deref(), user_config.user_id.as_deref()) else {
+        return Err(anyhow!("Access token or user ID is missing."));
+    };
+    let from = (today - Duration::days(admin_config.reconcile.window_days)).format("%Y-%m-%d").to_string();
+    let to = (today - Duration::days(1)).format("%Y-%m-%d").to_string();
+
+    let remote = client.fetch_work_spans(access_token, user_id, &from, &to).await?;
+    let in_window: Vec<WorkSpanData> = spans.iter()
+        .filter(|s| s.date >= from && s.date <= to)
+        .cloned()
+        .collect();
+    Ok(compare_spans(&in_window, &remote).iter()
+        .filter(|day| day.needs_post())
+        .filter_map(|day| spans.iter().find(|s| s.date == day.date).cloned())
+        .collect())
+}
+
+/// Reconciles the last `days` finalized days with the server and re-posts drifted days unless `dry_run`.
+/// Bounded by `[retry] run_deadline_seconds`.
+#[tracing::instrument(name = "reconcile", skip_all, fields(days = days, dry_run = dry_run, run_id = tracing::field::Empty))]
+pub async fn run_reconcile(admin_config: &AdminConfig, user_config: &mut UserConfig, days: i64, dry_run: bool) -> Result<()> {
+    let run_started_at = Local::now();
+    let mut history = open_history(run_started_at, "reconcile", "manual", user_config.profile.as_deref());
+    let deadline = RunDeadline::start(&admin_config.retry);
+    let result = within_deadline(deadline, reconcile_recent(admin_config, user_config, &mut history, run_started_at, days, dry_run, deadline)).await;
+    finish_history(history.as_ref(), &result, user_config);
+    result
+}
+
+async fn reconcile_recent(
+    admin_config: &AdminConfig,
+    user_config: &mut UserConfig,
+    history: &mut Option<(HistoryStore, i64)>,
+    run_started_at: DateTime<Local>,
+    days: i64,
+    dry_run: bool,
+    deadline: Option<RunDeadline>,
+) -> Result<()> {
+    let today = run_started_at.date_naive();
+    let client = SupabaseClient::from_admin_config(admin_config)?.with_deadline(deadline);
+    let (_, all_historical_data) = compute_spans(admin_config, user_config.profile.as_deref(), SpanMode::Run(history), run_started_at).await?;
+
+    let from = (today - Duration::days(days)).format("%Y-%m-%d").to_string();
+    let to = (today - Duration::days(1)).format("%Y-%m-%d").to_string();
+    let refreshes_before = RunMetrics::get(&run_metrics().token_refreshes);
+    let token_before = user_config.access_token.clone();
+    let drift = reconcile_window(&client, user_config, &all_historical_data, &from, &to).await;
+    record_token_refreshes(history.as_ref(), refreshes_before, token_before.as_deref(), user_config, drift.as_ref().err());
+    let drift = drift?;
+    print_drift(&drift);
+
+    let to_post: Vec<&WorkSpanData> = drift.iter()
+        .filter(|day| day.needs_post())
+        .filter_map(|day| all_historical_data.iter().find(|s| s.date == day.date))
+        .collect();
+
+    if dry_run || to_post.is_empty() {
+        if dry_run && !to_post.is_empty() {
+            info!("Dry run: {} day(s) would be re-posted.", to_post.len());
+        }
+        return Ok(());
+    }
+
+    for data in &to_post {
+        post_and_record(data, &client, admin_config, user_config, history.as_ref()).await?;
+    }
+    info!("Reconciled {} day(s) with the server.", to_post.len());
+    Ok(())
+}
+
+/// Applies `adjust` flags to the override for `date` and saves the overrides file of `profile`.
+pub fn adjust_day(profile: Option<&str>, date: NaiveDate, exclude: bool, span: Option<&str>, note: Option<&str>, clear: bool) -> Result<()> {
+    if !(exclude || clear) && span.is_none() && note.is_none() {
+        return Err(anyhow!("Nothing to adjust. Pass --exclude, --span HH:MM-HH:MM, --note TEXT or --clear."));
+    }
+    if let Some(span) = span {
+        parse_span(span)?;
+    }
+
+    // A broken overrides file must be fixed by hand rather than silently overwritten.
+    let mut overrides = load_overrides(profile)?;
+    let key = date.format("%Y-%m-%d").to_string();
+
+    if clear {
+        if overrides.days.remove(&key).is_none() {
+            println!("{} has no adjustments.", key);
+            return Ok(());
+        }
+        save_overrides(&overrides, profile)?;
+        println!("Adjustments for {} removed.", key);
+    } else {
+        let day = overrides.days.entry(key.clone()).or_default();
+        if exclude {
+            day.exclude = true;
+            day.span = None;
+        }
+        if let Some(span) = span {
+            day.exclude = false;
+            day.span = Some(span.to_string());
+        }
+        if let Some(note) = note {
+            day.note = if note.is_empty() { None } else { Some(note.to_string()) };
+        }
+        if *day == DayOverride::default() {
+            overrides.days.remove(&key);
+        }
+        save_overrides(&overrides, profile)?;
+
+        match overrides.days.get(&key) {
+            Some(day) => println!("{} adjusted in {}: {}", key, overrides_path(profile),
+                toml::to_string(day).unwrap_or_default().trim().replace('\n', ", ")),
+            None => println!("{} no longer has adjustments.", key),
+        }
+    }
+
+    let profile_flag = profile.map(|name| format!(" --profile {}", name)).unwrap_or_default();
+    println!("Recent days are re-posted by the next run. For older days run: avadhi-collector backfill --from {} --to {}{}", date, date, profile_flag);
+    Ok(())
+}
+
+/// True if the stored user config lacks any of the credentials needed to post.
+pub fn tokens_missing(user_config: &UserConfig) -> bool {
+    user_config.access_token.is_none()
+        || user_config.refresh_token.is_none()
+        || user_config.user_id.is_none()
+}
+
+/// systemd sets INVOCATION_ID for every unit it starts, so its presence means the timer fired.
+pub fn run_trigger() -> &'static str {
+    if std::env::var_os("INVOCATION_ID").is_some() { "timer" } else { "manual" }
+}
+
+/// The `setup` command line that creates the credentials of `profile`.
+pub fn setup_command(profile: Option<&str>) -> String {
+    match profile {
+        Some(name) => format!("/opt/avadhi-collector/avadhi-collector setup --profile {}", name),
+        None => "/opt/avadhi-collector/avadhi-collector setup".to_string(),
+    }
+}
+
+/// Runs the collector for one profile with its own config and credentials. Also returns the
+/// last_posted_date the profile is left with.
+async fn run_profile(admin_config: &AdminConfig, profile: Option<&str>, trigger: &str, deadline: Option<RunDeadline>) -> (Result<()>, Option<String>) {
+    let mut user_config = load_user_config(profile);
+    let result = match admin_config.for_profile(profile) {
+        Err(e) => Err(e.into()),
+        Ok(_) if tokens_missing(&user_config) => Err(CollectorError::ConfigMissing(
+            format!("User tokens are missing. Please run '{}' first.", setup_command(profile))).into()),
+        Ok(config) => run_collector_logic(&config, &mut user_config, trigger, deadline).await,
+    };
+    (result, user_config.last_posted_date)
+}
+
+/// Runs the collector once for `profile`, or for every configured profile, and records each profile's
+/// outcome in its AvadhiState.toml and, if enabled, the overall one in the Prometheus textfile. A failing profile does not stop the
+/// others; the first failure is returned. One `[retry] run_deadline_seconds` deadline covers all profiles.
+#[tracing::instrument(name = "run", skip_all, fields(trigger = trigger, run_id = tracing::field::Empty))]
+pub async fn run_once(admin_config: &AdminConfig, trigger: &str, profile: Option<&str>) -> Result<()> {
+    let profiles = admin_config.selected_profiles(profile)?;
+    let started_at = Local::now();
+    let deadline = RunDeadline::start(&admin_config.retry);
+    run_metrics().reset();
+
+    let mut result = Ok(());
+    let mut last_finalized: Option<NaiveDate> = None;
+    for profile in &profiles {
+        let (outcome, last_posted) = match profile.as_deref() {
+            Some(name) => run_profile(admin_config, Some(name), trigger, deadline)
+                .instrument(tracing::info_span!("profile", profile = name, run_id = tracing::field::Empty))
+                .await,
+            None => run_profile(admin_config, None, trigger, deadline).await,
+        };
+        if let (Err(e), Some(name)) = (&outcome, profile) {
+            error!("Profile '{}' failed: {:#}", name, e);
+        }
+        record_run(trigger, started_at, &outcome, profile.as_deref());
+
+        // The metric shows the profile that is furthest behind.
+        let last_posted = last_posted.as_deref().and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+        last_finalized = match (last_finalized, last_posted) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        if result.is_ok() {
+            result = outcome;
+        }
+    }
+
+    write_run_metrics(&admin_config.metrics, started_at, result.is_ok(), last_finalized);
+    result
+}
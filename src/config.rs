@@ -1,7 +1,11 @@
 use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::io::{self, Write};
 use std::path::Path;
+use tracing::{error, info, warn};
+
+use crate::error::CollectorError;
 
 pub const ADMIN_CONFIG_PATH: &str = "Config.toml";
 pub const USER_CONFIG_PATH: &str = "AvadhiConfig.toml";
@@ -12,6 +16,460 @@ pub struct AdminConfig {
     pub web_app_url: Option<String>,
     pub supabase_url: Option<String>,
     pub supabase_anon_key: Option<String>,
+
+    /// Local time (24h) at which `daemon` mode triggers the daily collector run.
+    pub daily_check_hour: Option<u32>,
+    pub daily_check_minute: Option<u32>,
+
+    /// Privacy rules applied to app-usage data before anything leaves the machine.
+    #[serde(default)]
+    pub app_usage: AppUsagePrivacyConfig,
+
+    /// Idle (away-from-keyboard) detection settings.
+    #[serde(default)]
+    pub idle: IdleConfig,
+
+    /// Screen lock tracking settings.
+    #[serde(default)]
+    pub lock: LockConfig,
+
+    /// Comparison of local spans against the rows already on the server.
+    #[serde(default)]
+    pub reconcile: ReconcileConfig,
+
+    /// Weekly work schedule and holidays used to classify days.
+    #[serde(default)]
+    pub schedule: ScheduleConfig,
+
+    /// How daily deviations from the expected minutes become overtime/undertime balances.
+    #[serde(default)]
+    pub balance: BalanceConfig,
+
+    /// Filters and rounding applied to boot sessions before daily spans are calculated.
+    #[serde(default)]
+    pub span_policy: SpanPolicyConfig,
+
+    /// Prometheus textfile written after every run.
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+
+    /// Retries of failed posts and the overall run deadline.
+    #[serde(default)]
+    pub retry: RetryConfig,
+
+    /// Timeouts, proxy and TLS settings of the HTTP client.
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    /// Named accounts, e.g. one per organization billed from this machine. Empty: the single
+    /// account of AvadhiConfig.toml.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+}
+
+/// One `[profiles.<name>]` table of Config.toml. Each profile keeps its own credentials and
+/// last_posted_date in AvadhiConfig.<name>.toml and its own AvadhiHistory.<name>.sqlite.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct ProfileConfig {
+    /// Backend of this profile. Unset values are taken from the top level of Config.toml.
+    pub web_app_url: Option<String>,
+    pub supabase_url: Option<String>,
+    pub supabase_anon_key: Option<String>,
+
+    /// Only sessions overlapping this daily window ("HH:MM") count for this profile. Replaces the
+    /// window of [span_policy].
+    pub window_start: Option<String>,
+    pub window_end: Option<String>,
+
+    /// Only these weekdays ("mon" .. "sun") are posted for this profile. Empty: [span_policy] weekdays.
+    pub weekdays: Vec<String>,
+}
+
+/// Profile names end up in file names, so they are limited to letters, digits, '-' and '_'.
+fn valid_profile_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl AdminConfig {
+    /// True if the values every backend command needs are set.
+    pub fn backend_configured(&self) -> bool {
+        self.supabase_url.is_some() && self.supabase_anon_key.is_some() && self.web_app_url.is_some()
+    }
+
+    fn profile_names(&self) -> String {
+        self.profiles.keys().map(String::as_str).collect::<Vec<_>>().join(", ")
+    }
+
+    /// The configuration `profile` runs with: the top level overridden by its `[profiles.<name>]` table.
+    /// None (the single-account setup) returns the top level unchanged.
+    pub fn for_profile(&self, profile: Option<&str>) -> Result<AdminConfig, CollectorError> {
+        let Some(name) = profile else {
+            return Ok(self.clone());
+        };
+        if !valid_profile_name(name) {
+            return Err(CollectorError::ConfigMissing(format!("Invalid profile name '{}' (use letters, digits, '-' and '_').", name)));
+        }
+        let profile = self.profiles.get(name).ok_or_else(|| CollectorError::ConfigMissing(if self.profiles.is_empty() {
+            format!("Unknown profile '{}': Config.toml defines no [profiles].", name)
+        } else {
+            format!("Unknown profile '{}'. Config.toml defines: {}.", name, self.profile_names())
+        }))?;
+
+        let mut config = self.clone();
+        config.profiles.clear();
+        if profile.web_app_url.is_some() {
+            config.web_app_url = profile.web_app_url.clone();
+        }
+        if profile.supabase_url.is_some() {
+            config.supabase_url = profile.supabase_url.clone();
+        }
+        if profile.supabase_anon_key.is_some() {
+            config.supabase_anon_key = profile.supabase_anon_key.clone();
+        }
+        if profile.window_start.is_some() || profile.window_end.is_some() {
+            config.span_policy.window_start = profile.window_start.clone();
+            config.span_policy.window_end = profile.window_end.clone();
+        }
+        if !profile.weekdays.is_empty() {
+            config.span_policy.weekdays = profile.weekdays.clone();
+        }
+        Ok(config)
+    }
+
+    /// The profiles `run` covers: `selected` only, otherwise every configured profile, or without
+    /// `[profiles]` the single default account (None).
+    pub fn selected_profiles(&self, selected: Option<&str>) -> Result<Vec<Option<String>>, CollectorError> {
+        if let Some(name) = selected {
+            self.for_profile(Some(name))?;
+            return Ok(vec![Some(name.to_string())]);
+        }
+        if self.profiles.is_empty() {
+            return Ok(vec![None]);
+        }
+        self.profiles.keys()
+            .map(|name| self.for_profile(Some(name)).map(|_| Some(name.clone())))
+            .collect()
+    }
+
+    /// The one profile a single-account command (setup, backfill, reconcile, history) works on.
+    /// With `[profiles]` configured, naming one is required.
+    pub fn single_profile(&self, selected: Option<&str>) -> Result<Option<String>, CollectorError> {
+        match selected {
+            None if !self.profiles.is_empty() => Err(CollectorError::ConfigMissing(format!(
+                "Config.toml defines profiles ({}); choose one with --profile.", self.profile_names()))),
+            None => Ok(None),
+            Some(name) => self.for_profile(Some(name)).map(|_| Some(name.to_string())),
+        }
+    }
+}
+
+/// The file of `profile` for a per-account file: "AvadhiConfig.toml" becomes "AvadhiConfig.acme.toml".
+/// None returns `path` unchanged.
+pub fn profile_path(path: &str, profile: Option<&str>) -> String {
+    match (profile, path.rsplit_once('.')) {
+        (None, _) => path.to_string(),
+        (Some(name), Some((stem, extension))) => format!("{}.{}.{}", stem, name, extension),
+        (Some(name), None) => format!("{}.{}", path, name),
+    }
+}
+
+/// The `[http]` table of Config.toml.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub connect_timeout_seconds: u64,
+
+    /// Limit for a whole request, from connecting until the response body is read.
+    pub timeout_seconds: u64,
+
+    /// Proxy for all requests, e.g. "http://proxy.corp:3128". Unset: HTTPS_PROXY/HTTP_PROXY from the environment.
+    pub proxy: Option<String>,
+
+    /// Hosts that bypass `proxy`, comma separated, e.g. "localhost,.corp.internal".
+    pub no_proxy: Option<String>,
+
+    /// PEM file with extra root certificates, e.g. a TLS-intercepting proxy's CA.
+    pub ca_bundle: Option<String>,
+
+    /// PEM client certificate and its PKCS#8 PEM private key, for mutual TLS.
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            connect_timeout_seconds: 10,
+            timeout_seconds: 30,
+            proxy: None,
+            no_proxy: None,
+            ca_bundle: None,
+            client_cert: None,
+            client_key: None,
+        }
+    }
+}
+
+/// The `[retry]` table of Config.toml.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Attempts per post, including the first.
+    pub max_attempts: u32,
+
+    /// Backoff before retry n is base_delay_seconds * 2^(n-1), at most max_delay_seconds.
+    pub base_delay_seconds: u64,
+    pub max_delay_seconds: u64,
+
+    /// Wait a random time between zero and the backoff instead of the full backoff.
+    pub jitter: bool,
+
+    /// A run (`run`, the timer's oneshot) is stopped after this many seconds (0 = no deadline).
+    pub run_deadline_seconds: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: 3,
+            base_delay_seconds: 2,
+            max_delay_seconds: 60,
+            jitter: true,
+            run_deadline_seconds: 900,
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn run_deadline(&self) -> Option<std::time::Duration> {
+        (self.run_deadline_seconds > 0).then(|| std::time::Duration::from_secs(self.run_deadline_seconds))
+    }
+}
+
+/// The `[metrics]` table of Config.toml.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+
+    /// The `.prom` file, usually inside node_exporter's --collector.textfile.directory.
+    pub textfile_path: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            enabled: false,
+            textfile_path: "/var/lib/node_exporter/textfile_collector/avadhi_collector.prom".to_string(),
+        }
+    }
+}
+
+/// The `[span_policy]` table of Config.toml. The defaults change nothing.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct SpanPolicyConfig {
+    /// Sessions separated by less than this many minutes are merged into one.
+    pub merge_gap_minutes: i64,
+
+    /// Sessions shorter than this (after merging) are ignored, e.g. a reboot to install updates.
+    pub min_session_minutes: i64,
+
+    /// Sessions that lie completely outside window_start..window_end ("HH:MM", local time) are ignored.
+    pub window_start: Option<String>,
+    pub window_end: Option<String>,
+
+    /// Round each day's total to the nearest multiple of this many minutes (e.g. 5 or 15; 0 = exact).
+    pub round_total_minutes: i64,
+
+    /// Only days on these weekdays ("mon" .. "sun") are kept. Empty: every day.
+    pub weekdays: Vec<String>,
+}
+
+/// Direction in which deviations are rounded to `rounding_minutes`.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    #[default]
+    Nearest,
+    /// Towards negative infinity (less overtime, more undertime).
+    Down,
+    /// Towards positive infinity.
+    Up,
+}
+
+/// The `[balance]` table of Config.toml.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct BalanceConfig {
+    /// Round each day's deviation to a multiple of this many minutes (0 or 1 = exact).
+    pub rounding_minutes: i64,
+
+    pub rounding_mode: RoundingMode,
+
+    /// At most this much overtime counts per day.
+    pub max_daily_overtime_minutes: Option<i64>,
+
+    /// At most this much undertime counts per day.
+    pub max_daily_undertime_minutes: Option<i64>,
+}
+
+/// The `[schedule]` table of Config.toml.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    /// Working weekdays ("mon" .. "sun"). Other days are classified as weekend.
+    pub working_days: Vec<String>,
+
+    /// Expected hours on a working day.
+    pub expected_hours: f64,
+
+    /// Per-weekday exceptions to expected_hours, e.g. { fri = 6.0 }.
+    pub expected_hours_by_day: HashMap<String, f64>,
+
+    /// Holiday dates (YYYY-MM-DD).
+    pub holidays: Vec<String>,
+
+    /// Additional holidays from an .ics calendar or a TOML file with `holidays = ["YYYY-MM-DD", ...]`.
+    pub holidays_file: Option<String>,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        ScheduleConfig {
+            working_days: ["mon", "tue", "wed", "thu", "fri"].iter().map(|d| d.to_string()).collect(),
+            expected_hours: 8.0,
+            expected_hours_by_day: HashMap::new(),
+            holidays: Vec::new(),
+            holidays_file: None,
+        }
+    }
+}
+
+/// The `[reconcile]` table of Config.toml.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ReconcileConfig {
+    /// Compare against the server on every run and re-post missing or different days.
+    pub enabled: bool,
+
+    /// How many days before today are compared.
+    pub window_days: i64,
+}
+
+impl Default for ReconcileConfig {
+    fn default() -> Self {
+        ReconcileConfig {
+            enabled: true,
+            window_days: 14,
+        }
+    }
+}
+
+/// The `[lock]` table of Config.toml.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LockConfig {
+    pub enabled: bool,
+
+    /// Login name whose sessions are tracked. Defaults to every seated (graphical) session.
+    pub user: Option<String>,
+
+    /// Optional D-Bus address used instead of the system bus (e.g. a local dbus-daemon stand-in).
+    pub dbus_address: Option<String>,
+}
+
+impl Default for LockConfig {
+    fn default() -> Self {
+        LockConfig {
+            enabled: true,
+            user: None,
+            dbus_address: None,
+        }
+    }
+}
+
+/// Where idle information is read from.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IdleSourceKind {
+    /// logind first, /proc/interrupts if logind is unavailable.
+    #[default]
+    Auto,
+    Logind,
+    Interrupts,
+}
+
+/// The `[idle]` table of Config.toml.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct IdleConfig {
+    pub enabled: bool,
+
+    /// Idle periods shorter than this are NOT subtracted from a day's active time.
+    pub threshold_minutes: i64,
+
+    pub source: IdleSourceKind,
+
+    /// Optional D-Bus address used instead of the system bus (e.g. a local dbus-daemon stand-in).
+    pub dbus_address: Option<String>,
+
+    /// Case-insensitive substrings identifying input-device lines in /proc/interrupts.
+    pub interrupt_patterns: Vec<String>,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        IdleConfig {
+            enabled: true,
+            threshold_minutes: 15,
+            source: IdleSourceKind::Auto,
+            dbus_address: None,
+            interrupt_patterns: vec![
+                "i8042".to_string(),
+                "keyboard".to_string(),
+                "mouse".to_string(),
+                "touchpad".to_string(),
+                "hid".to_string(),
+            ],
+        }
+    }
+}
+
+/// How process names are represented in the app-usage upload.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AppNameMode {
+    /// Only the category is sent; process names never leave the machine.
+    #[default]
+    CategoriesOnly,
+    /// Process names are replaced by a salted SHA-256 hash.
+    Hashed,
+    /// Process names are sent as-is.
+    Names,
+}
+
+/// The `[app_usage]` table of Config.toml.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct AppUsagePrivacyConfig {
+    pub name_mode: AppNameMode,
+
+    /// Salt mixed into every hashed process name. Required when `name_mode = "hashed"`.
+    pub hash_salt: Option<String>,
+
+    /// If non-empty, ONLY these process names are reported.
+    #[serde(default)]
+    pub allowlist: Vec<String>,
+
+    /// Process names that are never reported (applied after the allowlist).
+    #[serde(default)]
+    pub denylist: Vec<String>,
+
+    /// Category name -> process names belonging to it. Unlisted processes fall into "other"; a process
+    /// listed under several categories goes to the alphabetically first one.
+    #[serde(default)]
+    pub categories: BTreeMap<String, Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
@@ -24,69 +482,71 @@ pub struct UserConfig {
     /// Field used by main.rs to prevent re-posting of historical data.
     /// Stores the date (YYYY-MM-DD) of the last successfully posted day.
     pub last_posted_date: Option<String>, // Correctly defined here
+
+    /// The profile this config belongs to, which decides the file it is saved to. Not stored.
+    #[serde(skip)]
+    pub profile: Option<String>,
 }
 
 // --- File Handling Functions ---
+
+/// Reads and parses a TOML config file without printing anything. Used by `status`/`doctor`.
+pub fn read_config_file<T: serde::de::DeserializeOwned>(path: &str) -> Result<T, String> {
+    if !Path::new(path).exists() {
+        return Err(format!("{} not found", path));
+    }
+    let contents = fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path, e))?;
+    toml::from_str(&contents).map_err(|e| format!("Error parsing {}: {}", path, e))
+}
+
 pub fn load_admin_config() -> AdminConfig {
-    let path = Path::new(ADMIN_CONFIG_PATH);
-    if path.exists() {
-        match fs::read_to_string(path) {
-            Ok(contents) => match toml::from_str(&contents) {
-                Ok(config) => {
-                    println!("Admin configuration loaded successfully from {}.", ADMIN_CONFIG_PATH);
-                    config
-                },
-                Err(e) => {
-                    eprintln!("Error parsing {}: {}", ADMIN_CONFIG_PATH, e);
-                    AdminConfig::default()
-                }
-            },
-            Err(e) => {
-                eprintln!("Error reading {}: {}", ADMIN_CONFIG_PATH, e);
-                AdminConfig::default()
-            }
-        }
-    } else {
-        eprintln!("Admin configuration file {} not found.", ADMIN_CONFIG_PATH);
-        AdminConfig::default()
-    }
-}
-
-pub fn load_user_config() -> UserConfig {
-    let path = Path::new(USER_CONFIG_PATH);
-    if path.exists() {
-        match fs::read_to_string(path) {
-            Ok(contents) => match toml::from_str(&contents) {
-                Ok(config) => {
-                    println!("User configuration loaded successfully from {}.", USER_CONFIG_PATH);
-                    config
-                },
-                Err(e) => {
-                    eprintln!("Error parsing {}: {}", USER_CONFIG_PATH, e);
-                    UserConfig::default()
-                }
-            },
-            Err(e) => {
-                eprintln!("Error reading {}: {}", USER_CONFIG_PATH, e);
-                UserConfig::default()
-            }
+    match read_config_file(ADMIN_CONFIG_PATH) {
+        Ok(config) => {
+            info!("Admin configuration loaded successfully from {}.", ADMIN_CONFIG_PATH);
+            config
+        },
+        Err(_) if !Path::new(ADMIN_CONFIG_PATH).exists() => {
+            error!("Admin configuration file {} not found.", ADMIN_CONFIG_PATH);
+            AdminConfig::default()
+        },
+        Err(e) => {
+            error!("{}", e);
+            AdminConfig::default()
         }
-    } else {
-        println!("User configuration file {} not found. Will prompt for login details.", USER_CONFIG_PATH);
-        UserConfig::default()
     }
 }
 
-/// Serializes and saves the updated UserConfig back to the configuration file.
+/// Loads AvadhiConfig.toml, or AvadhiConfig.<profile>.toml for a named profile.
+pub fn load_user_config(profile: Option<&str>) -> UserConfig {
+    let path = profile_path(USER_CONFIG_PATH, profile);
+    let config = match read_config_file(&path) {
+        Ok(config) => {
+            info!("User configuration loaded successfully from {}.", path);
+            config
+        },
+        Err(_) if !Path::new(&path).exists() => {
+            warn!("User configuration file {} not found. Will prompt for login details.", path);
+            UserConfig::default()
+        },
+        Err(e) => {
+            error!("{}", e);
+            UserConfig::default()
+        }
+    };
+    UserConfig { profile: profile.map(str::to_string), ..config }
+}
+
+/// Serializes and saves the updated UserConfig back to the configuration file of its profile.
 pub fn save_user_config(user_config: &UserConfig) {
+    let path = profile_path(USER_CONFIG_PATH, user_config.profile.as_deref());
     match toml::to_string_pretty(user_config) { // Using pretty to make the file readable
         Ok(contents) => {
-            match fs::write(USER_CONFIG_PATH, contents) {
-                Ok(_) => println!("[INFO] User configuration saved successfully to {}.", USER_CONFIG_PATH),
-                Err(e) => eprintln!("Error writing to {}: {}", USER_CONFIG_PATH, e),
+            match fs::write(&path, contents) {
+                Ok(_) => info!("User configuration saved successfully to {}.", path),
+                Err(e) => error!("Error writing to {}: {}", path, e),
             }
         }
-        Err(e) => eprintln!("Error serializing user config: {}", e),
+        Err(e) => error!("Error serializing user config: {}", e),
     }
 }
 
@@ -139,9 +599,10 @@ pub fn initial_setup_and_login(
         refresh_token: Some(refresh_token),
         // === SAVE THE DATE HERE ===
         last_posted_date: initial_last_posted_date,
+        profile: user_config.profile.take(),
     };
 
 
     save_user_config(user_config);
-    println!("Tokens saved to {}.", USER_CONFIG_PATH);
+    println!("Tokens saved to {}.", profile_path(USER_CONFIG_PATH, user_config.profile.as_deref()));
 }
\ No newline at end of file
@@ -0,0 +1,195 @@
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone};
+use anyhow::{anyhow, Result};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+use crate::config::AdminConfig;
+use crate::idle::sample_idle;
+use crate::locks::watch_locks;
+use crate::state::{load_state, CollectorState};
+
+/// Used when Config.toml has no daily_check_hour/daily_check_minute (matches avadhi.timer).
+const DEFAULT_CHECK_HOUR: u32 = 10;
+const DEFAULT_CHECK_MINUTE: u32 = 0;
+
+/// The scheduler never sleeps longer than this in one go. Tokio timers use the monotonic clock,
+/// which does not advance during suspend, so the wall clock is re-checked after every tick.
+const MAX_TICK_SECS: i64 = 60;
+
+/// How often the daemon records an idle sample (replaces avadhi-idle.timer).
+const IDLE_SAMPLE_INTERVAL_MINUTES: i64 = 5;
+
+/// A failed run is retried within the same slot after this many minutes.
+const FAILED_RUN_RETRY_MINUTES: i64 = 30;
+
+/// Returns the configured daily check time, validating the Config.toml values.
+pub fn daily_check_time(admin_config: &AdminConfig) -> Result<NaiveTime> {
+    let hour = admin_config.daily_check_hour.unwrap_or(DEFAULT_CHECK_HOUR);
+    let minute = admin_config.daily_check_minute.unwrap_or(DEFAULT_CHECK_MINUTE);
+    NaiveTime::from_hms_opt(hour, minute, 0)
+        .ok_or_else(|| anyhow!("Invalid daily_check_hour/daily_check_minute in Config.toml: {}:{}", hour, minute))
+}
+
+/// The most recent scheduled slot at or before `now`.
+fn latest_slot(now: DateTime<Local>, check_time: NaiveTime) -> DateTime<Local> {
+    let today = now.date_naive();
+    let slot_on = |date: chrono::NaiveDate| {
+        Local.from_local_datetime(&date.and_time(check_time))
+            .earliest()
+            // The slot falls into a DST gap: use the same wall-clock time one hour later.
+            .unwrap_or_else(|| Local.from_local_datetime(&(date.and_time(check_time) + Duration::hours(1))).earliest().unwrap_or(now))
+    };
+
+    let today_slot = slot_on(today);
+    if today_slot <= now {
+        today_slot
+    } else {
+        slot_on(today - Duration::days(1))
+    }
+}
+
+/// A run is due when the last recorded run happened before the most recent slot.
+/// This also covers catch-up after sleep, downtime, or a daemon restart.
+/// A failed run is retried every FAILED_RUN_RETRY_MINUTES.
+fn run_is_due(now: DateTime<Local>, check_time: NaiveTime, state: &CollectorState) -> bool {
+    match state.last_run_at {
+        Some(last) if last < latest_slot(now, check_time) => true,
+        Some(last) => state.last_run_outcome.as_deref() == Some("failure")
+            && now - last >= Duration::minutes(FAILED_RUN_RETRY_MINUTES),
+        None => true,
+    }
+}
+
+/// Runs continuously: triggers the collector at the configured time each day, samples idle
+/// state periodically, watches screen locks, and exits cleanly on SIGTERM/SIGINT.
+///
+/// `run_once` performs one full collector run of every profile (including recording their outcomes);
+/// it is due as soon as one profile's last run is.
+pub async fn run_daemon<F, Fut>(admin_config: &AdminConfig, mut run_once: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<()>>,
+{
+    let check_time = daily_check_time(admin_config)?;
+    let profiles = admin_config.selected_profiles(None)?;
+    info!("Daemon started. Daily collector run scheduled at {} local time.", check_time.format("%H:%M"));
+
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sigint = signal(SignalKind::interrupt())?;
+
+    if admin_config.lock.enabled {
+        let lock_config = admin_config.lock.clone();
+        tokio::spawn(async move {
+            if let Err(e) = watch_locks(&lock_config).await {
+                warn!("Lock watcher stopped: {}. Locked intervals will only be polled.", e);
+            }
+        });
+    }
+
+    let mut next_idle_sample = Local::now();
+
+    loop {
+        let now = Local::now();
+
+        if admin_config.idle.enabled && now >= next_idle_sample {
+            if let Err(e) = sample_idle(&admin_config.idle).await {
+                warn!("Idle sample failed: {}", e);
+            }
+            next_idle_sample = now + Duration::minutes(IDLE_SAMPLE_INTERVAL_MINUTES);
+        }
+
+        if profiles.iter().any(|profile| run_is_due(now, check_time, &load_state(profile.as_deref()))) {
+            info!("Scheduled collector run is due (slot {}).", latest_slot(now, check_time).format("%Y-%m-%d %H:%M"));
+            // A stop request cancels the run instead of waiting for it (the run deadline is far longer than
+            // systemd's stop timeout). Upserts are idempotent and the cancelled run is not recorded as done,
+            // so the next start simply runs the slot again.
+            tokio::select! {
+                result = run_once() => if let Err(e) = result {
+                    error!("Scheduled collector run failed: {}. Retrying in {} minutes.", e, FAILED_RUN_RETRY_MINUTES);
+                },
+                _ = sigterm.recv() => {
+                    info!("SIGTERM received during a collector run. Cancelling it; daemon shutting down.");
+                    return Ok(());
+                },
+                _ = sigint.recv() => {
+                    info!("SIGINT received during a collector run. Cancelling it; daemon shutting down.");
+                    return Ok(());
+                },
+            }
+        }
+
+        let mut until_next_event = latest_slot(now, check_time) + Duration::days(1) - Local::now();
+        if admin_config.idle.enabled {
+            until_next_event = until_next_event.min(next_idle_sample - Local::now());
+        }
+        let tick = until_next_event.num_seconds().clamp(1, MAX_TICK_SECS);
+
+        tokio::select! {
+            _ = sleep(std::time::Duration::from_secs(tick as u64)) => {},
+            _ = sigterm.recv() => {
+                info!("SIGTERM received. Daemon shutting down.");
+                return Ok(());
+            },
+            _ = sigint.recv() => {
+                info!("SIGINT received. Daemon shutting down.");
+                return Ok(());
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, day, hour, minute, 0).unwrap()
+    }
+
+    fn ten() -> NaiveTime {
+        NaiveTime::from_hms_opt(10, 0, 0).unwrap()
+    }
+
+    fn last_run(at: DateTime<Local>, outcome: &str) -> CollectorState {
+        CollectorState { last_run_at: Some(at), last_run_outcome: Some(outcome.to_string()), ..CollectorState::default() }
+    }
+
+    #[test]
+    fn latest_slot_is_today_once_the_check_time_has_passed() {
+        assert_eq!(latest_slot(at(13, 9, 59), ten()), at(12, 10, 0));
+        assert_eq!(latest_slot(at(13, 10, 0), ten()), at(13, 10, 0));
+        assert_eq!(latest_slot(at(13, 23, 59), ten()), at(13, 10, 0));
+    }
+
+    #[test]
+    fn first_run_is_due_immediately() {
+        assert!(run_is_due(at(13, 8, 0), ten(), &CollectorState::default()));
+    }
+
+    #[test]
+    fn missed_slot_is_caught_up_after_suspend() {
+        // Suspended from yesterday evening until 14:00, past today's 10:00 slot.
+        assert!(run_is_due(at(13, 14, 0), ten(), &last_run(at(12, 10, 0), "success")));
+        // Several days of downtime still mean one catch-up run.
+        assert!(run_is_due(at(16, 9, 0), ten(), &last_run(at(12, 10, 0), "success")));
+    }
+
+    #[test]
+    fn slot_already_run_is_not_repeated() {
+        assert!(!run_is_due(at(13, 15, 0), ten(), &last_run(at(13, 10, 1), "success")));
+        // Before today's slot, yesterday's run covers the latest slot.
+        assert!(!run_is_due(at(13, 9, 0), ten(), &last_run(at(12, 10, 5), "success")));
+        // A manual run after the slot counts as well.
+        assert!(!run_is_due(at(13, 12, 0), ten(), &last_run(at(13, 11, 0), "success")));
+    }
+
+    #[test]
+    fn failed_run_is_retried_after_thirty_minutes() {
+        let failed = last_run(at(13, 10, 1), "failure");
+        assert!(!run_is_due(at(13, 10, 20), ten(), &failed));
+        assert!(!run_is_due(at(13, 10, 30), ten(), &failed));
+        assert!(run_is_due(at(13, 10, 31), ten(), &failed));
+        assert!(run_is_due(at(13, 18, 0), ten(), &failed));
+    }
+}
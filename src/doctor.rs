@@ -0,0 +1,375 @@
+use reqwest::{StatusCode, Url};
+use std::fs::{self, File};
+use std::net::IpAddr;
+use std::os::unix::fs::MetadataExt;
+use std::process::Command;
+
+use crate::api::{fetch_auth_settings, probe_work_span_read};
+use crate::config::{profile_path, read_config_file, AdminConfig, UserConfig, ADMIN_CONFIG_PATH, USER_CONFIG_PATH};
+use crate::jwt;
+use crate::span_policy::validate_policy;
+use crate::sessions::{parse_last_output, run_last_command, WTMP_PATH};
+
+/// The account the systemd unit runs as.
+const SERVICE_USER: &str = "avadhi";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckOutcome {
+    Pass,
+    Fail,
+    /// Not run because a check it depends on failed.
+    Skip,
+}
+
+#[derive(Debug)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub outcome: CheckOutcome,
+    pub detail: String,
+    /// What to do about a failure. Empty for passing checks.
+    pub remediation: String,
+}
+
+fn pass(name: &'static str, detail: impl Into<String>) -> CheckResult {
+    CheckResult { name, outcome: CheckOutcome::Pass, detail: detail.into(), remediation: String::new() }
+}
+
+fn fail(name: &'static str, detail: impl Into<String>, remediation: impl Into<String>) -> CheckResult {
+    CheckResult { name, outcome: CheckOutcome::Fail, detail: detail.into(), remediation: remediation.into() }
+}
+
+fn skip(name: &'static str, reason: impl Into<String>) -> CheckResult {
+    CheckResult { name, outcome: CheckOutcome::Skip, detail: reason.into(), remediation: String::new() }
+}
+
+// --- Individual Checks ---
+
+fn check_url(name: &'static str, value: Option<&str>, key: &str) -> CheckResult {
+    match value {
+        None => fail(name, format!("'{}' is not set.", key), format!("Set '{}' in {} (see Config.toml.example).", key, ADMIN_CONFIG_PATH)),
+        Some(raw) => match Url::parse(raw) {
+            Ok(url) if url.scheme() == "https" && url.host().is_some() => pass(name, raw),
+            Ok(url) if url.scheme() == "http" && url.host_str().is_some_and(is_loopback) => pass(name, format!("{} (plain http, loopback only)", raw)),
+            Ok(url) => fail(name, format!("'{}' uses scheme '{}' or has no host.", raw, url.scheme()),
+                format!("Use a full https:// URL for '{}' in {} (http is only accepted for localhost).", key, ADMIN_CONFIG_PATH)),
+            Err(e) => fail(name, format!("'{}' is not a valid URL: {}", raw, e),
+                format!("Fix '{}' in {}.", key, ADMIN_CONFIG_PATH)),
+        },
+    }
+}
+
+/// A local Supabase (`supabase start`) listens on plain http; that never leaves the machine.
+fn is_loopback(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost")
+        || host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+fn check_anon_key(admin_config: &AdminConfig) -> CheckResult {
+    const NAME: &str = "Anon key is a JWT";
+    let Some(key) = admin_config.supabase_anon_key.as_deref() else {
+        return fail(NAME, "'supabase_anon_key' is not set.",
+            format!("Copy the anon/public key from the Supabase project settings into {}.", ADMIN_CONFIG_PATH));
+    };
+
+    match jwt::decode_claims(key) {
+        Ok(claims) => match claims["role"].as_str() {
+            Some("anon") => pass(NAME, "role=anon"),
+            Some(role) => fail(NAME, format!("Key has role '{}', expected 'anon'.", role),
+                "Use the project's anon/public key, never the service_role key."),
+            None => fail(NAME, "Key has no 'role' claim.", "Use the project's anon/public key."),
+        },
+        Err(e) => fail(NAME, e.to_string(), "Re-copy the anon/public key; it should look like xxxxx.yyyyy.zzzzz."),
+    }
+}
+
+fn check_span_policy(admin_config: &AdminConfig) -> CheckResult {
+    const NAME: &str = "span_policy is valid";
+    match validate_policy(&admin_config.span_policy) {
+        Ok(()) => pass(NAME, "[span_policy] values are consistent."),
+        Err(e) => fail(NAME, e.to_string(), format!("Fix the [span_policy] section of {} (see Config.toml.example).", ADMIN_CONFIG_PATH)),
+    }
+}
+
+fn current_user() -> String {
+    if let Ok(user) = std::env::var("USER") {
+        return user;
+    }
+    Command::new("id").arg("-un").output().ok()
+        .filter(|out| out.status.success())
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// The service account's uid and groups as `id` resolves them (getpwnam/getgrouplist),
+/// i.e. what the kernel checks file access against when the unit runs.
+struct ServiceAccount {
+    uid: u32,
+    gids: Vec<u32>,
+    groups: Vec<String>,
+}
+
+fn id_of_service_user(flag: &str) -> Option<String> {
+    Command::new("id").args([flag, SERVICE_USER]).output().ok()
+        .filter(|out| out.status.success())
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+fn service_account() -> Option<ServiceAccount> {
+    Some(ServiceAccount {
+        uid: id_of_service_user("-u")?.parse().ok()?,
+        gids: id_of_service_user("-G")?.split_whitespace().filter_map(|gid| gid.parse().ok()).collect(),
+        groups: id_of_service_user("-Gn")?.split_whitespace().map(str::to_string).collect(),
+    })
+}
+
+fn missing_service_user(name: &'static str) -> CheckResult {
+    fail(name, format!("Service user '{}' does not exist.", SERVICE_USER),
+        format!("Create it as install/linux/install.sh does: sudo useradd --system --shell /usr/sbin/nologin {}", SERVICE_USER))
+}
+
+/// Whether `account` may read a file with these mode bits and owner. Like the kernel, only the
+/// first matching class (owner, group, other) counts; ACLs are not considered.
+fn readable_by(mode: u32, owner: u32, group: u32, account: &ServiceAccount) -> bool {
+    if account.uid == 0 {
+        true
+    } else if owner == account.uid {
+        mode & 0o400 != 0
+    } else if account.gids.contains(&group) {
+        mode & 0o040 != 0
+    } else {
+        mode & 0o004 != 0
+    }
+}
+
+fn check_wtmp_readable() -> CheckResult {
+    const NAME: &str = "wtmp is readable";
+    let Some(account) = service_account() else {
+        return missing_service_user(NAME);
+    };
+    let meta = match fs::metadata(WTMP_PATH) {
+        Ok(meta) => meta,
+        Err(e) => return fail(NAME, format!("Cannot stat {}: {}", WTMP_PATH, e),
+            format!("Ensure {} exists (it is written by login and systemd).", WTMP_PATH)),
+    };
+
+    let mode = meta.mode() & 0o777;
+    if !readable_by(mode, meta.uid(), meta.gid(), &account) {
+        return fail(NAME, format!("{} is mode {:o}, uid {}, gid {}: not readable by '{}'.", WTMP_PATH, mode, meta.uid(), meta.gid(), SERVICE_USER),
+            format!("Make it world-readable (the usual mode is 664, group utmp): sudo chmod o+r {}", WTMP_PATH));
+    }
+
+    // The mode bits say yes; when running as the service account, confirm nothing else (ACLs,
+    // directory permissions, MAC policy) says no.
+    if current_user() == SERVICE_USER {
+        if let Err(e) = File::open(WTMP_PATH) {
+            return fail(NAME, format!("Cannot open {} as '{}': {}", WTMP_PATH, SERVICE_USER, e),
+                format!("Check ACLs and SELinux/AppArmor policy on {}.", WTMP_PATH));
+        }
+    }
+    pass(NAME, format!("{} (mode {:o}) readable by '{}'.", WTMP_PATH, mode, SERVICE_USER))
+}
+
+/// Groups that journald grants read access to the system journal.
+const JOURNAL_GROUPS: [&str; 3] = ["systemd-journal", "adm", "wheel"];
+
+fn check_journal_readable() -> CheckResult {
+    const NAME: &str = "Journal is readable";
+    let Some(account) = service_account() else {
+        return missing_service_user(NAME);
+    };
+    let journal_group = account.groups.iter().find(|group| JOURNAL_GROUPS.contains(&group.as_str()));
+    if account.uid != 0 && journal_group.is_none() {
+        return fail(NAME, format!("'{}' is in none of the journal groups ({}).", SERVICE_USER, JOURNAL_GROUPS.join(", ")),
+            format!("Add the service user to the journal group: sudo usermod -aG systemd-journal {}", SERVICE_USER));
+    }
+
+    let output = Command::new("journalctl").args(["-n", "1", "-q", "--no-pager"]).output();
+    let via = journal_group.map(|group| format!(" (group {})", group)).unwrap_or_default();
+    match output {
+        Ok(out) if out.status.success() || current_user() != SERVICE_USER => pass(NAME, format!("'{}' can read the journal{}.", SERVICE_USER, via)),
+        Ok(out) => fail(NAME, String::from_utf8_lossy(&out.stderr).trim().to_string(),
+            format!("Add the service user to the journal group: sudo usermod -aG systemd-journal {}", SERVICE_USER)),
+        Err(e) => fail(NAME, format!("Cannot execute journalctl: {}", e),
+            "journalctl is only available on systemd systems; use daemon mode logging elsewhere."),
+    }
+}
+
+async fn check_session_source() -> CheckResult {
+    const NAME: &str = "Session source returns data";
+    let sessions = match run_last_command().await {
+        Ok(stdout) => parse_last_output(&stdout),
+        Err(e) => Err(e),
+    };
+    match sessions {
+        Ok(s) if !s.is_empty() => pass(NAME, format!("{} boot session(s) parsed.", s.len())),
+        Ok(_) => fail(NAME, "'last -x -F reboot' returned no boot sessions.",
+            format!("Check that {} is not empty or rotated away, and that the 'last' command (util-linux) is installed.", WTMP_PATH)),
+        Err(e) => fail(NAME, e.to_string(), "Install util-linux ('last') and make sure wtmp is readable."),
+    }
+}
+
+async fn check_auth_settings(admin_config: &AdminConfig) -> CheckResult {
+    const NAME: &str = "Supabase reachable (/auth/v1/settings)";
+    match fetch_auth_settings(admin_config).await {
+        Ok((status, _)) if status.is_success() => pass(NAME, format!("HTTP {}", status)),
+        Ok((StatusCode::UNAUTHORIZED, _)) => fail(NAME, "HTTP 401: the anon key was rejected.",
+            "The anon key does not belong to this Supabase project. Re-copy it from the project settings."),
+        Ok((status, body)) => fail(NAME, format!("HTTP {}: {}", status, body.trim()), "Check supabase_url and the project status in the Supabase dashboard."),
+        Err(e) => fail(NAME, e.to_string(), "Check network connectivity, DNS, proxies and supabase_url."),
+    }
+}
+
+async fn check_authenticated_read(admin_config: &AdminConfig, user_config: &UserConfig) -> CheckResult {
+    const NAME: &str = "Authenticated read of daily_work_span";
+    match probe_work_span_read(admin_config, user_config).await {
+        Ok((status, _)) if status.is_success() => pass(NAME, format!("HTTP {}", status)),
+        Ok((StatusCode::UNAUTHORIZED, body)) => fail(NAME, format!("HTTP 401: {}", body.trim()),
+            "The access token is expired or invalid. A normal 'run' refreshes it automatically; if that fails, run './avadhi-collector setup'."),
+        Ok((status, body)) if status == StatusCode::FORBIDDEN || body.contains("policy") || body.contains("permission") => fail(NAME, format!("HTTP {}: {}", status, body.trim()),
+            "Row Level Security denies SELECT. Ensure authenticated users may read their own rows in daily_work_span."),
+        Ok((status, body)) => fail(NAME, format!("HTTP {}: {}", status, body.trim()), "Check that the daily_work_span table exists (see daily_work_span.sql)."),
+        Err(e) => fail(NAME, e.to_string(), "Check network connectivity and supabase_url."),
+    }
+}
+
+// --- Runner ---
+
+/// Runs every preflight check in dependency order. Network checks are skipped when config checks fail.
+/// With `profile`, the backend and credentials of that [profiles] entry are checked.
+pub async fn run_checks(profile: Option<&str>) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+
+    let admin_config = match read_config_file::<AdminConfig>(ADMIN_CONFIG_PATH) {
+        Ok(config) => {
+            results.push(pass("Config.toml parses", ADMIN_CONFIG_PATH));
+            Some(config)
+        },
+        Err(e) => {
+            results.push(fail("Config.toml parses", e, "Create it from Config.toml.example in the install directory and fix any TOML syntax errors."));
+            None
+        }
+    };
+
+    let admin_config = match (admin_config, profile) {
+        (Some(config), Some(name)) => match config.for_profile(Some(name)) {
+            Ok(config) => {
+                results.push(pass("Profile is configured", format!("[profiles.{}]", name)));
+                Some(config)
+            },
+            Err(e) => {
+                results.push(fail("Profile is configured", e.to_string(), "Add the [profiles.<name>] table to Config.toml or check the --profile spelling."));
+                None
+            }
+        },
+        (config, _) => config,
+    };
+
+    let setup = match profile {
+        Some(name) => format!("./avadhi-collector setup --profile {}", name),
+        None => "./avadhi-collector setup".to_string(),
+    };
+    let user_config_path = profile_path(USER_CONFIG_PATH, profile);
+    let user_config = match read_config_file::<UserConfig>(&user_config_path) {
+        Ok(config) => {
+            results.push(pass("AvadhiConfig.toml parses", user_config_path));
+            Some(config)
+        },
+        Err(e) => {
+            results.push(fail("AvadhiConfig.toml parses", e, format!("Run '{}' to create it.", setup)));
+            None
+        }
+    };
+
+    let mut config_ok = admin_config.is_some();
+    if let Some(admin) = admin_config.as_ref() {
+        for check in [
+            check_url("supabase_url is valid", admin.supabase_url.as_deref(), "supabase_url"),
+            check_url("web_app_url is valid", admin.web_app_url.as_deref(), "web_app_url"),
+            check_anon_key(admin),
+            check_span_policy(admin),
+        ] {
+            config_ok &= check.outcome == CheckOutcome::Pass;
+            results.push(check);
+        }
+    }
+
+    results.push(check_wtmp_readable());
+    results.push(check_journal_readable());
+    results.push(check_session_source().await);
+
+    match (admin_config.as_ref(), config_ok) {
+        (Some(admin), true) => {
+            let reachable = check_auth_settings(admin).await;
+            let reachable_ok = reachable.outcome == CheckOutcome::Pass;
+            results.push(reachable);
+
+            const READ: &str = "Authenticated read of daily_work_span";
+            match user_config.as_ref() {
+                Some(user) if reachable_ok && user.access_token.is_some() => results.push(check_authenticated_read(admin, user).await),
+                Some(_) if reachable_ok => results.push(fail(READ, "No access token stored.", format!("Run '{}'.", setup))),
+                _ => results.push(skip(READ, "Skipped: Supabase not reachable or AvadhiConfig.toml missing.")),
+            }
+        },
+        _ => {
+            results.push(skip("Supabase reachable (/auth/v1/settings)", "Skipped: fix Config.toml first."));
+            results.push(skip("Authenticated read of daily_work_span", "Skipped: fix Config.toml first."));
+        }
+    }
+
+    results
+}
+
+/// Prints one pass/fail line per check with remediation text. Returns true if nothing failed.
+pub fn print_checks(results: &[CheckResult]) -> bool {
+    println!("Avadhi Collector doctor (running as '{}')\n", current_user());
+
+    for result in results {
+        let tag = match result.outcome {
+            CheckOutcome::Pass => "PASS",
+            CheckOutcome::Fail => "FAIL",
+            CheckOutcome::Skip => "SKIP",
+        };
+        println!("[{}] {} - {}", tag, result.name, result.detail);
+        if result.outcome == CheckOutcome::Fail {
+            println!("       -> {}", result.remediation);
+        }
+    }
+
+    let failed = results.iter().filter(|r| r.outcome == CheckOutcome::Fail).count();
+    println!("\n{} check(s), {} failed.", results.len(), failed);
+    failed == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(uid: u32, gids: &[u32]) -> ServiceAccount {
+        ServiceAccount { uid, gids: gids.to_vec(), groups: Vec::new() }
+    }
+
+    #[test]
+    fn readable_by_uses_the_first_matching_class() {
+        let avadhi = account(990, &[990, 43]);
+        // The usual wtmp: root:utmp 664.
+        assert!(readable_by(0o664, 0, 43, &avadhi));
+        assert!(!readable_by(0o660, 0, 22, &avadhi));
+        assert!(readable_by(0o664, 0, 22, &avadhi));
+        // The owner class decides even when group or other would allow it.
+        assert!(!readable_by(0o044, 990, 43, &avadhi));
+        assert!(!readable_by(0o604, 0, 43, &avadhi));
+        assert!(readable_by(0o000, 5, 5, &account(0, &[0])));
+    }
+
+    #[test]
+    fn http_is_only_accepted_for_loopback_hosts() {
+        let outcome = |url: &str| check_url("url", Some(url), "supabase_url").outcome;
+        assert_eq!(outcome("https://x.supabase.co"), CheckOutcome::Pass);
+        assert_eq!(outcome("http://localhost:54321"), CheckOutcome::Pass);
+        assert_eq!(outcome("http://127.0.0.1:54321"), CheckOutcome::Pass);
+        assert_eq!(outcome("http://[::1]:54321"), CheckOutcome::Pass);
+        assert_eq!(outcome("http://x.supabase.co"), CheckOutcome::Fail);
+        assert_eq!(outcome("http://10.0.0.5"), CheckOutcome::Fail);
+        assert_eq!(outcome("ftp://localhost"), CheckOutcome::Fail);
+    }
+}
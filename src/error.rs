@@ -0,0 +1,118 @@
+//! Typed failures of a collector run and the process exit codes they map to.
+
+use reqwest::StatusCode;
+
+use crate::api::ApiStatusError;
+
+/// Failures that callers (systemd `OnFailure=`, monitoring, scripts) can tell apart by exit code.
+///
+/// | Code | Meaning |
+/// |------|---------|
+/// | 0    | Success |
+/// | 1    | Any other failure |
+/// | 2    | Invalid command line |
+/// | 3    | `ConfigMissing` |
+/// | 4    | `AuthExpired` |
+/// | 5    | `RefreshTokenConsumed` |
+/// | 6    | `SourceUnavailable` |
+/// | 7    | `Network` |
+/// | 8    | `Server` |
+/// | 9    | `RlsDenied` |
+/// | 10   | `Rejected` |
+/// | 11   | `DeadlineExceeded` |
+/// | 12   | `doctor` found failing checks |
+#[derive(Debug, thiserror::Error)]
+pub enum CollectorError {
+    /// Config.toml or AvadhiConfig.toml lacks required values (URL, anon key, tokens, user ID), or an `[http]`
+    /// setting is unusable (proxy URL, CA bundle or client certificate).
+    #[error("Configuration incomplete: {0}")]
+    ConfigMissing(String),
+
+    /// The access token was rejected and could not be refreshed without interactive setup.
+    #[error("Authentication expired: {0}")]
+    AuthExpired(String),
+
+    /// The single-use refresh token was already used. Only `setup` can recover.
+    #[error("Refresh token consumed (Already Used), run 'avadhi-collector setup' to log in again")]
+    RefreshTokenConsumed,
+
+    /// Boot sessions could not be read (`last` missing, wtmp unreadable or unparsable).
+    #[error("Boot session source unavailable: {0}")]
+    SourceUnavailable(String),
+
+    /// The backend could not be reached (DNS, connection, TLS, timeout).
+    #[error("Network error: {0}")]
+    Network(String),
+
+    /// The backend kept answering with 5xx.
+    #[error(transparent)]
+    Server(ApiStatusError),
+
+    /// The request was refused by a Row Level Security policy (403 or a policy/permission message).
+    #[error(transparent)]
+    RlsDenied(ApiStatusError),
+
+    /// Any other 4xx response.
+    #[error(transparent)]
+    Rejected(ApiStatusError),
+
+    /// The run took longer than `[retry] run_deadline_seconds` and was stopped.
+    #[error("Run deadline of {0}s exceeded")]
+    DeadlineExceeded(u64),
+}
+
+impl CollectorError {
+    pub const EXIT_FAILURE: i32 = 1;
+    pub const EXIT_USAGE: i32 = 2;
+    pub const EXIT_CONFIG_MISSING: i32 = 3;
+    pub const EXIT_AUTH_EXPIRED: i32 = 4;
+    pub const EXIT_REFRESH_TOKEN_CONSUMED: i32 = 5;
+    pub const EXIT_SOURCE_UNAVAILABLE: i32 = 6;
+    pub const EXIT_NETWORK: i32 = 7;
+    pub const EXIT_SERVER: i32 = 8;
+    pub const EXIT_RLS_DENIED: i32 = 9;
+    pub const EXIT_REJECTED: i32 = 10;
+    pub const EXIT_DEADLINE_EXCEEDED: i32 = 11;
+    /// Not an error variant: `doctor` ran to completion but at least one check failed.
+    pub const EXIT_DOCTOR_FAILED: i32 = 12;
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CollectorError::ConfigMissing(_) => Self::EXIT_CONFIG_MISSING,
+            CollectorError::AuthExpired(_) => Self::EXIT_AUTH_EXPIRED,
+            CollectorError::RefreshTokenConsumed => Self::EXIT_REFRESH_TOKEN_CONSUMED,
+            CollectorError::SourceUnavailable(_) => Self::EXIT_SOURCE_UNAVAILABLE,
+            CollectorError::Network(_) => Self::EXIT_NETWORK,
+            CollectorError::Server(_) => Self::EXIT_SERVER,
+            CollectorError::RlsDenied(_) => Self::EXIT_RLS_DENIED,
+            CollectorError::Rejected(_) => Self::EXIT_REJECTED,
+            CollectorError::DeadlineExceeded(_) => Self::EXIT_DEADLINE_EXCEEDED,
+        }
+    }
+
+    /// The HTTP response behind this error, if the server answered.
+    pub fn api_error(&self) -> Option<&ApiStatusError> {
+        match self {
+            CollectorError::Server(e) | CollectorError::RlsDenied(e) | CollectorError::Rejected(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Classifies a non-success response that is not a 401.
+    pub fn from_status(error: ApiStatusError) -> Self {
+        if error.status.is_server_error() {
+            CollectorError::Server(error)
+        } else if error.status == StatusCode::FORBIDDEN || error.body.contains("policy") || error.body.contains("permission") {
+            CollectorError::RlsDenied(error)
+        } else {
+            CollectorError::Rejected(error)
+        }
+    }
+}
+
+/// The exit code for a failed command: the first `CollectorError` in the error chain, or 1.
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    error.chain()
+        .find_map(|e| e.downcast_ref::<CollectorError>())
+        .map_or(CollectorError::EXIT_FAILURE, CollectorError::exit_code)
+}
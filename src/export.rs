@@ -0,0 +1,345 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, SubsecRound, TimeZone, Utc};
+use serde::Serialize;
+use anyhow::{anyhow, Result};
+use std::io::Write;
+
+use crate::api::WorkSpanData;
+use crate::calendar::DayType;
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum ExportFormat {
+    /// Comma-separated values with a header row.
+    Csv,
+    /// One JSON object per line.
+    Jsonl,
+    /// iCalendar with one VEVENT per day (first boot to last shutdown).
+    Ics,
+}
+
+/// One exported day. Fields and order follow the daily_work_span table (see daily_work_span.sql).
+#[derive(Debug, Serialize)]
+pub struct ExportRow {
+    pub date: String,
+    /// Start of the day's span (first boot) in UTC.
+    pub timestamp: DateTime<Utc>,
+    pub first_boot: String,
+    pub last_shutdown: String,
+    pub total_span_minutes: i32,
+    pub total_span: String,
+    pub adjusted: bool,
+    pub note: Option<String>,
+    pub day_type: DayType,
+    pub expected_minutes: i32,
+    pub deviation_minutes: i32,
+    pub week_balance_minutes: i32,
+    pub month_balance_minutes: i32,
+    /// When this export computed the row.
+    pub updated_at: DateTime<Utc>,
+}
+
+const CSV_HEADER: &str = "date,timestamp,first_boot,last_shutdown,total_span_minutes,total_span,adjusted,note,day_type,expected_minutes,deviation_minutes,week_balance_minutes,month_balance_minutes,updated_at";
+
+/// A wall-clock time as an instant in `tz`. When the clock is turned back the time exists twice: a start
+/// takes the first, an end the second. A time skipped when the clock jumps forward (only possible
+/// through `adjust --span`) is read as the same wall-clock time one hour later.
+fn to_zone<Tz: TimeZone>(tz: &Tz, date: NaiveDate, time: NaiveTime, is_end: bool) -> Result<DateTime<Tz>> {
+    let at = |naive: NaiveDateTime| {
+        let local = tz.from_local_datetime(&naive);
+        if is_end { local.latest() } else { local.earliest() }
+    };
+    let naive = date.and_time(time);
+    at(naive).or_else(|| at(naive + Duration::hours(1)))
+        .ok_or_else(|| anyhow!("{} {} does not exist in the local time zone", date, time))
+}
+
+/// The start and end of a day's span in `tz`. A last_shutdown before first_boot belongs to the next day.
+fn span_bounds_in<Tz: TimeZone>(tz: &Tz, span: &WorkSpanData) -> Result<(DateTime<Tz>, DateTime<Tz>)> {
+    let date = NaiveDate::parse_from_str(&span.date, "%Y-%m-%d")?;
+    let first = NaiveTime::parse_from_str(&span.first_boot, "%H:%M:%S")?;
+    let last = NaiveTime::parse_from_str(&span.last_shutdown, "%H:%M:%S")?;
+    let end_date = if last < first { date + Duration::days(1) } else { date };
+    Ok((to_zone(tz, date, first, false)?, to_zone(tz, end_date, last, true)?))
+}
+
+/// The local start and end of a day's span.
+fn span_bounds(span: &WorkSpanData) -> Result<(DateTime<Local>, DateTime<Local>)> {
+    span_bounds_in(&Local, span)
+}
+
+pub fn export_rows(spans: &[WorkSpanData], updated_at: DateTime<Utc>) -> Result<Vec<ExportRow>> {
+    spans.iter()
+        .map(|span| {
+            let (start, _) = span_bounds(span)?;
+            Ok(ExportRow {
+                date: span.date.clone(),
+                timestamp: start.with_timezone(&Utc),
+                first_boot: span.first_boot.clone(),
+                last_shutdown: span.last_shutdown.clone(),
+                total_span_minutes: span.total_span_minutes,
+                total_span: span.total_span.clone(),
+                adjusted: span.adjusted,
+                note: span.note.clone(),
+                day_type: span.day_type,
+                expected_minutes: span.expected_minutes,
+                deviation_minutes: span.deviation_minutes,
+                week_balance_minutes: span.week_balance_minutes,
+                month_balance_minutes: span.month_balance_minutes,
+                updated_at,
+            })
+        })
+        .collect()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn write_csv(out: &mut dyn Write, rows: &[ExportRow]) -> Result<()> {
+    writeln!(out, "{}", CSV_HEADER)?;
+    for row in rows {
+        let fields = [
+            row.date.clone(),
+            row.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+            row.first_boot.clone(),
+            row.last_shutdown.clone(),
+            row.total_span_minutes.to_string(),
+            row.total_span.clone(),
+            row.adjusted.to_string(),
+            row.note.clone().unwrap_or_default(),
+            row.day_type.as_str().to_string(),
+            row.expected_minutes.to_string(),
+            row.deviation_minutes.to_string(),
+            row.week_balance_minutes.to_string(),
+            row.month_balance_minutes.to_string(),
+            row.updated_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ];
+        let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        writeln!(out, "{}", line.join(","))?;
+    }
+    Ok(())
+}
+
+fn write_jsonl(out: &mut dyn Write, rows: &[ExportRow]) -> Result<()> {
+    for row in rows {
+        writeln!(out, "{}", serde_json::to_string(row)?)?;
+    }
+    Ok(())
+}
+
+/// Escapes TEXT values per RFC 5545 section 3.3.11. Any line break (CRLF, CR or LF) becomes `\n`.
+fn ics_text(value: &str) -> String {
+    value.replace("\r\n", "\n").replace('\r', "\n")
+        .replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
+
+/// Folds a content line at 75 octets (RFC 5545 section 3.1). Notes are free text, so a line is only
+/// broken between UTF-8 characters, never inside one.
+fn ics_fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut rest = line;
+    let mut limit = 75;
+    while rest.len() > limit {
+        let at = (0..=limit).rev().find(|&i| rest.is_char_boundary(i)).unwrap_or(0);
+        let (head, tail) = rest.split_at(at);
+        folded.push_str(head);
+        folded.push_str("\r\n ");
+        rest = tail;
+        // Continuation lines start with a space, which counts towards the limit.
+        limit = 74;
+    }
+    folded.push_str(rest);
+    folded
+}
+
+fn ics_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn write_ics(out: &mut dyn Write, spans: &[WorkSpanData], generated_at: DateTime<Utc>) -> Result<()> {
+    // RFC 5545 requires CRLF line endings.
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Avadhi//Avadhi Collector//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    for span in spans {
+        let (start, end) = span_bounds(span)?;
+        if start == end {
+            // Excluded via `adjust --exclude`: nothing to show.
+            continue;
+        }
+        let mut description = format!(
+            "First boot {}, last shutdown {}. {} active minute(s), {} idle/locked minute(s) excluded.",
+            span.first_boot, span.last_shutdown, span.total_span_minutes, span.excluded_minutes);
+        if span.adjusted {
+            description.push_str(" Manually adjusted.");
+        }
+        if let Some(note) = &span.note {
+            description.push_str(&format!(" Note: {}", note));
+        }
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}@avadhi-collector", span.date),
+            format!("DTSTAMP:{}", ics_time(generated_at)),
+            format!("DTSTART:{}", ics_time(start.with_timezone(&Utc))),
+            format!("DTEND:{}", ics_time(end.with_timezone(&Utc))),
+            format!("SUMMARY:{}", ics_text(&format!("Work span {}", span.total_span))),
+            format!("DESCRIPTION:{}", ics_text(&description)),
+            "TRANSP:TRANSPARENT".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    for line in lines {
+        write!(out, "{}\r\n", ics_fold(&line))?;
+    }
+    Ok(())
+}
+
+/// Writes `spans` (already limited to the requested range) in `format`.
+pub fn write_export(out: &mut dyn Write, spans: &[WorkSpanData], format: ExportFormat) -> Result<()> {
+    let now = Utc::now().trunc_subsecs(0);
+    match format {
+        ExportFormat::Csv => write_csv(out, &export_rows(spans, now)?),
+        ExportFormat::Jsonl => write_jsonl(out, &export_rows(spans, now)?),
+        ExportFormat::Ics => write_ics(out, spans, now),
+    }?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, MappedLocalTime};
+
+    /// Central European time with the 2026 switches: +02:00 from Mar 29 01:00 UTC to Oct 25 01:00 UTC, else +01:00.
+    #[derive(Debug, Clone, Copy)]
+    struct Cet2026;
+
+    fn offset(hours: i32) -> FixedOffset {
+        FixedOffset::east_opt(hours * 3600).unwrap()
+    }
+
+    fn utc(month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, month, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    impl TimeZone for Cet2026 {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            Cet2026
+        }
+
+        fn offset_from_utc_datetime(&self, utc_time: &NaiveDateTime) -> FixedOffset {
+            if (utc(3, 29, 1, 0)..utc(10, 25, 1, 0)).contains(utc_time) { offset(2) } else { offset(1) }
+        }
+
+        fn offset_from_utc_date(&self, utc_date: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc_date.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> MappedLocalTime<FixedOffset> {
+            let valid: Vec<FixedOffset> = [offset(2), offset(1)].into_iter()
+                .filter(|o| self.offset_from_utc_datetime(&(*local - Duration::seconds(o.local_minus_utc() as i64))) == *o)
+                .collect();
+            match valid[..] {
+                [single] => MappedLocalTime::Single(single),
+                [earliest, latest] => MappedLocalTime::Ambiguous(earliest, latest),
+                _ => MappedLocalTime::None,
+            }
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> MappedLocalTime<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+        }
+    }
+
+    fn span(date: &str, first_boot: &str, last_shutdown: &str) -> WorkSpanData {
+        WorkSpanData {
+            date: date.to_string(),
+            total_span_minutes: 0,
+            total_span: "0h 0m".to_string(),
+            first_boot: first_boot.to_string(),
+            last_shutdown: last_shutdown.to_string(),
+            excluded_minutes: 0,
+            adjusted: false,
+            note: None,
+            day_type: DayType::Workday,
+            expected_minutes: 0,
+            deviation_minutes: 0,
+            week_balance_minutes: 0,
+            month_balance_minutes: 0,
+        }
+    }
+
+    fn bounds_utc(span: &WorkSpanData) -> (NaiveDateTime, NaiveDateTime) {
+        let (start, end) = span_bounds_in(&Cet2026, span).unwrap();
+        (start.naive_utc(), end.naive_utc())
+    }
+
+    #[test]
+    fn repeated_hour_starts_at_the_first_and_ends_at_the_second_occurrence() {
+        // 02:00-03:00 happens twice on Oct 25: first at +02:00, then at +01:00.
+        assert_eq!(bounds_utc(&span("2026-10-25", "02:30:00", "02:45:00")), (utc(10, 25, 0, 30), utc(10, 25, 1, 45)));
+        assert_eq!(bounds_utc(&span("2026-10-24", "09:00:00", "17:00:00")), (utc(10, 24, 7, 0), utc(10, 24, 15, 0)));
+    }
+
+    #[test]
+    fn skipped_hour_is_read_one_hour_later() {
+        // 02:00-03:00 does not exist on Mar 29; 02:30 is taken as 03:30 summer time.
+        let date = NaiveDate::from_ymd_opt(2026, 3, 29).unwrap();
+        let start = to_zone(&Cet2026, date, NaiveTime::from_hms_opt(2, 30, 0).unwrap(), false).unwrap();
+        assert_eq!(start.naive_utc(), utc(3, 29, 1, 30));
+    }
+
+    #[test]
+    fn shutdown_before_boot_ends_on_the_next_day() {
+        assert_eq!(bounds_utc(&span("2026-10-12", "20:00:00", "02:00:00")), (utc(10, 12, 18, 0), utc(10, 13, 0, 0)));
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_needed() {
+        assert_eq!(csv_field("8h 30m"), "8h 30m");
+        assert_eq!(csv_field("dentist, then home"), "\"dentist, then home\"");
+        assert_eq!(csv_field("the \"big\" release"), "\"the \"\"big\"\" release\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn ics_text_escapes_special_characters_and_line_breaks() {
+        assert_eq!(ics_text(r"a\b;c,d"), r"a\\b\;c\,d");
+        assert_eq!(ics_text("one\ntwo\r\nthree\rfour"), r"one\ntwo\nthree\nfour");
+    }
+
+    #[test]
+    fn ics_lines_fold_at_75_octets() {
+        let exact = "X".repeat(75);
+        assert_eq!(ics_fold(&exact), exact);
+
+        let long = format!("DESCRIPTION:{}", "a".repeat(200));
+        let folded = ics_fold(&long);
+        let lines: Vec<&str> = folded.split("\r\n").collect();
+        assert_eq!(lines[0].len(), 75);
+        assert!(lines[1..].iter().all(|l| l.starts_with(' ') && l.len() <= 75), "{:?}", lines);
+        assert_eq!(folded.replace("\r\n ", ""), long);
+    }
+
+    #[test]
+    fn ics_folding_never_splits_a_character() {
+        // Two-, three- and four-byte characters around every fold position.
+        let note = format!("DESCRIPTION:Note: {}", "é€😀".repeat(40));
+
+        let folded = ics_fold(&note);
+
+        assert!(folded.split("\r\n").all(|l| l.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), note);
+    }
+}
@@ -0,0 +1,127 @@
+//! Local TOML files shared by several processes (lock watcher, idle timer, `run`, the daemon).
+//!
+//! Writers hold an exclusive `flock` on a `<file>.lock` sibling for the whole read-modify-write and
+//! replace the file by renaming a temporary copy over it, so readers never see a partial file and
+//! concurrent writers never drop each other's events.
+
+use anyhow::{anyhow, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Writes `contents` to a temporary file next to `path` and renames it over `path`,
+/// so no reader ever sees a partial file.
+pub(crate) fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+    let file_name = path.file_name().ok_or_else(|| anyhow!("{} is not a file path", path.display()))?;
+    let tmp = path.with_file_name(format!(".{}.{}.tmp", file_name.to_string_lossy(), std::process::id()));
+    fs::write(&tmp, contents).map_err(|e| anyhow!("Cannot write {}: {}", tmp.display(), e))?;
+    fs::rename(&tmp, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        anyhow!("Cannot move {} to {}: {}", tmp.display(), path.display(), e)
+    })
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn read_toml<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    match fs::read_to_string(path) {
+        Ok(contents) => toml::from_str(&contents).map_err(|e| anyhow!("Error parsing {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(anyhow!("Error reading {}: {}", path.display(), e)),
+    }
+}
+
+/// Reads `path`, or the default if it is missing or unreadable (`what` names it in the warning).
+pub(crate) fn load_toml<T: DeserializeOwned + Default>(path: &Path, what: &str) -> T {
+    read_toml(path).unwrap_or_else(|e| {
+        warn!("{}. Using an empty {}.", e, what);
+        T::default()
+    })
+}
+
+/// Loads `path`, applies `update` and writes the result back, all under an exclusive lock.
+///
+/// An unparseable file is moved aside to `<file>.corrupt` before starting afresh, so a damaged log is
+/// never silently overwritten. Nothing is written if reading fails for any other reason.
+pub(crate) fn update_toml<T, R>(path: &Path, what: &str, update: impl FnOnce(&mut T) -> R) -> Result<R>
+where
+    T: Serialize + DeserializeOwned + Default,
+{
+    let lock_path = sibling(path, ".lock");
+    let lock: File = OpenOptions::new().create(true).truncate(false).write(true).open(&lock_path)
+        .map_err(|e| anyhow!("Cannot open {}: {}", lock_path.display(), e))?;
+    lock.lock().map_err(|e| anyhow!("Cannot lock {}: {}", lock_path.display(), e))?;
+
+    let mut value: T = match fs::read_to_string(path) {
+        Ok(contents) => match toml::from_str(&contents) {
+            Ok(value) => value,
+            Err(e) => {
+                let corrupt = sibling(path, ".corrupt");
+                warn!("Error parsing {}: {}. Moving it to {} and starting a fresh {}.", path.display(), e, corrupt.display(), what);
+                fs::rename(path, &corrupt).map_err(|e| anyhow!("Cannot move {} aside: {}", path.display(), e))?;
+                T::default()
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => T::default(),
+        Err(e) => return Err(anyhow!("Error reading {}: {}", path.display(), e)),
+    };
+
+    let result = update(&mut value);
+    let contents = toml::to_string_pretty(&value).map_err(|e| anyhow!("Error serializing {}: {}", what, e))?;
+    write_atomically(path, &contents)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+    struct Counter {
+        count: u32,
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("avadhi-fileio-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("Log.toml")
+    }
+
+    #[test]
+    fn concurrent_updates_are_not_lost() {
+        let path = temp_path("concurrent");
+        let threads: Vec<_> = (0..8).map(|_| {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                for _ in 0..25 {
+                    update_toml(&path, "counter", |c: &mut Counter| c.count += 1).unwrap();
+                }
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(load_toml::<Counter>(&path, "counter"), Counter { count: 200 });
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn unparseable_file_is_moved_aside() {
+        let path = temp_path("corrupt");
+        fs::write(&path, "count = ").unwrap();
+
+        update_toml(&path, "counter", |c: &mut Counter| c.count += 1).unwrap();
+
+        assert_eq!(load_toml::<Counter>(&path, "counter"), Counter { count: 1 });
+        assert_eq!(fs::read_to_string(sibling(&path, ".corrupt")).unwrap(), "count = ");
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
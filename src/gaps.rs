@@ -0,0 +1,43 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Duration, Local};
+
+/// An interval inside a boot session that does not count as active time
+/// (idle, screen locked, ...).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+}
+
+impl Gap {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+/// Minutes of `gaps` that fall inside [start, end], counting overlapping gaps once.
+pub fn gap_minutes_within(gaps: &[Gap], start: DateTime<Local>, end: DateTime<Local>) -> i64 {
+    let mut clipped: Vec<(DateTime<Local>, DateTime<Local>)> = gaps.iter()
+        .map(|g| (g.start.max(start), g.end.min(end)))
+        .filter(|(s, e)| e > s)
+        .collect();
+    clipped.sort();
+
+    let mut total = Duration::zero();
+    let mut current: Option<(DateTime<Local>, DateTime<Local>)> = None;
+    for (s, e) in clipped {
+        current = match current {
+            Some((cs, ce)) if s <= ce => Some((cs, ce.max(e))),
+            Some((cs, ce)) => {
+                total += ce - cs;
+                Some((s, e))
+            },
+            None => Some((s, e)),
+        };
+    }
+    if let Some((cs, ce)) = current {
+        total += ce - cs;
+    }
+
+    total.num_minutes()
+}
@@ -0,0 +1,169 @@
+//! `history`: what past runs did, read from the local history (AvadhiHistory.sqlite).
+
+use chrono::{DateTime, Local, NaiveDate};
+use anyhow::{anyhow, Result};
+
+use crate::store::{HistoryStore, PostRecord, RunRecord};
+
+fn format_time(time: &DateTime<Local>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn describe_duration(run: &RunRecord) -> String {
+    match run.finished_at {
+        Some(finished) => format!("{}s", (finished - run.started_at).num_seconds()),
+        None => "-".to_string(),
+    }
+}
+
+/// "ok", "failed" or "unfinished" (the process died before recording the outcome).
+/// Runs recorded before outcomes were tracked have no kind and show "-".
+fn describe_outcome(run: &RunRecord) -> &'static str {
+    match run.outcome.as_deref() {
+        Some("success") => "ok",
+        Some(_) => "failed",
+        None if run.kind.is_some() => "unfinished",
+        None => "-",
+    }
+}
+
+fn describe_post(post: &PostRecord) -> String {
+    let status = post.http_status.map_or("no response".to_string(), |s| s.to_string());
+    match &post.error {
+        Some(error) => format!("FAILED ({}): {}", status, error),
+        None => format!("posted ({})", status),
+    }
+}
+
+/// One line per run, newest first.
+pub fn print_runs(runs: &[RunRecord]) {
+    if runs.is_empty() {
+        println!("No runs recorded yet.");
+        return;
+    }
+    println!("{:>5}  {:<19}  {:<9}  {:<7}  {:>8}  {:>8}  {:>6}  {:>6}  {:<10}  {:<10}",
+        "ID", "STARTED", "KIND", "TRIGGER", "DURATION", "SESSIONS", "POSTED", "FAILED", "RESULT", "LAST POSTED");
+    for run in runs {
+        println!("{:>5}  {:<19}  {:<9}  {:<7}  {:>8}  {:>8}  {:>6}  {:>6}  {:<10}  {:<10}",
+            run.id,
+            format_time(&run.started_at),
+            run.kind.as_deref().unwrap_or("-"),
+            run.trigger.as_deref().unwrap_or("-"),
+            describe_duration(run),
+            run.session_count.map_or("-".to_string(), |c| c.to_string()),
+            run.days_posted,
+            run.days_failed,
+            describe_outcome(run),
+            run.last_posted_date.as_deref().unwrap_or("-"));
+    }
+    println!("\nRun 'avadhi-collector history <ID>' for details or 'history --date YYYY-MM-DD' to trace one day.");
+}
+
+/// Everything recorded about one run: trigger, sessions, each day's post result and token refreshes.
+pub fn print_run(store: &HistoryStore, run_id: i64) -> Result<()> {
+    let run = store.run(run_id)?.ok_or_else(|| anyhow!("No run with ID {} in the local history.", run_id))?;
+
+    println!("Run {}", run.id);
+    println!("  Kind:             {}", run.kind.as_deref().unwrap_or("-"));
+    println!("  Trigger:          {}", run.trigger.as_deref().unwrap_or("-"));
+    println!("  Started:          {}", format_time(&run.started_at));
+    println!("  Finished:         {}", run.finished_at.as_ref().map_or("-".to_string(), format_time));
+    println!("  Result:           {}", describe_outcome(&run));
+    if let Some(error) = &run.error {
+        println!("  Error:            {}", error);
+    }
+    match (&run.session_source, run.session_count) {
+        (Some(source), Some(count)) => println!("  Sessions:         {} from {}", count, source),
+        _ => println!("  Sessions:         - (not read)"),
+    }
+    println!("  last_posted_date: {}", run.last_posted_date.as_deref().unwrap_or("-"));
+
+    let posts = store.posts_for_run(run_id)?;
+    println!("\n  Days attempted: {}", posts.len());
+    for post in &posts {
+        println!("    {}  {}  {}", post.date, post.posted_at.format("%H:%M:%S"), describe_post(post));
+    }
+
+    let refreshes = store.refreshes_for_run(run_id)?;
+    if !refreshes.is_empty() {
+        println!("\n  Token refreshes: {}", refreshes.len());
+        for refresh in &refreshes {
+            match &refresh.error {
+                None => println!("    {}  refreshed", refresh.at.format("%H:%M:%S")),
+                Some(error) => println!("    {}  FAILED: {}", refresh.at.format("%H:%M:%S"), error),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The stored span of `date` and every attempt to post it, to answer "why is this day missing?".
+pub fn print_date(store: &HistoryStore, date: NaiveDate) -> Result<()> {
+    let key = date.format("%Y-%m-%d").to_string();
+    println!("{}", key);
+
+    match store.stored_span(&key)? {
+        Some(span) => println!("  Computed span:  {} - {} = {} ({} min excluded), run {} at {}",
+            span.first_boot, span.last_shutdown, span.total_span, span.excluded_minutes, span.run_id, format_time(&span.computed_at)),
+        None => println!("  Computed span:  none. No run saw a boot session on this day."),
+    }
+
+    let posts = store.posts_for_date(&key)?;
+    if posts.is_empty() {
+        println!("  Post attempts:  none");
+        return Ok(());
+    }
+    println!("  Post attempts:  {}", posts.len());
+    for post in &posts {
+        println!("    run {:>5}  {}  {}", post.run_id, format_time(&post.posted_at), describe_post(post));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(http_status: Option<u16>, error: Option<&str>) -> PostRecord {
+        PostRecord {
+            run_id: 1,
+            date: "2026-10-13".to_string(),
+            posted_at: Local::now(),
+            http_status,
+            success: error.is_none(),
+            error: error.map(str::to_string),
+        }
+    }
+
+    fn run(kind: Option<&str>, outcome: Option<&str>) -> RunRecord {
+        RunRecord {
+            id: 1,
+            started_at: Local::now(),
+            finished_at: None,
+            kind: kind.map(str::to_string),
+            trigger: None,
+            session_source: None,
+            session_count: None,
+            outcome: outcome.map(str::to_string),
+            error: None,
+            last_posted_date: None,
+            days_posted: 0,
+            days_failed: 0,
+        }
+    }
+
+    #[test]
+    fn post_attempts_show_status_and_error() {
+        assert_eq!(describe_post(&post(Some(201), None)), "posted (201)");
+        assert_eq!(describe_post(&post(Some(409), Some("conflict"))), "FAILED (409): conflict");
+        assert_eq!(describe_post(&post(None, Some("timed out"))), "FAILED (no response): timed out");
+    }
+
+    #[test]
+    fn runs_without_outcome_are_unfinished_unless_recorded_before_kinds() {
+        assert_eq!(describe_outcome(&run(Some("run"), Some("success"))), "ok");
+        assert_eq!(describe_outcome(&run(Some("backfill"), Some("failure"))), "failed");
+        assert_eq!(describe_outcome(&run(Some("run"), None)), "unfinished");
+        assert_eq!(describe_outcome(&run(None, None)), "-");
+    }
+}
@@ -0,0 +1,61 @@
+//! The HTTP client used for every Supabase request of a run: timeouts, proxy, extra CA certificates,
+//! an optional client certificate and a user agent carrying the collector version.
+
+use anyhow::{anyhow, Result};
+use reqwest::{Certificate, Client, Identity, NoProxy, Proxy};
+use std::fs;
+use std::time::Duration;
+
+use crate::config::HttpConfig;
+use crate::error::CollectorError;
+
+/// Sent with every request, e.g. "avadhi-collector/0.1.0".
+pub const USER_AGENT: &str = concat!("avadhi-collector/", env!("CARGO_PKG_VERSION"));
+
+/// An unusable `[http]` setting. Like missing values it needs the config fixed, so it shares their exit code.
+fn invalid(message: String) -> anyhow::Error {
+    CollectorError::ConfigMissing(message).into()
+}
+
+fn read_file(path: &str, what: &str) -> Result<Vec<u8>> {
+    fs::read(path).map_err(|e| invalid(format!("Cannot read [http] {} {}: {}", what, path, e)))
+}
+
+/// Builds the client from `[http]`. Without `proxy`, HTTPS_PROXY/HTTP_PROXY/NO_PROXY from the environment apply.
+/// Unusable settings (bad proxy URL, unreadable or invalid certificate files) are `CollectorError::ConfigMissing`.
+pub fn build_http_client(config: &HttpConfig) -> Result<Client> {
+    let mut builder = Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(Duration::from_secs(config.connect_timeout_seconds))
+        .timeout(Duration::from_secs(config.timeout_seconds));
+
+    if let Some(proxy_url) = &config.proxy {
+        let proxy = Proxy::all(proxy_url.as_str())
+            .map_err(|e| invalid(format!("Invalid [http] proxy '{}': {}", proxy_url, e)))?
+            .no_proxy(config.no_proxy.as_deref().and_then(NoProxy::from_string));
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(path) = &config.ca_bundle {
+        let certificates = Certificate::from_pem_bundle(&read_file(path, "CA bundle")?)
+            .map_err(|e| invalid(format!("Invalid [http] CA bundle {}: {}", path, e)))?;
+        if certificates.is_empty() {
+            return Err(invalid(format!("[http] CA bundle {} contains no PEM certificates.", path)));
+        }
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    match (&config.client_cert, &config.client_key) {
+        (Some(cert), Some(key)) => {
+            let identity = Identity::from_pkcs8_pem(&read_file(cert, "client certificate")?, &read_file(key, "client key")?)
+                .map_err(|e| invalid(format!("Invalid [http] client certificate {} / key {}: {}", cert, key, e)))?;
+            builder = builder.identity(identity);
+        },
+        (None, None) => {},
+        _ => return Err(invalid("[http] client_cert and client_key must be set together.".to_string())),
+    }
+
+    builder.build().map_err(|e| anyhow!("Cannot build HTTP client: {}", e))
+}
@@ -0,0 +1,358 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Duration, Local, TimeZone};
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::config::{IdleConfig, IdleSourceKind};
+use crate::fileio::{load_toml, update_toml};
+use crate::gaps::Gap;
+use crate::logind::{connect, manager_proxy};
+
+pub const IDLE_LOG_PATH: &str = "AvadhiIdle.toml";
+
+/// Observed periods older than this are dropped from the idle log.
+const IDLE_RETENTION_DAYS: i64 = 120;
+
+// --- Data Structures ---
+
+/// Locally persisted idle observations. Idle state is only visible "right now",
+/// so every sample is folded into this log and spans are computed from it later.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct IdleLog {
+    /// Source used for the most recent sample ("logind" or "interrupts").
+    pub last_source: Option<String>,
+    pub last_sample: Option<DateTime<Local>>,
+
+    /// Start of the currently open idle period reported by logind.
+    pub idle_since: Option<DateTime<Local>>,
+
+    /// Input interrupt counter at the last sample, and when it last changed.
+    pub last_input_count: Option<u64>,
+    pub last_input_change: Option<DateTime<Local>>,
+
+    #[serde(default)]
+    pub periods: Vec<Gap>,
+}
+
+/// Snapshot of logind's idle hints.
+#[derive(Debug, Clone, Copy)]
+struct LogindIdle {
+    idle: bool,
+    /// Time of the last idle state change (CLOCK_REALTIME).
+    since: Option<DateTime<Local>>,
+}
+
+/// One reading of the configured idle source.
+#[derive(Debug, Clone, Copy)]
+enum IdleSample {
+    Logind(LogindIdle),
+    /// Input interrupt count from /proc/interrupts.
+    Interrupts(u64),
+}
+
+// --- File Handling ---
+
+pub fn load_idle_log() -> IdleLog {
+    load_toml(Path::new(IDLE_LOG_PATH), "idle log")
+}
+
+/// Applies `update` to the idle log under the shared file lock and saves it.
+fn update_idle_log<R>(update: impl FnOnce(&mut IdleLog) -> R) -> Result<R> {
+    update_toml(Path::new(IDLE_LOG_PATH), "idle log", update)
+}
+
+// --- Sources ---
+
+/// Reads `IdleHint`/`IdleSinceHint` from the logind Manager over D-Bus.
+async fn read_logind_idle(config: &IdleConfig) -> Result<LogindIdle> {
+    let connection = connect(config.dbus_address.as_deref()).await?;
+    let proxy = manager_proxy(&connection).await?;
+    let idle: bool = proxy.get_property("IdleHint").await?;
+    let since_usec: u64 = proxy.get_property("IdleSinceHint").await?;
+
+    // logind reports 0 when the hint was never set.
+    let since = match since_usec {
+        0 => None,
+        usec => Local.timestamp_micros(usec as i64).single(),
+    };
+
+    Ok(LogindIdle { idle, since })
+}
+
+/// Sums the interrupt counters (over all CPUs) of input-device lines in /proc/interrupts.
+pub fn parse_input_interrupts(contents: &str, patterns: &[String]) -> Option<u64> {
+    let patterns: Vec<String> = patterns.iter().map(|p| p.to_lowercase()).collect();
+    let mut total = 0u64;
+    let mut matched = false;
+
+    // First line is the CPU header.
+    for line in contents.lines().skip(1) {
+        let mut fields = line.split_whitespace();
+        if fields.next().is_none() {
+            continue;
+        }
+
+        let mut count = 0u64;
+        let mut description = Vec::new();
+        for field in fields {
+            match field.parse::<u64>() {
+                Ok(n) if description.is_empty() => count += n,
+                _ => description.push(field),
+            }
+        }
+
+        let description = description.join(" ").to_lowercase();
+        if patterns.iter().any(|p| description.contains(p.as_str())) {
+            total += count;
+            matched = true;
+        }
+    }
+
+    matched.then_some(total)
+}
+
+fn read_input_interrupts(config: &IdleConfig) -> Result<u64> {
+    let contents = fs::read_to_string("/proc/interrupts")
+        .map_err(|e| anyhow!("Cannot read /proc/interrupts: {}", e))?;
+    parse_input_interrupts(&contents, &config.interrupt_patterns)
+        .ok_or_else(|| anyhow!("No input-device lines matched in /proc/interrupts (patterns: {:?}).", config.interrupt_patterns))
+}
+
+// --- Sampling ---
+
+impl IdleLog {
+    fn record(&mut self, start: DateTime<Local>, end: DateTime<Local>) {
+        if end > start {
+            self.periods.push(Gap { start, end });
+        }
+    }
+
+    fn apply_logind(&mut self, hint: LogindIdle, now: DateTime<Local>) {
+        if hint.idle {
+            // Keep the earliest known start if logind's hint moves while still idle.
+            let since = hint.since.unwrap_or(now);
+            self.idle_since = Some(self.idle_since.map_or(since, |s| s.min(since)));
+        } else if let Some(start) = self.idle_since.take() {
+            // While active, IdleSinceHint is the moment the user came back.
+            let end = hint.since.filter(|t| *t > start && *t <= now).unwrap_or(now);
+            self.record(start, end);
+        }
+        self.last_source = Some("logind".to_string());
+    }
+
+    fn apply_interrupts(&mut self, count: u64, now: DateTime<Local>) {
+        if self.last_input_count != Some(count) {
+            // Input happened since the previous sample: the quiet stretch ended no later than that sample.
+            if let (Some(quiet_from), Some(last_sample)) = (self.last_input_change, self.last_sample) {
+                self.record(quiet_from, last_sample);
+            }
+            self.last_input_count = Some(count);
+            self.last_input_change = Some(now);
+        }
+        self.last_source = Some("interrupts".to_string());
+    }
+
+    /// All known idle periods including the currently open one (clipped to `now`).
+    pub fn periods_until(&self, now: DateTime<Local>) -> Vec<Gap> {
+        let mut periods = self.periods.clone();
+        if let Some(start) = self.idle_since {
+            if now > start {
+                periods.push(Gap { start, end: now });
+            }
+        } else if let (Some(quiet_from), Some(last_sample)) = (self.last_input_change, self.last_sample) {
+            if self.last_source.as_deref() == Some("interrupts") && last_sample > quiet_from {
+                periods.push(Gap { start: quiet_from, end: last_sample });
+            }
+        }
+        periods
+    }
+
+    fn prune(&mut self, now: DateTime<Local>) {
+        let cutoff = now - Duration::days(IDLE_RETENTION_DAYS);
+        self.periods.retain(|p| p.end >= cutoff);
+    }
+}
+
+/// Takes one idle sample from the configured source and persists it to the idle log.
+pub async fn sample_idle(config: &IdleConfig) -> Result<IdleLog> {
+    let use_logind = matches!(config.source, IdleSourceKind::Auto | IdleSourceKind::Logind);
+    let logind = if use_logind { Some(read_logind_idle(config).await) } else { None };
+
+    // Read the source before taking the file lock, so no other sampler waits on D-Bus.
+    let sample = match logind {
+        Some(Ok(hint)) => {
+            info!("logind IdleHint={} (since {:?}).", hint.idle, hint.since);
+            IdleSample::Logind(hint)
+        },
+        Some(Err(e)) if config.source == IdleSourceKind::Logind => {
+            return Err(anyhow!("logind idle hint unavailable: {}", e));
+        },
+        other => {
+            if let Some(Err(e)) = other {
+                info!("logind idle hint unavailable ({}). Falling back to /proc/interrupts.", e);
+            }
+            IdleSample::Interrupts(read_input_interrupts(config)?)
+        }
+    };
+
+    update_idle_log(|log| {
+        let now = Local::now();
+        match sample {
+            IdleSample::Logind(hint) => log.apply_logind(hint, now),
+            IdleSample::Interrupts(count) => log.apply_interrupts(count, now),
+        }
+        log.last_sample = Some(now);
+        log.prune(now);
+        log.clone()
+    })
+}
+
+/// Idle periods from `log` that are at least `threshold_minutes` long. Does not sample.
+pub fn idle_gaps(log: &IdleLog, config: &IdleConfig, now: DateTime<Local>) -> Vec<Gap> {
+    if !config.enabled {
+        return Vec::new();
+    }
+    let threshold = Duration::minutes(config.threshold_minutes);
+    log.periods_until(now)
+        .into_iter()
+        .filter(|p| p.duration() >= threshold)
+        .collect()
+}
+
+/// Returns the idle periods to subtract from spans: samples now (best effort) and
+/// keeps only periods at least `threshold_minutes` long.
+pub async fn collect_idle_periods(config: &IdleConfig) -> Vec<Gap> {
+    if !config.enabled {
+        return Vec::new();
+    }
+
+    let log = match sample_idle(config).await {
+        Ok(log) => log,
+        Err(e) => {
+            warn!("Idle sample failed ({}). Using previously recorded idle periods only.", e);
+            load_idle_log()
+        }
+    };
+
+    let periods = idle_gaps(&log, config, Local::now());
+
+    info!("{} idle period(s) of {}+ minutes will be excluded from active time.", periods.len(), config.threshold_minutes);
+    periods
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// /proc/interrupts of a laptop with a PS/2 keyboard and touchpad (i8042) and a USB receiver.
+    const INTERRUPTS: &str = "\
+           CPU0       CPU1
+  0:         20          0   IO-APIC    2-edge      timer
+  1:       1200        340   IO-APIC    1-edge      i8042
+  8:          0          1   IO-APIC    8-edge      rtc0
+ 12:       5000        100   IO-APIC   12-edge      i8042
+ 45:        300          7   PCI-MSI 327680-edge      xhci_hcd
+ 51:         90         10   IR-IO-APIC   51-fasteoi   ELAN0501:00 (i2c-hid)
+NMI:          0          0   Non-maskable interrupts
+LOC:     123456     654321   Local timer interrupts
+";
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, 13, hour, minute, 0).unwrap()
+    }
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn input_interrupts_sum_every_cpu_of_matching_lines() {
+        assert_eq!(parse_input_interrupts(INTERRUPTS, &patterns(&["i8042"])), Some(1200 + 340 + 5000 + 100));
+        assert_eq!(parse_input_interrupts(INTERRUPTS, &patterns(&["I8042", "HID"])), Some(6640 + 100), "case-insensitive");
+        assert_eq!(parse_input_interrupts(INTERRUPTS, &IdleConfig::default().interrupt_patterns), Some(6740));
+    }
+
+    #[test]
+    fn input_interrupts_ignore_header_and_report_no_match() {
+        // "CPU0" in the header and the IRQ numbers inside descriptions must not count.
+        assert_eq!(parse_input_interrupts(INTERRUPTS, &patterns(&["cpu"])), None);
+        assert_eq!(parse_input_interrupts(INTERRUPTS, &patterns(&["keyboard"])), None);
+        assert_eq!(parse_input_interrupts(INTERRUPTS, &patterns(&["xhci"])), Some(307));
+        assert_eq!(parse_input_interrupts("", &patterns(&["i8042"])), None);
+    }
+
+    #[test]
+    fn logind_idle_period_runs_from_idle_since_until_the_user_returns() {
+        let mut log = IdleLog::default();
+        log.apply_logind(LogindIdle { idle: true, since: Some(at(10, 0)) }, at(10, 20));
+        assert!(log.periods.is_empty());
+        assert_eq!(log.idle_since, Some(at(10, 0)));
+        assert_eq!(log.periods_until(at(10, 30)), [Gap { start: at(10, 0), end: at(10, 30) }], "open period counts until now");
+
+        // A later IdleSinceHint while still idle does not move the start.
+        log.apply_logind(LogindIdle { idle: true, since: Some(at(10, 5)) }, at(10, 40));
+        assert_eq!(log.idle_since, Some(at(10, 0)));
+
+        // Back at 10:45, noticed at 11:00.
+        log.apply_logind(LogindIdle { idle: false, since: Some(at(10, 45)) }, at(11, 0));
+        assert_eq!(log.idle_since, None);
+        assert_eq!(log.periods, [Gap { start: at(10, 0), end: at(10, 45) }]);
+        assert_eq!(log.last_source.as_deref(), Some("logind"));
+    }
+
+    #[test]
+    fn logind_hints_without_a_usable_time_fall_back_to_the_sample_time() {
+        let mut log = IdleLog::default();
+        log.apply_logind(LogindIdle { idle: true, since: None }, at(9, 0));
+        assert_eq!(log.idle_since, Some(at(9, 0)));
+
+        // An active hint older than the idle start (or in the future) cannot end the period.
+        log.apply_logind(LogindIdle { idle: false, since: Some(at(8, 0)) }, at(9, 30));
+        assert_eq!(log.periods, [Gap { start: at(9, 0), end: at(9, 30) }]);
+
+        // Active without an open period records nothing.
+        log.apply_logind(LogindIdle { idle: false, since: Some(at(9, 40)) }, at(9, 45));
+        assert_eq!(log.periods.len(), 1);
+    }
+
+    fn sample_interrupts(log: &mut IdleLog, count: u64, now: DateTime<Local>) {
+        log.apply_interrupts(count, now);
+        log.last_sample = Some(now);
+    }
+
+    #[test]
+    fn interrupt_quiet_stretch_ends_at_the_last_quiet_sample() {
+        let mut log = IdleLog::default();
+        sample_interrupts(&mut log, 100, at(9, 0));
+        sample_interrupts(&mut log, 100, at(9, 5));
+        sample_interrupts(&mut log, 100, at(9, 10));
+        assert!(log.periods.is_empty());
+        assert_eq!(log.periods_until(at(9, 12)), [Gap { start: at(9, 0), end: at(9, 10) }], "open quiet stretch");
+
+        // Input between 9:10 and 9:15: the stretch is closed at 9:10, not 9:15.
+        sample_interrupts(&mut log, 150, at(9, 15));
+        assert_eq!(log.periods, [Gap { start: at(9, 0), end: at(9, 10) }]);
+        assert_eq!(log.last_input_change, Some(at(9, 15)));
+
+        // Continuous input records nothing.
+        sample_interrupts(&mut log, 180, at(9, 20));
+        assert_eq!(log.periods.len(), 1);
+        assert_eq!(log.periods_until(at(9, 20)).len(), 1);
+        assert_eq!(log.last_source.as_deref(), Some("interrupts"));
+    }
+
+    #[test]
+    fn only_periods_above_the_threshold_become_gaps() {
+        let mut log = IdleLog::default();
+        log.record(at(9, 0), at(9, 10));
+        log.record(at(11, 0), at(11, 30));
+        log.record(at(12, 0), at(12, 0));
+        assert_eq!(log.periods.len(), 2, "empty periods are not recorded");
+
+        let config = IdleConfig { threshold_minutes: 15, ..IdleConfig::default() };
+        assert_eq!(idle_gaps(&log, &config, at(13, 0)), [Gap { start: at(11, 0), end: at(11, 30) }]);
+        assert!(idle_gaps(&log, &IdleConfig { enabled: false, ..config }, at(13, 0)).is_empty());
+    }
+}
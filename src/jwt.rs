@@ -0,0 +1,25 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Local, TimeZone};
+use serde_json::Value;
+use anyhow::{anyhow, Result};
+
+/// Decodes the (unverified) claims of a JWT. Only used for diagnostics such as expiry display;
+/// the signature is never checked locally.
+pub fn decode_claims(token: &str) -> Result<Value> {
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 {
+        return Err(anyhow!("Not a JWT: expected 3 dot-separated segments, found {}.", parts.len()));
+    }
+
+    let payload = URL_SAFE_NO_PAD.decode(parts[1].trim_end_matches('='))
+        .map_err(|e| anyhow!("JWT payload is not valid base64url: {}", e))?;
+    serde_json::from_slice(&payload).map_err(|e| anyhow!("JWT payload is not valid JSON: {}", e))
+}
+
+/// Returns the `exp` claim of a JWT as local time.
+pub fn expiry(token: &str) -> Result<DateTime<Local>> {
+    let claims = decode_claims(token)?;
+    let exp = claims["exp"].as_i64().ok_or_else(|| anyhow!("JWT has no numeric 'exp' claim."))?;
+    Local.timestamp_opt(exp, 0).single().ok_or_else(|| anyhow!("JWT 'exp' claim is out of range: {}", exp))
+}
@@ -0,0 +1,75 @@
+//! Avadhi collector library: boot sessions to daily work spans, posted to Supabase.
+//!
+//! The `avadhi-collector` binary is a thin CLI over this crate. The stable entry points are:
+//!
+//! - [`sessions`]: `SessionRecord`, parsed from `last -x -F reboot` text ([`sessions::parse_last_output`])
+//!   or from raw wtmp records ([`sessions::parse_wtmp`]).
+//! - [`spans`]: [`spans::calculate_work_spans`], which turns sessions into typed [`spans::WorkSpan`] days.
+//! - [`api`]: [`api::SupabaseClient`] for the work span table and token refresh.
+//!
+//! ```no_run
+//! use avadhi_collector::calendar::WorkCalendar;
+//! use avadhi_collector::config::{ScheduleConfig, SpanPolicyConfig};
+//! use avadhi_collector::overrides::Overrides;
+//! use avadhi_collector::sessions::parse_last_output;
+//! use avadhi_collector::spans::calculate_work_spans;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let output = std::fs::read_to_string("last.txt")?;
+//! let sessions = parse_last_output(&output)?;
+//! let calendar = WorkCalendar::from_config(&ScheduleConfig::default())?;
+//! for day in calculate_work_spans(sessions, &[], &Overrides::default(), &calendar, &SpanPolicyConfig::default())? {
+//!     println!("{} {}-{} {}", day.date, day.first_boot, day.last_shutdown, day.total_span());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod config;
+pub mod api;
+pub mod error;
+pub mod gaps;
+pub mod overrides;
+pub mod calendar;
+pub mod sessions;
+pub mod spans;
+
+// The modules below back the `avadhi-collector` binary and its integration tests. They are not a stable API.
+#[doc(hidden)]
+pub mod logging;
+#[doc(hidden)]
+pub mod retry;
+#[doc(hidden)]
+pub mod app_privacy;
+#[doc(hidden)]
+pub mod idle;
+#[doc(hidden)]
+pub mod locks;
+#[doc(hidden)]
+pub mod state;
+#[doc(hidden)]
+pub mod daemon;
+#[doc(hidden)]
+pub mod status;
+#[doc(hidden)]
+pub mod doctor;
+#[doc(hidden)]
+pub mod store;
+#[doc(hidden)]
+pub mod history;
+#[doc(hidden)]
+pub mod report;
+#[doc(hidden)]
+pub mod export;
+#[doc(hidden)]
+pub mod collector;
+
+mod http;
+mod fileio;
+mod metrics;
+mod app_usage;
+mod logind;
+mod jwt;
+mod reconcile;
+mod balance;
+mod span_policy;
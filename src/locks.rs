@@ -0,0 +1,412 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Duration, Local};
+use anyhow::{anyhow, Result};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use tracing::{error, info, warn};
+
+use crate::config::LockConfig;
+use crate::fileio::{load_toml, update_toml};
+use crate::gaps::Gap;
+use crate::logind::{connect, manager_proxy, LOGIND_BUS_NAME, LOGIND_SESSION_INTERFACE};
+use crate::sessions::SessionRecord;
+
+pub const LOCK_LOG_PATH: &str = "AvadhiLocks.toml";
+
+/// Random ID the kernel assigns to each boot.
+const BOOT_ID_PATH: &str = "/proc/sys/kernel/random/boot_id";
+
+/// Lock events older than this are dropped from the lock log.
+const LOCK_RETENTION_DAYS: i64 = 120;
+
+// --- Data Structures ---
+
+/// A single lock state change of a logind session.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LockEvent {
+    pub at: DateTime<Local>,
+    /// Kernel boot ID of the session. logind numbers sessions afresh after every reboot, so a session
+    /// is only identified by (boot, session). `None` for events recorded before boot IDs were kept.
+    #[serde(default)]
+    pub boot: Option<String>,
+    pub session: String,
+    pub locked: bool,
+    /// "signal" (Lock/Unlock), "locked_hint" (property change), "poll", "watcher_start" or
+    /// "session_gone" (the session was no longer listed by logind).
+    pub source: String,
+}
+
+/// Locally persisted lock/unlock history. Complements the boot-based sessions from `last`.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct LockLog {
+    #[serde(default)]
+    pub events: Vec<LockEvent>,
+}
+
+/// (boot id, session id) identifying one logind session across reboots.
+type SessionKey = (Option<String>, String);
+
+/// (session id, object path) of a logind session being watched.
+type WatchedSession = (String, zbus::zvariant::OwnedObjectPath);
+
+/// Yields Some((session, locked, source)), or None when logind's session list changed.
+type LockStream = BoxStream<'static, Option<(String, bool, &'static str)>>;
+
+// --- File Handling ---
+
+pub fn load_lock_log() -> LockLog {
+    load_toml(Path::new(LOCK_LOG_PATH), "lock log")
+}
+
+/// Applies `update` to the lock log under the shared file lock and saves it.
+fn update_lock_log<R>(update: impl FnOnce(&mut LockLog) -> R) -> Result<R> {
+    update_toml(Path::new(LOCK_LOG_PATH), "lock log", update)
+}
+
+/// Boot ID of the running system, or `None` if the kernel does not expose it.
+fn current_boot_id() -> Option<String> {
+    fs::read_to_string(BOOT_ID_PATH).ok().map(|id| id.trim().to_string())
+}
+
+impl LockLog {
+    fn last_state(&self, boot: Option<&str>, session: &str) -> Option<bool> {
+        self.events.iter().rev()
+            .find(|e| e.boot.as_deref() == boot && e.session == session)
+            .map(|e| e.locked)
+    }
+
+    /// Appends an event unless the session of that boot is already known to be in that state.
+    fn push(&mut self, boot: Option<&str>, session: &str, locked: bool, source: &str, at: DateTime<Local>) -> bool {
+        if self.last_state(boot, session) == Some(locked) {
+            return false;
+        }
+        self.events.push(LockEvent {
+            at,
+            boot: boot.map(str::to_string),
+            session: session.to_string(),
+            locked,
+            source: source.to_string(),
+        });
+        true
+    }
+
+    /// Closes every session still marked as locked (e.g. it was locked when the machine went down).
+    fn close_open_locks(&mut self, at: DateTime<Local>) {
+        for (boot, session) in self.open_locks() {
+            self.push(boot.as_deref(), &session, false, "watcher_start", at);
+        }
+    }
+
+    /// Closes the open locks of sessions logind no longer lists in the running boot `boot`, including
+    /// every session of an earlier boot. Such a lock would otherwise never see its unlock.
+    /// Returns true if any was closed.
+    fn close_vanished_locks(&mut self, boot: Option<&str>, listed: &[String], at: DateTime<Local>) -> bool {
+        let vanished: Vec<SessionKey> = self.open_locks().into_iter()
+            .filter(|(b, s)| b.as_deref() != boot || !listed.contains(s))
+            .collect();
+        for (b, session) in &vanished {
+            info!("Session {} is gone while locked. Closing its lock.", session);
+            self.push(b.as_deref(), session, false, "session_gone", at);
+        }
+        !vanished.is_empty()
+    }
+
+    fn open_locks(&self) -> Vec<SessionKey> {
+        self.sessions().into_iter()
+            .filter(|(boot, session)| self.last_state(boot.as_deref(), session) == Some(true))
+            .collect()
+    }
+
+    fn sessions(&self) -> Vec<SessionKey> {
+        let mut sessions: Vec<SessionKey> = self.events.iter().map(|e| (e.boot.clone(), e.session.clone())).collect();
+        sessions.sort();
+        sessions.dedup();
+        sessions
+    }
+
+    fn prune(&mut self, now: DateTime<Local>) {
+        let cutoff = now - Duration::days(LOCK_RETENTION_DAYS);
+        self.events.retain(|e| e.at >= cutoff);
+    }
+
+    /// Locked intervals per session. No lock outlasts the boot session it started in (the machine went
+    /// down locked); a lock without a matching unlock lasts until then, at most until `now`.
+    pub fn locked_gaps(&self, boot_sessions: &[SessionRecord], now: DateTime<Local>) -> Vec<Gap> {
+        let mut by_session: BTreeMap<(Option<&str>, &str), Vec<&LockEvent>> = BTreeMap::new();
+        for event in &self.events {
+            by_session.entry((event.boot.as_deref(), event.session.as_str())).or_default().push(event);
+        }
+
+        let mut gaps = Vec::new();
+        for events in by_session.values_mut() {
+            events.sort_by_key(|e| e.at);
+            let mut locked_since: Option<DateTime<Local>> = None;
+            for event in events.iter() {
+                match (event.locked, locked_since) {
+                    (true, None) => locked_since = Some(event.at),
+                    (false, Some(start)) => {
+                        if let Some(end) = lock_end(start, Some(event.at), boot_sessions, now) {
+                            if end > start {
+                                gaps.push(Gap { start, end });
+                            }
+                        }
+                        locked_since = None;
+                    },
+                    _ => {},
+                }
+            }
+            if let Some(start) = locked_since {
+                match lock_end(start, None, boot_sessions, now) {
+                    Some(end) if end > start => gaps.push(Gap { start, end }),
+                    _ => {},
+                }
+            }
+        }
+        gaps
+    }
+}
+
+/// Where a lock starting at `start` ends: at its `unlock`, but never after the end of the boot session
+/// it started in. Without an unlock it lasts until that boot session ends, or until `now` if no boot
+/// sessions are known. An unmatched lock outside every known boot session cannot be bounded and is ignored.
+fn lock_end(start: DateTime<Local>, unlock: Option<DateTime<Local>>, boot_sessions: &[SessionRecord], now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let boot_end = boot_sessions.iter()
+        .find(|s| s.start_time <= start && start < s.end_time)
+        .map(|s| s.end_time);
+    match (unlock, boot_end) {
+        (Some(unlock), Some(boot_end)) => Some(unlock.min(boot_end)),
+        (Some(unlock), None) => Some(unlock),
+        (None, Some(boot_end)) => Some(boot_end.min(now)),
+        (None, None) if boot_sessions.is_empty() => Some(now),
+        (None, None) => None,
+    }
+}
+
+/// Records one lock event and persists it immediately (the watcher may be killed at any time).
+fn record_event(boot: Option<&str>, session: &str, locked: bool, source: &str) {
+    let recorded = update_lock_log(|log| {
+        let now = Local::now();
+        log.prune(now);
+        log.push(boot, session, locked, source, now)
+    });
+    match recorded {
+        Ok(true) => info!("Session {} {} ({}).", session, if locked { "locked" } else { "unlocked" }, source),
+        Ok(false) => {},
+        Err(e) => error!("Could not record lock event of session {}: {}", session, e),
+    }
+}
+
+// --- logind Sessions ---
+
+/// Lists the logind sessions to track: those of the configured user, or every seated (graphical) session.
+async fn list_watched_sessions(manager: &zbus::Proxy<'static>, config: &LockConfig) -> Result<Vec<WatchedSession>> {
+    let sessions: Vec<(String, u32, String, String, zbus::zvariant::OwnedObjectPath)> =
+        manager.call("ListSessions", &()).await?;
+
+    Ok(sessions.into_iter()
+        .filter(|(_, _, user, seat, _)| match config.user.as_deref() {
+            Some(wanted) => user == wanted,
+            None => !seat.is_empty(),
+        })
+        .map(|(id, _, _, _, path)| (id, path))
+        .collect())
+}
+
+async fn session_proxy(connection: &zbus::Connection, path: zbus::zvariant::OwnedObjectPath) -> Result<zbus::Proxy<'static>> {
+    Ok(zbus::Proxy::new_owned(connection.clone(), LOGIND_BUS_NAME, path, LOGIND_SESSION_INTERFACE).await?)
+}
+
+/// Reads the current `LockedHint` of every watched session once and records changes.
+/// Used when no long-running watcher is installed.
+pub async fn sample_locked_hint(config: &LockConfig) -> Result<()> {
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let connection = connect(config.dbus_address.as_deref()).await?;
+    let manager = manager_proxy(&connection).await?;
+
+    let sessions = list_watched_sessions(&manager, config).await?;
+
+    let boot = current_boot_id();
+    let listed: Vec<String> = sessions.iter().map(|(id, _)| id.clone()).collect();
+    update_lock_log(|log| log.close_vanished_locks(boot.as_deref(), &listed, Local::now()))?;
+
+    // A session can disappear between listing and reading it; that must not stop the others.
+    for (id, path) in sessions {
+        let locked = match session_proxy(&connection, path).await {
+            Ok(proxy) => proxy.get_property::<bool>("LockedHint").await.map_err(anyhow::Error::from),
+            Err(e) => Err(e),
+        };
+        match locked {
+            Ok(locked) => record_event(boot.as_deref(), &id, locked, "poll"),
+            Err(e) => warn!("Could not read LockedHint of session {}: {}. Skipping it.", id, e),
+        }
+    }
+    Ok(())
+}
+
+/// Long-running listener for logind `Lock`/`Unlock` signals and `LockedHint` changes.
+/// Re-enumerates sessions whenever logind announces a new or removed session.
+pub async fn watch_locks(config: &LockConfig) -> Result<()> {
+    if !config.enabled {
+        return Err(anyhow!("Lock tracking is disabled ([lock] enabled = false in Config.toml)."));
+    }
+
+    let connection = connect(config.dbus_address.as_deref()).await?;
+    let manager = manager_proxy(&connection).await?;
+
+    // Whatever was locked when the watcher last stopped is not locked any more.
+    update_lock_log(|log| log.close_open_locks(Local::now()))?;
+    let boot = current_boot_id();
+
+    loop {
+        let sessions = list_watched_sessions(&manager, config).await?;
+        info!("Watching lock state of {} session(s): {:?}", sessions.len(),
+            sessions.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>());
+
+        let mut streams: Vec<LockStream> = Vec::new();
+
+        for (id, path) in sessions {
+            let proxy = session_proxy(&connection, path).await?;
+
+            let locked: bool = proxy.get_property("LockedHint").await.unwrap_or(false);
+            record_event(boot.as_deref(), &id, locked, "locked_hint");
+
+            let lock_id = id.clone();
+            streams.push(proxy.receive_signal("Lock").await?
+                .map(move |_| Some((lock_id.clone(), true, "signal")))
+                .boxed());
+
+            let unlock_id = id.clone();
+            streams.push(proxy.receive_signal("Unlock").await?
+                .map(move |_| Some((unlock_id.clone(), false, "signal")))
+                .boxed());
+
+            streams.push(proxy.receive_property_changed::<bool>("LockedHint").await
+                .filter_map(move |change| {
+                    let id = id.clone();
+                    async move { change.get().await.ok().map(|locked| Some((id, locked, "locked_hint"))) }
+                })
+                .boxed());
+        }
+
+        streams.push(manager.receive_signal("SessionNew").await?.map(|_| None).boxed());
+        streams.push(manager.receive_signal("SessionRemoved").await?.map(|_| None).boxed());
+
+        let mut events = stream::select_all(streams);
+        while let Some(item) = events.next().await {
+            match item {
+                Some((session, locked, source)) => record_event(boot.as_deref(), &session, locked, source),
+                None => break,
+            }
+        }
+    }
+}
+
+/// Returns locked intervals to treat as gaps: polls logind once (best effort) and reads the lock log.
+/// `boot_sessions` bound locks that were never unlocked.
+pub async fn collect_locked_gaps(config: &LockConfig, boot_sessions: &[SessionRecord]) -> Vec<Gap> {
+    if !config.enabled {
+        return Vec::new();
+    }
+
+    if let Err(e) = sample_locked_hint(config).await {
+        warn!("Could not read session LockedHint ({}). Using previously recorded lock events only.", e);
+    }
+
+    let gaps = load_lock_log().locked_gaps(boot_sessions, Local::now());
+    info!("{} locked interval(s) will be treated as gaps.", gaps.len());
+    gaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, day, hour, minute, 0).unwrap()
+    }
+
+    fn boot(start: DateTime<Local>, end: DateTime<Local>) -> SessionRecord {
+        SessionRecord { start_time: start, end_time: end }
+    }
+
+    #[test]
+    fn lock_left_open_at_shutdown_ends_with_its_boot_session() {
+        let mut log = LockLog::default();
+        log.push(Some("a"), "3", true, "poll", at(12, 17, 0));
+        // Oct 12 ends at 17:30 with the session still locked; Oct 13 boots with a new session ID.
+        let boots = [boot(at(12, 8, 0), at(12, 17, 30)), boot(at(13, 8, 0), at(13, 12, 0))];
+
+        let gaps = log.locked_gaps(&boots, at(13, 12, 0));
+
+        assert_eq!(gaps.len(), 1);
+        assert_eq!((gaps[0].start, gaps[0].end), (at(12, 17, 0), at(12, 17, 30)));
+    }
+
+    #[test]
+    fn open_lock_in_the_running_session_lasts_until_now() {
+        let mut log = LockLog::default();
+        log.push(Some("b"), "5", false, "poll", at(13, 9, 0));
+        log.push(Some("b"), "5", true, "signal", at(13, 11, 0));
+        let boots = [boot(at(13, 8, 0), at(13, 12, 0))];
+
+        let gaps = log.locked_gaps(&boots, at(13, 12, 0));
+
+        assert_eq!(gaps.len(), 1);
+        assert_eq!((gaps[0].start, gaps[0].end), (at(13, 11, 0), at(13, 12, 0)));
+        // Without any boot sessions there is nothing better than `now`.
+        assert_eq!(log.locked_gaps(&[], at(13, 12, 0))[0].end, at(13, 12, 0));
+    }
+
+    #[test]
+    fn open_lock_outside_every_boot_session_is_ignored() {
+        let mut log = LockLog::default();
+        log.push(Some("a"), "3", true, "poll", at(11, 20, 0));
+
+        assert!(log.locked_gaps(&[boot(at(12, 8, 0), at(12, 17, 0))], at(12, 17, 0)).is_empty());
+    }
+
+    #[test]
+    fn locks_of_sessions_no_longer_listed_are_closed() {
+        let mut log = LockLog::default();
+        log.push(Some("a"), "3", true, "poll", at(12, 17, 0));
+        log.push(Some("b"), "4", true, "poll", at(13, 10, 0));
+        log.push(Some("b"), "2", false, "poll", at(13, 9, 0));
+
+        assert!(log.close_vanished_locks(Some("b"), &["4".to_string()], at(13, 11, 0)));
+
+        assert_eq!(log.last_state(Some("a"), "3"), Some(false));
+        assert_eq!(log.events.last().map(|e| e.source.as_str()), Some("session_gone"));
+        assert_eq!(log.last_state(Some("b"), "4"), Some(true), "a listed session keeps its lock");
+        assert_eq!(log.last_state(Some("b"), "2"), Some(false));
+        assert!(!log.close_vanished_locks(Some("b"), &["4".to_string()], at(13, 12, 0)), "nothing left to close");
+
+        // The unlock is only noticed the next day; the gap still ends with the Oct 12 boot session.
+        let boots = [boot(at(12, 8, 0), at(12, 17, 30)), boot(at(13, 8, 0), at(13, 12, 0))];
+        let gaps = log.locked_gaps(&boots, at(13, 12, 0));
+        assert!(gaps.iter().any(|g| (g.start, g.end) == (at(12, 17, 0), at(12, 17, 30))), "{:?}", gaps);
+        assert!(gaps.iter().all(|g| g.start != at(12, 17, 0) || g.end == at(12, 17, 30)));
+    }
+
+    #[test]
+    fn session_ids_reused_after_a_reboot_are_kept_apart() {
+        let mut log = LockLog::default();
+        log.push(Some("a"), "2", true, "poll", at(12, 17, 0));
+        // logind starts numbering again after the reboot; the new session 2 is listed and locks too.
+        assert!(log.push(Some("b"), "2", true, "signal", at(13, 11, 0)), "not a repeat of the old lock");
+
+        assert!(log.close_vanished_locks(Some("b"), &["2".to_string()], at(13, 11, 30)));
+        assert_eq!(log.last_state(Some("a"), "2"), Some(false), "the lock of the earlier boot is closed");
+        assert_eq!(log.last_state(Some("b"), "2"), Some(true), "the running session keeps its lock");
+
+        let boots = [boot(at(12, 8, 0), at(12, 17, 30)), boot(at(13, 8, 0), at(13, 12, 0))];
+        let mut gaps: Vec<_> = log.locked_gaps(&boots, at(13, 12, 0)).into_iter().map(|g| (g.start, g.end)).collect();
+        gaps.sort();
+        assert_eq!(gaps, [(at(12, 17, 0), at(12, 17, 30)), (at(13, 11, 0), at(13, 12, 0))]);
+    }
+}
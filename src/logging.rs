@@ -0,0 +1,91 @@
+//! Log output for the collector: pretty text, JSON lines or native journald fields.
+//!
+//! Logs go to stderr (or the journal), so command output on stdout (`status --json`, `export`) stays clean.
+
+use clap::ValueEnum;
+use std::io::IsTerminal;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, EnvFilter, Registry};
+
+/// Environment variable holding the filter, e.g. `info` or `avadhi_collector::api=debug,warn`.
+pub const LOG_FILTER_ENV: &str = "AVADHI_LOG";
+/// Environment variable selecting the output format (`pretty`, `json` or `journald`).
+pub const LOG_FORMAT_ENV: &str = "AVADHI_LOG_FORMAT";
+/// Used when neither `--log-filter` nor AVADHI_LOG is set.
+const DEFAULT_FILTER: &str = "info";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines on stderr.
+    Pretty,
+    /// One JSON object per line on stderr, including the current spans.
+    Json,
+    /// Native journald entries with structured fields (PRIORITY, DATE, STATUS, ...).
+    Journald,
+}
+
+/// The format from `--log-format`, else AVADHI_LOG_FORMAT, else journald when stderr is connected
+/// to the journal (systemd sets JOURNAL_STREAM), else pretty.
+fn resolve_format(flag: Option<LogFormat>) -> LogFormat {
+    if let Some(format) = flag {
+        return format;
+    }
+    match std::env::var(LOG_FORMAT_ENV).ok().and_then(|v| LogFormat::from_str(&v, true).ok()) {
+        Some(format) => format,
+        None if std::env::var_os("JOURNAL_STREAM").is_some() => LogFormat::Journald,
+        None => LogFormat::Pretty,
+    }
+}
+
+/// The filter from `--log-filter`, else AVADHI_LOG, else `info`. An invalid filter falls back to `info`.
+fn resolve_filter(flag: Option<&str>) -> (EnvFilter, Option<String>) {
+    let directives = flag.map(str::to_string)
+        .or_else(|| std::env::var(LOG_FILTER_ENV).ok())
+        .unwrap_or_else(|| DEFAULT_FILTER.to_string());
+    match EnvFilter::try_new(&directives) {
+        Ok(filter) => (filter, None),
+        Err(e) => (EnvFilter::new(DEFAULT_FILTER), Some(format!("Invalid log filter '{}': {}. Using '{}'.", directives, e, DEFAULT_FILTER))),
+    }
+}
+
+/// Installs the global subscriber. Call once, before anything logs.
+pub fn init_logging(format: Option<LogFormat>, filter: Option<&str>) {
+    let (filter, filter_warning) = resolve_filter(filter);
+    let mut format = resolve_format(format);
+    let mut journald_warning = None;
+
+    let journald = if format == LogFormat::Journald {
+        match tracing_journald::layer() {
+            Ok(layer) => Some(layer.with_syslog_identifier("avadhi-collector".to_string())),
+            Err(e) => {
+                journald_warning = Some(format!("Cannot connect to journald: {}. Logging to stderr instead.", e));
+                format = LogFormat::Pretty;
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let pretty = (format == LogFormat::Pretty).then(|| fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_target(false));
+    let json = (format == LogFormat::Json).then(|| fmt::layer()
+        .json()
+        .with_writer(std::io::stderr)
+        .with_current_span(true)
+        .with_span_list(true));
+
+    let _ = Registry::default()
+        .with(filter)
+        .with(pretty)
+        .with(json)
+        .with(journald)
+        .try_init();
+
+    // Both are only known before the subscriber exists, so they are logged once it is installed.
+    for warning in journald_warning.into_iter().chain(filter_warning) {
+        tracing::warn!("{}", warning);
+    }
+}
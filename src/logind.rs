@@ -0,0 +1,19 @@
+use anyhow::Result;
+
+pub const LOGIND_BUS_NAME: &str = "org.freedesktop.login1";
+pub const LOGIND_MANAGER_PATH: &str = "/org/freedesktop/login1";
+pub const LOGIND_MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+pub const LOGIND_SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+
+/// Connects to the system bus, or to `address` when one is configured (e.g. a local dbus-daemon stand-in).
+pub async fn connect(address: Option<&str>) -> Result<zbus::Connection> {
+    let connection = match address {
+        Some(address) => zbus::connection::Builder::address(address)?.build().await?,
+        None => zbus::Connection::system().await?,
+    };
+    Ok(connection)
+}
+
+pub async fn manager_proxy(connection: &zbus::Connection) -> Result<zbus::Proxy<'static>> {
+    Ok(zbus::Proxy::new(connection, LOGIND_BUS_NAME, LOGIND_MANAGER_PATH, LOGIND_MANAGER_INTERFACE).await?)
+}
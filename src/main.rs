@@ -1,18 +1,30 @@
-// Correct imports for the necessary modules (defined in lib.rs or separate files)
-mod config;
-mod api;
+// The collector logic lives in the avadhi_collector library (src/lib.rs); this binary only parses
+// arguments and dispatches to it.
 
 // --- Imports for Command Line Argument Parsing and Core Logic ---
-use clap::{Parser, Subcommand}; // Added Subcommand import
-use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone, NaiveDate};
-use std::{collections::HashMap, process::Command};
-use regex::Regex;
+use clap::{Args, Parser, Subcommand}; // Added Subcommand import
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate};
+use std::path::PathBuf;
 use anyhow::{anyhow, Result};
-use tokio;
+use tracing::{error, info, warn};
 
 // NOTE: The signature for 'initial_setup_and_login' MUST be updated in config.rs
-use crate::config::{load_admin_config, load_user_config, initial_setup_and_login, AdminConfig, UserConfig, save_user_config};
-use crate::api::{post_work_span, WorkSpanData};
+use avadhi_collector::config::{load_admin_config, load_user_config, initial_setup_and_login, profile_path, read_config_file, AdminConfig, UserConfig, ADMIN_CONFIG_PATH, USER_CONFIG_PATH};
+use avadhi_collector::app_privacy::collect_app_usage;
+use avadhi_collector::idle::sample_idle;
+use avadhi_collector::locks::{sample_locked_hint, watch_locks};
+use avadhi_collector::daemon::run_daemon;
+use avadhi_collector::status::{collect_status, print_status};
+use avadhi_collector::doctor::{print_checks, run_checks};
+use avadhi_collector::report::{print_replay, print_report, spans_in_range};
+use avadhi_collector::sessions::read_sessions_file;
+use avadhi_collector::export::{write_export, ExportFormat};
+use avadhi_collector::calendar::WorkCalendar;
+use avadhi_collector::history::{print_date, print_run, print_runs};
+use avadhi_collector::store::{history_path, HistoryStore};
+use avadhi_collector::error::{exit_code, CollectorError};
+use avadhi_collector::logging::{init_logging, LogFormat};
+use avadhi_collector::collector::{adjust_day, compute_spans, replay_spans, run_backfill, run_once, run_reconcile, run_trigger, setup_command, tokens_missing, SpanMode};
 
 
 // --- New CLI Argument Structure using clap ---
@@ -24,336 +36,521 @@ struct Cli {
     // NOTE: The subcommand attribute tells clap to look at the Commands enum
     #[clap(subcommand)]
     command: Commands,
+
+    /// Log output: pretty, json or journald. Defaults to AVADHI_LOG_FORMAT, journald under systemd, else pretty.
+    #[clap(long, global = true, value_enum)]
+    log_format: Option<LogFormat>,
+
+    /// Log filter such as "debug" or "avadhi_collector::api=debug,info". Defaults to AVADHI_LOG, else "info".
+    #[clap(long, global = true)]
+    log_filter: Option<String>,
 }
 
 // CRITICAL FIX: The enum holding subcommands must derive `Subcommand`, not `Parser`.
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Runs the collector logic (default action).
-    Run,
+    Run {
+        /// Replay mode: compute spans from a saved `last -x -F reboot` output or a raw wtmp file instead of
+        /// running `last`. Prints the sessions and days; nothing is posted or saved.
+        #[clap(long, value_parser, value_name = "PATH")]
+        from_file: Option<PathBuf>,
+
+        /// Pins "now" for the replay (RFC 3339, e.g. 2026-10-14T18:00:00+02:00): still-running sessions end
+        /// here and its date is "today". Defaults to the current time.
+        #[clap(long, value_parser, requires = "from_file")]
+        now: Option<DateTime<FixedOffset>>,
+
+        /// Only run this profile from [profiles] in Config.toml. Without it every profile is run in turn.
+        #[clap(long)]
+        profile: Option<String>,
+    },
 
     /// Runs the interactive user token setup.
     Setup {
         /// Optional: Overrides historical log tracking by setting the last known posted date (YYYY-MM-DD).
         #[clap(long, value_parser)]
         last_posted_date: Option<String>,
+
+        /// Profile from [profiles] in Config.toml. Required when profiles are configured.
+        #[clap(long)]
+        profile: Option<String>,
     },
-}
 
-// --- Internal Data Structures & Constants (UNCHANGED) ---
-#[derive(Debug, Clone)]
-struct SessionRecord {
-    start_time: DateTime<Local>,
-    end_time: DateTime<Local>,
-}
-const LAST_DATE_FORMAT: &str = "%a %b %d %H:%M:%S %Y";
-
-// --- Data Parsing Functions (UNCHANGED) ---
-
-/// Executes the 'last' command and parses raw output into structured session records. -n 100 so only
-async fn fetch_last_logs() -> Result<Vec<SessionRecord>> {
-    println!("[INFO] Executing 'last -x -F reboot' to fetch logs...");
-
-    let output = tokio::task::spawn_blocking(|| {
-        Command::new("last")
-            // CRITICAL CHANGE: Add -n 100 to fetch the last 100 entries.
-            // This ensures we have a broad history for the collector to filter.
-            .args(&["-x", "-F", "reboot", "-n", "100"])
-            .output()
-    }).await
-    .map_err(|e| anyhow!("Task failed to join: {}", e))?
-    .map_err(|e| anyhow!("I/O Error executing 'last': {}", e))?;
-
-    let stdout = String::from_utf8(output.stdout).map_err(|e| anyhow!("UTF-8 Decoding Error: {}", e))?;
-    let mut sessions = Vec::new();
-
-    // Regex to capture start time and optional end time
-    // FIX APPLIED HERE: Group 2 (End Time) is corrected to mirror the robust date pattern of Group 1 (Start Time).
-    let re = Regex::new(r"reboot\s+system boot\s+.*?\s+([A-Z][a-z]{2}\s+[A-Z][a-z]{2}\s+\d+\s+\d{2}:\d{2}:\d{2}\s+\d{4})\s+(?:-\s+([A-Z][a-z]{2}\s+[A-z]{2}\s+\d+\s+\d{2}:\d{2}:\d{2}\s+\d{4})|still running)")
-        .map_err(|e| anyhow!("Regex Error: {}", e))?;
-
-    // NOTE: The Regex above is still using the incorrect [A-z]{2} for the month in the end capture group!
-    // Let's ensure the corrected regex is used:
-    let re = Regex::new(r"reboot\s+system boot\s+.*?\s+([A-Z][a-z]{2}\s+[A-Z][a-z]{2}\s+\d+\s+\d{2}:\d{2}:\d{2}\s+\d{4})\s+(?:-\s+([A-Z][a-z]{2}\s+[A-Z][a-z]{2}\s+\d+\s+\d{2}:\d{2}:\d{2}\s+\d{4})|still running)")
-        .map_err(|e| anyhow!("Regex Error: {}", e))?;
-    // The inner group was fixed from [A-z]{2} to [A-Z][a-z]{2}. This will now correctly capture Dec 9th and Dec 8th.
-
-    for line in stdout.lines() {
-        if let Some(caps) = re.captures(line) {
-            let start_str = caps.get(1).map_or("", |m| m.as_str());
-            let end_str_opt = caps.get(2).map(|m| m.as_str());
-
-            // ... (Time parsing logic remains the same) ...
-            let start_dt_naive = NaiveDateTime::parse_from_str(start_str, LAST_DATE_FORMAT)
-                                .map_err(|e| anyhow!("Chrono Parse Error (Start): {}", e))?;
-
-            let start_dt_local = Local
-                .from_local_datetime(&start_dt_naive)
-                .single()
-                .ok_or_else(|| anyhow!("Date conversion failed for start time"))?;
-
-            // Determine End Time
-            let end_dt_local = match end_str_opt {
-                Some(end_str) => {
-                    let end_dt_naive = NaiveDateTime::parse_from_str(end_str, LAST_DATE_FORMAT)
-                        .map_err(|e| anyhow!("Chrono Parse Error (End): {}", e))?;
-
-                    Local
-                        .from_local_datetime(&end_dt_naive)
-                        .single()
-                        .ok_or_else(|| anyhow!("Date conversion failed for end time"))?
-                },
-                None => Local::now(), // "still running" means current time
-            };
-
-            if start_dt_local <= end_dt_local {
-                sessions.push(SessionRecord {
-                    start_time: start_dt_local,
-                    end_time: end_dt_local,
-                });
-            }
-        }
-    }
+    /// Shows exactly which app-usage data would be uploaded, after the [app_usage] privacy rules.
+    /// Nothing is sent over the network.
+    AppUsagePreview,
 
-    println!("[INFO] Successfully parsed {} raw sessions.", sessions.len());
-    Ok(sessions)
-}
+    /// Records one idle (away-from-keyboard) sample and the current screen lock state.
+    /// Meant to be triggered every few minutes.
+    IdleSample,
 
-/// Calculates the Earliest Boot/Latest Shutdown span for each calendar day,
-/// and converts the data into the format required by the API (WorkSpanData).
-fn calculate_spans(sessions: Vec<SessionRecord>) -> Result<Vec<WorkSpanData>> {
-    // Key: NaiveDate | Value: (min_start, max_end)
-    let mut daily_data: HashMap<chrono::NaiveDate, (DateTime<Local>, DateTime<Local>)> = HashMap::new();
+    /// Runs continuously, recording logind screen Lock/Unlock events for the user's session(s).
+    LockWatch,
 
-    for session in sessions {
-        let date = session.start_time.date_naive();
-        let end_date = session.end_time.date_naive();
+    /// Runs continuously and triggers the collector daily at daily_check_hour:daily_check_minute.
+    /// For systems without systemd timers (containers, non-systemd distros).
+    Daemon,
 
-        // 1. Update Span for Start Date
-        let entry = daily_data.entry(date)
-            .or_insert_with(|| (session.start_time, session.end_time));
+    /// Summarizes collector health: config files, tokens, last run, pending days and session sources.
+    Status {
+        /// Print the status as JSON instead of human-readable text.
+        #[clap(long)]
+        json: bool,
 
-        if session.start_time < entry.0 {
-            entry.0 = session.start_time; // Earliest Start
-        }
-        if session.end_time > entry.1 {
-            entry.1 = session.end_time; // Latest End
-        }
+        /// Show the credentials and pending days of this profile from [profiles] in Config.toml.
+        #[clap(long)]
+        profile: Option<String>,
+    },
 
-        // 2. If the session crosses midnight, update the span for the end date as well.
-        if date != end_date {
-            let end_entry = daily_data.entry(end_date)
-                .or_insert_with(|| (session.end_time, session.end_time));
+    /// Runs end-to-end preflight checks (config, tokens, wtmp/journal access, Supabase) with remediation hints.
+    /// Exits non-zero if any check fails.
+    Doctor {
+        /// Check the backend and credentials of this profile from [profiles] in Config.toml.
+        #[clap(long)]
+        profile: Option<String>,
+    },
 
-            if session.end_time > end_entry.1 {
-                end_entry.1 = session.end_time;
-            }
-        }
-    }
+    /// Prints daily work spans with totals, averages and weekday/weekend splits. Works fully offline.
+    Report {
+        #[clap(flatten)]
+        range: RangeArgs,
 
-    // --- Final Calculation and Formatting into WorkSpanData ---
-    let mut results = Vec::new();
+        /// Draw ASCII bar charts next to each day.
+        #[clap(long)]
+        bars: bool,
 
-    for (date, (first_boot, last_shutdown)) in daily_data {
-        if last_shutdown <= first_boot {
-            continue;
-        }
+        /// Use the span policy, schedule and adjustments of this profile from [profiles] in Config.toml.
+        #[clap(long)]
+        profile: Option<String>,
+    },
 
-        let span: Duration = last_shutdown - first_boot;
-        let total_seconds = span.num_seconds();
-        let total_minutes = total_seconds / 60;
+    /// Writes daily work spans for a date range as CSV, JSON lines or an iCalendar file. Works fully offline.
+    Export {
+        #[clap(flatten)]
+        range: RangeArgs,
 
-        let hours = total_seconds / 3600;
-        let remaining_minutes = (total_seconds % 3600) / 60;
+        /// Output format. Columns follow the daily_work_span table.
+        #[clap(long, value_enum, default_value = "csv")]
+        format: ExportFormat,
 
-        // Format for API: "8h 30m"
-        let total_span_formatted = format!("{}h {}m", hours, remaining_minutes);
+        /// Write to this file instead of stdout.
+        #[clap(long, short, value_parser)]
+        output: Option<std::path::PathBuf>,
 
-        results.push(WorkSpanData {
-            date: date.format("%Y-%m-%d").to_string(),
-            first_boot: first_boot.format("%H:%M:%S").to_string(),
-            last_shutdown: last_shutdown.format("%H:%M:%S").to_string(),
-            total_span: total_span_formatted,
-            total_span_minutes: total_minutes as i32, // Cast from i64 to i32
-        });
-    }
+        /// Use the span policy, schedule and adjustments of this profile from [profiles] in Config.toml.
+        #[clap(long)]
+        profile: Option<String>,
+    },
 
-    Ok(results)
-}
+    /// Recomputes and upserts exactly the days in --from..=--to. Stored credentials are left as they are.
+    Backfill {
+        /// First day to post (YYYY-MM-DD).
+        #[clap(long, value_parser)]
+        from: NaiveDate,
 
+        /// Last day to post (YYYY-MM-DD).
+        #[clap(long, value_parser)]
+        to: NaiveDate,
 
-/// Filters the calculated WorkSpanData to only include entries newer than or equal to
-/// the date stored in the user configuration (last_posted_date).
-fn filter_data_for_posting(
-    mut historical_data: Vec<WorkSpanData>,
-    user_config: &UserConfig,
-) -> Vec<WorkSpanData> {
-    let last_posted_date = match user_config.last_posted_date.as_ref() {
-        Some(date_str) => NaiveDate::parse_from_str(date_str, "%Y-%m-%d").ok(),
-        None => None,
-    };
+        /// Re-post days even if the local history shows the same data was already posted successfully.
+        #[clap(long)]
+        force: bool,
 
-    if last_posted_date.is_none() {
-        println!("[WARN] No last posted date found in config. Posting all calculated history.");
-        return historical_data;
-    }
+        /// Profile from [profiles] in Config.toml. Required when profiles are configured.
+        #[clap(long)]
+        profile: Option<String>,
+    },
 
-    let filter_date = last_posted_date.unwrap();
+    /// Manually corrects or annotates one day (stored in AvadhiOverrides.toml, or the profile's AvadhiOverrides.<name>.toml).
+    Adjust {
+        /// The day to adjust (YYYY-MM-DD).
+        #[clap(value_parser)]
+        date: NaiveDate,
 
-    // Use >= instead of >
-    // This ensures we always re-post the last recorded day (the "Yesterday" data)
-    // to finalize its shutdown time.
-    historical_data.retain(|data| {
-        if let Ok(data_date) = NaiveDate::parse_from_str(&data.date, "%Y-%m-%d") {
-            // Keep data if date is equal to OR greater than the last posted date
-            data_date >= filter_date
-        } else {
-            // Log an error if date parsing fails for an entry, but keep it just in case
-            eprintln!("[ERROR] Failed to parse date in historical data: {}", data.date);
-            true
-        }
-    });
+        /// The day does not count at all; it is posted as 0 minutes.
+        #[clap(long, conflicts_with = "span")]
+        exclude: bool,
 
-    println!("[INFO] Filtering data to start from or after: {}.", filter_date);
+        /// Replace the computed span, e.g. 09:00-17:30 (local time).
+        #[clap(long)]
+        span: Option<String>,
 
+        /// Annotation shown on the dashboard. An empty string removes it.
+        #[clap(long)]
+        note: Option<String>,
 
-    // Sort by date to ensure we post chronologically (essential for configuration update logic)
-    historical_data.sort_by(|a, b| a.date.cmp(&b.date));
+        /// Remove every adjustment for this day.
+        #[clap(long, conflicts_with_all = ["exclude", "span", "note"])]
+        clear: bool,
 
-    historical_data
-}
+        /// Profile from [profiles] in Config.toml. Required when profiles are configured.
+        #[clap(long)]
+        profile: Option<String>,
+    },
 
+    /// Compares recent local spans with the rows on the server and re-posts missing or different days.
+    Reconcile {
+        /// Number of finalized days before today to compare. Defaults to [reconcile] window_days.
+        #[clap(long, value_parser)]
+        days: Option<i64>,
 
-/// Main entry point for the data collector logic: retrieves data and posts it asynchronously.
-async fn run_collector_logic(admin_config: &AdminConfig, user_config: &mut UserConfig) -> Result<()> {
-    let current_day_naive = Local::now().date_naive();
-    println!("[INFO] Collector running on day: {}", current_day_naive.format("%Y-%m-%d"));
+        /// Only report drift; post nothing.
+        #[clap(long)]
+        dry_run: bool,
 
-    let sessions = match fetch_last_logs().await {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("[FATAL] Failed to retrieve and calculate historical data: {}. Cannot post anything.", e);
-            return Err(e.into());
-        }
-    };
+        /// Profile from [profiles] in Config.toml. Required when profiles are configured.
+        #[clap(long)]
+        profile: Option<String>,
+    },
 
-    let all_historical_data = calculate_spans(sessions)?;
+    /// Lists recent runs from the local history, or shows one run or one day in detail. Works fully offline.
+    History {
+        /// Show everything recorded for this run (ID from the list).
+        #[clap(value_parser)]
+        run_id: Option<i64>,
 
-    // 1. FILTER: Only process data newer than or equal to the last successful post
-    let data_to_post = filter_data_for_posting(all_historical_data, user_config);
+        /// Show the computed span and every post attempt for this day (YYYY-MM-DD).
+        #[clap(long, value_parser, conflicts_with = "run_id")]
+        date: Option<NaiveDate>,
 
-    let total_entries = data_to_post.len();
+        /// Number of runs to list.
+        #[clap(long, value_parser, default_value_t = 20)]
+        limit: usize,
 
-    if total_entries == 0 {
-        println!("No new work span data found to post since last run.");
-        return Ok(());
-    }
+        /// Profile from [profiles] in Config.toml. Required when profiles are configured.
+        #[clap(long)]
+        profile: Option<String>,
+    },
+}
 
-    println!("\n[INFO] Starting posting process for {} historical day(s).", total_entries);
+/// Date range selection shared by the local reporting commands. Defaults to the current week.
+#[derive(Args, Debug)]
+struct RangeArgs {
+    /// The current week, Monday through today.
+    #[clap(long, conflicts_with_all = ["month", "from", "to"])]
+    week: bool,
 
-    let mut last_successful_date_posted: Option<String> = user_config.last_posted_date.clone();
+    /// The current month, the 1st through today.
+    #[clap(long, conflicts_with_all = ["from", "to"])]
+    month: bool,
 
-    for data in data_to_post {
-        println!("\n--- Processing data for date: {} ---", data.date);
+    /// First day of the range (YYYY-MM-DD).
+    #[clap(long, value_parser)]
+    from: Option<NaiveDate>,
 
-        // 2. POST: Post the data
-        match post_work_span(data.clone(), admin_config, user_config).await {
-            Ok(_) => {
-                // Determine if this successfully posted date is "Today"
-                let posted_date_naive = match NaiveDate::parse_from_str(&data.date, "%Y-%m-%d") {
-                    Ok(d) => d,
-                    Err(_) => {
-                        eprintln!("[ERROR] Failed to parse posted date string '{}'. Skipping config update for safety.", data.date);
-                        continue;
-                    }
-                };
+    /// Last day of the range (YYYY-MM-DD). Defaults to today.
+    #[clap(long, value_parser, requires = "from")]
+    to: Option<NaiveDate>,
+}
+
+impl RangeArgs {
+    /// Resolves the selection to an inclusive (from, to) pair relative to `today`.
+    fn resolve(&self, today: NaiveDate) -> Result<(NaiveDate, NaiveDate)> {
+        let range = if self.month {
+            (today.with_day(1).unwrap_or(today), today)
+        } else if let Some(from) = self.from {
+            (from, self.to.unwrap_or(today))
+        } else {
+            (today - Duration::days(today.weekday().num_days_from_monday() as i64), today)
+        };
 
-                // Conditional Config Update
-                // We only update the config date if the successfully posted data is NOT today.
-                // This ensures "Today" is re-posted tomorrow for finalization.
-                if posted_date_naive < current_day_naive {
-                    last_successful_date_posted = Some(data.date);
-                } else {
-                    println!("[INFO] Posted data for today ({}). Will NOT update config to this date to ensure finalization tomorrow.", data.date);
+        if range.0 > range.1 {
+            return Err(anyhow!("--from {} is after --to {}.", range.0, range.1));
+        }
+        Ok(range)
+    }
+}
+
+// --- Main Execution Block ---
+
+fn main() {
+    // 1. Parse CLI Arguments
+    let cli = Cli::parse();
+    init_logging(cli.log_format, cli.log_filter.as_deref());
+
+    // Status is read-only and keeps stdout clean for --json, so it runs before any config loading output.
+    if let Commands::Status { json, profile } = &cli.command {
+        if let Err(e) = read_config_file::<AdminConfig>(ADMIN_CONFIG_PATH).unwrap_or_default().for_profile(profile.as_deref()) {
+            error!("{}", e);
+            std::process::exit(CollectorError::EXIT_CONFIG_MISSING);
+        }
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let report = runtime.block_on(collect_status(profile.as_deref()));
+        if *json {
+            match serde_json::to_string_pretty(&report) {
+                Ok(out) => println!("{}", out),
+                Err(e) => {
+                    let e = anyhow!("Failed to serialize status: {}", e);
+                    error!("{}", e);
+                    std::process::exit(exit_code(&e));
                 }
-            },
-            Err(e) => {
-                eprintln!("[ERROR] Failed to post data for date {}: {}. Aborting remaining posts.", data.date, e);
-                // If posting fails, we stop the iteration.
-                break;
             }
+        } else {
+            print_status(&report);
         }
+        return;
     }
 
-    // 3. UPDATE CONFIG: Persist the last successfully finalized date (which is yesterday or older).
-    if user_config.last_posted_date != last_successful_date_posted {
-        user_config.last_posted_date = last_successful_date_posted.clone();
+    // Doctor diagnoses the configuration itself, so it must not depend on it loading.
+    if let Commands::Doctor { profile } = &cli.command {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
 
-        save_user_config(user_config);
+        let results = runtime.block_on(run_checks(profile.as_deref()));
+        if !print_checks(&results) {
+            std::process::exit(CollectorError::EXIT_DOCTOR_FAILED);
+        }
+        return;
+    }
 
-        if let Some(date) = last_successful_date_posted {
-             println!("\n[SUCCESS] User configuration updated. Last FINALIZED post date is now: {}", date);
-        } else {
-             println!("\n[INFO] No finalized posts were made in this run, or all posts failed.");
+    // Adjustments only touch the local overrides file (the profile's, with profiles configured).
+    if let Commands::Adjust { date, exclude, span, note, clear, profile } = &cli.command {
+        let profile = match read_config_file::<AdminConfig>(ADMIN_CONFIG_PATH).unwrap_or_default().single_profile(profile.as_deref()) {
+            Ok(profile) => profile,
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(CollectorError::EXIT_CONFIG_MISSING);
+            }
+        };
+        if let Err(e) = adjust_day(profile.as_deref(), *date, *exclude, span.as_deref(), note.as_deref(), *clear) {
+            error!("{}", e);
+            std::process::exit(exit_code(&e));
         }
-    } else {
-        println!("\n[INFO] Last finalized date remains unchanged.");
+        return;
     }
 
+    // A replay only reads the given file (and Config.toml / AvadhiConfig.toml if present), so it works offline.
+    if let Commands::Run { from_file: Some(path), now, profile } = &cli.command {
+        let now = now.map_or_else(Local::now, |now| now.with_timezone(&Local));
+        let admin_config = read_config_file::<AdminConfig>(ADMIN_CONFIG_PATH).unwrap_or_else(|e| {
+            warn!("{}. Using default schedule, balance and span policy.", e);
+            AdminConfig::default()
+        });
+        let admin_config = match admin_config.for_profile(profile.as_deref()) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(CollectorError::EXIT_CONFIG_MISSING);
+            }
+        };
+        let last_posted = read_config_file::<UserConfig>(&profile_path(USER_CONFIG_PATH, profile.as_deref())).ok()
+            .and_then(|user| user.last_posted_date)
+            .and_then(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok());
+
+        let replay = read_sessions_file(path, now).and_then(|(sessions, format)| {
+            println!("Replaying {} ({}) as of {}. Nothing is posted or saved.\n", path.display(), format, now.format("%Y-%m-%d %H:%M:%S %:z"));
+            replay_spans(&admin_config, sessions.clone()).map(|spans| (sessions, spans))
+        });
+        match replay {
+            Ok((sessions, spans)) => print_replay(&sessions, &spans, now.date_naive(), last_posted),
+            Err(e) => {
+                error!("Replay failed: {:#}", e);
+                std::process::exit(exit_code(&e));
+            }
+        }
+        return;
+    }
 
-    println!("\nCollector run finished successfully.");
-    Ok(())
-}
+    // History only reads AvadhiHistory.sqlite (or the profile's AvadhiHistory.<name>.sqlite).
+    if let Commands::History { run_id, date, limit, profile } = &cli.command {
+        let profile = match read_config_file::<AdminConfig>(ADMIN_CONFIG_PATH).unwrap_or_default().single_profile(profile.as_deref()) {
+            Ok(profile) => profile,
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(CollectorError::EXIT_CONFIG_MISSING);
+            }
+        };
+        let path = history_path(profile.as_deref());
+        if !std::path::Path::new(&path).exists() {
+            println!("No local history yet ({} is created by the first run).", path);
+            return;
+        }
+        let result = HistoryStore::open_profile(profile.as_deref()).and_then(|store| match (run_id, date) {
+            (Some(id), _) => print_run(&store, *id),
+            (None, Some(date)) => print_date(&store, *date),
+            (None, None) => store.recent_runs(*limit).map(|runs| print_runs(&runs)),
+        });
+        if let Err(e) = result {
+            error!("{}", e);
+            std::process::exit(exit_code(&e));
+        }
+        return;
+    }
 
-// --- Main Execution Block ---
+    // Reports and exports are computed from local data only, so they need neither credentials nor a reachable backend.
+    if let Commands::Report { range, profile, .. } | Commands::Export { range, profile, .. } = &cli.command {
+        let (from, to) = match range.resolve(Local::now().date_naive()) {
+            Ok(r) => r,
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(CollectorError::EXIT_USAGE);
+            }
+        };
+        let admin_config = read_config_file::<AdminConfig>(ADMIN_CONFIG_PATH).unwrap_or_else(|e| {
+            warn!("{}. Using default idle/lock settings.", e);
+            AdminConfig::default()
+        });
+        let admin_config = match admin_config.for_profile(profile.as_deref()) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(CollectorError::EXIT_CONFIG_MISSING);
+            }
+        };
 
-fn main() {
-    // 1. Parse CLI Arguments
-    let cli = Cli::parse();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let spans = match runtime.block_on(compute_spans(&admin_config, profile.as_deref(), SpanMode::Preview, Local::now())) {
+            Ok((_, spans)) => spans_in_range(spans, from, to),
+            Err(e) => {
+                error!("Cannot compute work spans: {}", e);
+                std::process::exit(exit_code(&e));
+            }
+        };
+
+        match &cli.command {
+            Commands::Report { bars, .. } => print_report(&spans, from, to, *bars, &WorkCalendar::from_config_or_warn(&admin_config.schedule)),
+            Commands::Export { format, output, .. } => {
+                let result = match output {
+                    Some(path) => std::fs::File::create(path)
+                        .map_err(|e| anyhow!("Cannot create {}: {}", path.display(), e))
+                        .and_then(|file| write_export(&mut std::io::BufWriter::new(file), &spans, *format)),
+                    None => write_export(&mut std::io::stdout().lock(), &spans, *format),
+                };
+                match (result, output) {
+                    (Ok(()), Some(path)) => info!("Exported {} day(s) ({} .. {}) to {}.", spans.len(), from, to, path.display()),
+                    (Ok(()), None) => {},
+                    (Err(e), _) => {
+                        error!("Export failed: {:#}", e);
+                        std::process::exit(exit_code(&e));
+                    }
+                }
+            },
+            _ => unreachable!(),
+        }
+        return;
+    }
 
     // 2. Load static Admin Configuration (needed for setup and run)
     let admin_config = load_admin_config();
 
-    // 3. Critical check: Ensure AdminConfig has essential values (URL and Key)
-    if admin_config.supabase_url.is_none()
-        || admin_config.supabase_anon_key.is_none()
-        || admin_config.web_app_url.is_none()
-    {
-        eprintln!("\nFATAL: Critical Admin Configuration (Config.toml) is missing Supabase URL, Anon Key, or Web App URL. Cannot proceed. Please check and set Config.toml.");
+    // The preview never talks to Supabase, so it must work before the backend is configured.
+    if let Commands::AppUsagePreview = &cli.command {
+        match collect_app_usage(&admin_config.app_usage) {
+            Ok(records) => {
+                info!("{} app-usage record(s) would be uploaded (name mode: {:?}).", records.len(), admin_config.app_usage.name_mode);
+                match serde_json::to_string_pretty(&records) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => {
+                        let e = anyhow!("Failed to serialize app-usage preview: {}", e);
+                        error!("{}", e);
+                        std::process::exit(exit_code(&e));
+                    }
+                }
+            },
+            Err(e) => {
+                error!("Cannot build app-usage preview: {:#}", e);
+                std::process::exit(exit_code(&e));
+            }
+        }
         return;
     }
 
+    // 3. Critical check: Ensure AdminConfig has essential values (URL and Key), for every profile if there are any
+    let profiles = admin_config.selected_profiles(None).unwrap_or_else(|e| {
+        error!("{}", e);
+        std::process::exit(CollectorError::EXIT_CONFIG_MISSING);
+    });
+    for profile in &profiles {
+        if !admin_config.for_profile(profile.as_deref()).is_ok_and(|config| config.backend_configured()) {
+            let scope = profile.as_ref().map_or(String::new(), |name| format!(" (profile '{}')", name));
+            error!("Critical Admin Configuration (Config.toml) is missing Supabase URL, Anon Key, or Web App URL{}. Cannot proceed. Please check and set Config.toml.", scope);
+            std::process::exit(CollectorError::EXIT_CONFIG_MISSING);
+        }
+    }
+
+    // The one profile that setup, backfill and reconcile work on, with its effective configuration.
+    let single_profile = |selected: Option<&str>| -> (Option<String>, AdminConfig) {
+        admin_config.single_profile(selected)
+            .and_then(|profile| admin_config.for_profile(profile.as_deref()).map(|config| (profile, config)))
+            .unwrap_or_else(|e| {
+                error!("{}", e);
+                std::process::exit(CollectorError::EXIT_CONFIG_MISSING);
+            })
+    };
+
 
     match cli.command {
-        Commands::Setup { last_posted_date } => {
+        Commands::Setup { last_posted_date, profile } => {
             // --- SETUP MODE ---
+            let (profile, profile_config) = single_profile(profile.as_deref());
             // Load a dummy user config to pass its mutable reference,
             // the setup function will overwrite it.
-            let mut user_config = load_user_config();
+            let mut user_config = load_user_config(profile.as_deref());
 
-            println!("Running initial user setup.");
+            match &profile {
+                Some(name) => println!("Running initial user setup for profile '{}'.", name),
+                None => println!("Running initial user setup."),
+            }
 
             // CRITICAL: Call the setup function with the optional date argument
-            initial_setup_and_login(&admin_config, &mut user_config, last_posted_date);
+            initial_setup_and_login(&profile_config, &mut user_config, last_posted_date);
 
             // Setup is complete, exit gracefully. The bootstrap script will restart the service.
             println!("Setup finished successfully. Ready to run the collector service.");
         },
 
-        Commands::Run => {
-            // --- RUN MODE (Default Service Behavior) ---
+        Commands::IdleSample => {
+            // --- IDLE SAMPLING MODE (local only) ---
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            let idle = runtime.block_on(sample_idle(&admin_config.idle));
+            match &idle {
+                Ok(log) => info!("Idle sample recorded via {}. {} closed idle period(s) on file.",
+                    log.last_source.as_deref().unwrap_or("unknown"), log.periods.len()),
+                Err(e) => error!("Idle sample failed: {:#}", e),
+            }
+
+            if let Err(e) = runtime.block_on(sample_locked_hint(&admin_config.lock)) {
+                warn!("Lock state sample failed: {}", e);
+            }
 
-            let mut user_config = load_user_config();
+            // Let avadhi-idle.service show the failure.
+            if let Err(e) = idle {
+                std::process::exit(exit_code(&e));
+            }
+        },
 
-            // Check for user token requirement. If missing, it's a fatal error in run mode.
-            if user_config.access_token.is_none()
-                || user_config.refresh_token.is_none()
-                || user_config.user_id.is_none()
-            {
-                eprintln!("\nFATAL: User tokens are missing. Please run the interactive setup command manually first:");
-                eprintln!("/opt/avadhi-collector/avadhi-collector setup"); // Updated command to use 'setup' subcommand
-                return;
+        Commands::LockWatch => {
+            // --- LOCK WATCH MODE (long-running, local only) ---
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            // A non-zero exit lets avadhi-locks.service (Restart=on-failure) restart the watcher.
+            if let Err(e) = runtime.block_on(watch_locks(&admin_config.lock)) {
+                error!("Lock watcher stopped: {:#}", e);
+                std::process::exit(exit_code(&e));
             }
+        },
+
+        Commands::Run { profile, .. } => {
+            // --- RUN MODE (Default Service Behavior) ---
+            // Missing tokens are reported per profile by run_once, so one unconfigured account does not stop the others.
 
             // 6. Start the main runtime loop
             let runtime = tokio::runtime::Builder::new_current_thread()
@@ -362,8 +559,68 @@ fn main() {
                 .unwrap();
 
             // Pass both configs to the collector logic
-            runtime.block_on(run_collector_logic(&admin_config, &mut user_config))
-                .expect("Collector runtime failed unexpectedly.");
+            if let Err(e) = runtime.block_on(run_once(&admin_config, run_trigger(), profile.as_deref())) {
+                error!("Collector run failed: {:#}", e);
+                std::process::exit(exit_code(&e));
+            }
+        },
+
+        Commands::Backfill { from, to, force, profile } => {
+            // --- BACKFILL MODE ---
+            let (profile, profile_config) = single_profile(profile.as_deref());
+            let mut user_config = load_user_config(profile.as_deref());
+            if tokens_missing(&user_config) {
+                error!("User tokens are missing. Please run '{}' first.", setup_command(profile.as_deref()));
+                std::process::exit(CollectorError::EXIT_CONFIG_MISSING);
+            }
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            if let Err(e) = runtime.block_on(run_backfill(&profile_config, &mut user_config, from, to, force)) {
+                error!("Backfill failed: {:#}", e);
+                std::process::exit(exit_code(&e));
+            }
+        },
+
+        Commands::Reconcile { days, dry_run, profile } => {
+            // --- RECONCILE MODE ---
+            let (profile, profile_config) = single_profile(profile.as_deref());
+            let mut user_config = load_user_config(profile.as_deref());
+            if tokens_missing(&user_config) {
+                error!("User tokens are missing. Please run '{}' first.", setup_command(profile.as_deref()));
+                std::process::exit(CollectorError::EXIT_CONFIG_MISSING);
+            }
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            let days = days.unwrap_or(profile_config.reconcile.window_days);
+            if let Err(e) = runtime.block_on(run_reconcile(&profile_config, &mut user_config, days, dry_run)) {
+                error!("Reconcile failed: {:#}", e);
+                std::process::exit(exit_code(&e));
+            }
+        },
+
+        Commands::Status { .. } | Commands::Doctor { .. } | Commands::Report { .. } | Commands::Export { .. } | Commands::Adjust { .. } | Commands::History { .. } => unreachable!("handled before configuration loading"),
+        Commands::AppUsagePreview => unreachable!("handled before the backend check"),
+
+        Commands::Daemon => {
+            // --- DAEMON MODE (internal scheduler) ---
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            let result = runtime.block_on(run_daemon(&admin_config, || run_once(&admin_config, "daemon", None)));
+            if let Err(e) = result {
+                error!("Daemon stopped: {}", e);
+                std::process::exit(exit_code(&e));
+            }
         }
     }
 }
\ No newline at end of file
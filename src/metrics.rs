@@ -0,0 +1,147 @@
+//! Per-run counters, written as a Prometheus textfile for node_exporter's textfile collector.
+
+use chrono::{DateTime, Local, NaiveDate};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::{info, warn};
+
+use crate::config::MetricsConfig;
+use crate::fileio::write_atomically;
+
+/// Counters of the current run. Reset by `run_once`, bumped wherever the event happens.
+/// With several profiles every counter is the sum over all of them.
+#[derive(Debug, Default)]
+pub struct RunMetrics {
+    pub sessions_parsed: AtomicU64,
+    pub days_computed: AtomicU64,
+    pub days_posted: AtomicU64,
+    pub days_failed: AtomicU64,
+    /// Requests repeated after a network error or a 5xx response.
+    pub http_retries: AtomicU64,
+    /// Access token refresh attempts (after a 401).
+    pub token_refreshes: AtomicU64,
+}
+
+static RUN_METRICS: RunMetrics = RunMetrics {
+    sessions_parsed: AtomicU64::new(0),
+    days_computed: AtomicU64::new(0),
+    days_posted: AtomicU64::new(0),
+    days_failed: AtomicU64::new(0),
+    http_retries: AtomicU64::new(0),
+    token_refreshes: AtomicU64::new(0),
+};
+
+/// The process-wide counters of the current run.
+pub fn run_metrics() -> &'static RunMetrics {
+    &RUN_METRICS
+}
+
+impl RunMetrics {
+    pub fn inc(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(counter: &AtomicU64) -> u64 {
+        counter.load(Ordering::Relaxed)
+    }
+
+    pub fn add(counter: &AtomicU64, value: usize) {
+        counter.fetch_add(value as u64, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        for (_, counter, _) in self.counters() {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// (metric suffix, counter, help) for every counter, in output order.
+    fn counters(&self) -> [(&'static str, &AtomicU64, &'static str); 6] {
+        [
+            ("sessions_parsed", &self.sessions_parsed, "Boot sessions parsed from last/wtmp in the last run."),
+            ("days_computed", &self.days_computed, "Days with a computed work span in the last run."),
+            ("days_posted", &self.days_posted, "Days posted successfully in the last run."),
+            ("days_failed", &self.days_failed, "Days whose post failed in the last run."),
+            ("http_retries", &self.http_retries, "HTTP requests retried after a network error or 5xx in the last run."),
+            ("token_refreshes", &self.token_refreshes, "Access token refresh attempts in the last run."),
+        ]
+    }
+}
+
+fn write_gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "# HELP avadhi_collector_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE avadhi_collector_{} gauge", name);
+    let _ = writeln!(out, "avadhi_collector_{} {}", name, value);
+}
+
+/// Renders the textfile for a run that started at `started_at`.
+/// `last_finalized` is last_posted_date after the run; it is exported as 00:00 UTC of that day.
+pub fn render_textfile(metrics: &RunMetrics, started_at: DateTime<Local>, success: bool, last_finalized: Option<NaiveDate>) -> String {
+    let mut out = String::new();
+    write_gauge(&mut out, "last_run_timestamp_seconds", "Unix time the last collector run started.", started_at.timestamp());
+    write_gauge(&mut out, "last_run_success", "1 if the last collector run succeeded, else 0.", u8::from(success));
+    for (name, counter, help) in metrics.counters() {
+        write_gauge(&mut out, name, help, counter.load(Ordering::Relaxed));
+    }
+    if let Some(date) = last_finalized.and_then(|d| d.and_hms_opt(0, 0, 0)) {
+        write_gauge(&mut out, "last_finalized_date_timestamp_seconds", "Latest finalized (posted) date, as 00:00 UTC of that day.", date.and_utc().timestamp());
+    }
+    out
+}
+
+/// Writes the run's metrics if `[metrics]` is enabled. Failures are logged, never fatal.
+pub fn write_run_metrics(config: &MetricsConfig, started_at: DateTime<Local>, success: bool, last_finalized: Option<NaiveDate>) {
+    if !config.enabled {
+        return;
+    }
+    let contents = render_textfile(run_metrics(), started_at, success, last_finalized);
+    match write_atomically(Path::new(&config.textfile_path), &contents) {
+        Ok(()) => info!("Metrics written to {}.", config.textfile_path),
+        Err(e) => warn!("Failed to write metrics: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn textfile_has_every_gauge_with_help_and_type() {
+        let metrics = RunMetrics::default();
+        // Two profiles: each parses sessions and posts a day.
+        for _ in 0..2 {
+            RunMetrics::add(&metrics.sessions_parsed, 3);
+            RunMetrics::add(&metrics.days_computed, 5);
+            RunMetrics::inc(&metrics.days_posted);
+        }
+        RunMetrics::inc(&metrics.days_failed);
+        let started_at = Local.timestamp_opt(1_760_000_000, 0).unwrap();
+
+        let out = render_textfile(&metrics, started_at, false, NaiveDate::from_ymd_opt(2026, 10, 12));
+
+        let samples: Vec<&str> = out.lines().filter(|l| !l.starts_with('#')).collect();
+        assert_eq!(samples, [
+            "avadhi_collector_last_run_timestamp_seconds 1760000000",
+            "avadhi_collector_last_run_success 0",
+            "avadhi_collector_sessions_parsed 6",
+            "avadhi_collector_days_computed 10",
+            "avadhi_collector_days_posted 2",
+            "avadhi_collector_days_failed 1",
+            "avadhi_collector_http_retries 0",
+            "avadhi_collector_token_refreshes 0",
+            "avadhi_collector_last_finalized_date_timestamp_seconds 1791763200",
+        ]);
+        assert_eq!(out.lines().filter(|l| l.starts_with("# HELP ")).count(), samples.len());
+        assert!(out.contains("# TYPE avadhi_collector_days_posted gauge\n"));
+    }
+
+    #[test]
+    fn textfile_omits_last_finalized_date_before_the_first_post() {
+        let out = render_textfile(&RunMetrics::default(), Local::now(), true, None);
+
+        assert!(out.contains("avadhi_collector_last_run_success 1\n"));
+        assert!(!out.contains("last_finalized_date"));
+    }
+}
@@ -0,0 +1,89 @@
+use chrono::{NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use tracing::warn;
+
+use crate::config::profile_path;
+
+pub const OVERRIDES_PATH: &str = "AvadhiOverrides.toml";
+
+/// AvadhiOverrides.toml, or AvadhiOverrides.<profile>.toml for a named profile.
+pub fn overrides_path(profile: Option<&str>) -> String {
+    profile_path(OVERRIDES_PATH, profile)
+}
+
+/// A manual correction for one day, keyed by date in AvadhiOverrides.toml.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct DayOverride {
+    /// The day does not count at all (machine left on, used by someone else, ...). Posted as 0 minutes.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exclude: bool,
+
+    /// Replaces the computed span, e.g. "09:00-17:30". Idle/locked gaps are not subtracted from it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<String>,
+
+    /// Free-text annotation shown on the dashboard.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct Overrides {
+    #[serde(default)]
+    pub days: BTreeMap<String, DayOverride>,
+}
+
+impl Overrides {
+    pub fn get(&self, date: NaiveDate) -> Option<&DayOverride> {
+        self.days.get(&date.format("%Y-%m-%d").to_string())
+    }
+}
+
+/// Parses "HH:MM-HH:MM" into (start, end). The end must be after the start on the same day.
+pub fn parse_span(value: &str) -> Result<(NaiveTime, NaiveTime)> {
+    let (start, end) = value.split_once('-')
+        .ok_or_else(|| anyhow!("Invalid span '{}': expected HH:MM-HH:MM", value))?;
+    let parse = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M")
+        .map_err(|e| anyhow!("Invalid time '{}' in span '{}': {}", t.trim(), value, e));
+    let (start, end) = (parse(start)?, parse(end)?);
+    if end <= start {
+        return Err(anyhow!("Invalid span '{}': the end must be after the start", value));
+    }
+    Ok((start, end))
+}
+
+/// Loads the overrides of `profile`. A missing file means no overrides.
+pub fn load_overrides(profile: Option<&str>) -> Result<Overrides> {
+    let path = overrides_path(profile);
+    if !Path::new(&path).exists() {
+        return Ok(Overrides::default());
+    }
+    let contents = fs::read_to_string(&path).map_err(|e| anyhow!("Error reading {}: {}", path, e))?;
+    let overrides: Overrides = toml::from_str(&contents).map_err(|e| anyhow!("Error parsing {}: {}", path, e))?;
+
+    for (date, day) in &overrides.days {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|e| anyhow!("Invalid date '{}' in {}: {}", date, path, e))?;
+        if let Some(span) = &day.span {
+            parse_span(span)?;
+        }
+    }
+    Ok(overrides)
+}
+
+/// Like `load_overrides`, but a broken file is reported and ignored so collection keeps working.
+pub fn load_overrides_or_warn(profile: Option<&str>) -> Overrides {
+    load_overrides(profile).unwrap_or_else(|e| {
+        warn!("{}. Manual adjustments are ignored until it is fixed.", e);
+        Overrides::default()
+    })
+}
+
+pub fn save_overrides(overrides: &Overrides, profile: Option<&str>) -> Result<()> {
+    let path = overrides_path(profile);
+    let contents = toml::to_string_pretty(overrides).map_err(|e| anyhow!("Error serializing overrides: {}", e))?;
+    fs::write(&path, contents).map_err(|e| anyhow!("Error writing to {}: {}", path, e))
+}
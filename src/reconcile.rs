@@ -0,0 +1,230 @@
+use chrono::NaiveTime;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+
+use crate::api::{fetch_work_spans, RemoteWorkSpan, SupabaseClient, WorkSpanData};
+use crate::config::UserConfig;
+
+/// How a day differs between the local computation and the server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DriftKind {
+    /// Computed locally but no row on the server (deleted, or a previous post failed).
+    Missing,
+    /// Both exist but disagree; lists "field: server -> local".
+    Different(Vec<String>),
+    /// A row on the server with no local data (e.g. posted from another machine). Reported only.
+    ServerOnly,
+}
+
+#[derive(Debug, Clone)]
+pub struct DayDrift {
+    pub date: String,
+    pub kind: DriftKind,
+}
+
+impl DayDrift {
+    /// True if re-posting the local span fixes this drift.
+    pub fn needs_post(&self) -> bool {
+        matches!(self.kind, DriftKind::Missing | DriftKind::Different(_))
+    }
+}
+
+/// PostgREST may render TIME columns with fractional seconds; compare them as times.
+fn same_time(local: &str, remote: &str) -> bool {
+    match (NaiveTime::parse_from_str(local, "%H:%M:%S"), NaiveTime::parse_from_str(remote, "%H:%M:%S%.f")) {
+        (Ok(l), Ok(r)) => l == r,
+        _ => local == remote,
+    }
+}
+
+fn differences(local: &WorkSpanData, remote: &RemoteWorkSpan) -> Vec<String> {
+    let mut diffs = Vec::new();
+    if !same_time(&local.first_boot, &remote.first_boot) {
+        diffs.push(format!("first_boot: {} -> {}", remote.first_boot, local.first_boot));
+    }
+    if !same_time(&local.last_shutdown, &remote.last_shutdown) {
+        diffs.push(format!("last_shutdown: {} -> {}", remote.last_shutdown, local.last_shutdown));
+    }
+    if local.total_span_minutes as i64 != remote.total_span_minutes {
+        diffs.push(format!("total_span_minutes: {} -> {}", remote.total_span_minutes, local.total_span_minutes));
+    }
+    if local.total_span != remote.total_span {
+        diffs.push(format!("total_span: {} -> {}", remote.total_span, local.total_span));
+    }
+    if local.adjusted != remote.adjusted {
+        diffs.push(format!("adjusted: {} -> {}", remote.adjusted, local.adjusted));
+    }
+    if local.note != remote.note {
+        diffs.push(format!("note: {:?} -> {:?}", remote.note, local.note));
+    }
+    // Rows posted before the calendar and balance columns existed have them null. That is not drift:
+    // re-posting every old day would only fill in values nobody relied on.
+    if let Some(day_type) = remote.day_type.as_deref().filter(|d| *d != local.day_type.as_str()) {
+        diffs.push(format!("day_type: {} -> {}", day_type, local.day_type.as_str()));
+    }
+    for (field, remote_value, local_value) in [
+        ("expected_minutes", remote.expected_minutes, local.expected_minutes),
+        ("deviation_minutes", remote.deviation_minutes, local.deviation_minutes),
+        ("week_balance_minutes", remote.week_balance_minutes, local.week_balance_minutes),
+        ("month_balance_minutes", remote.month_balance_minutes, local.month_balance_minutes),
+    ] {
+        if let Some(remote_value) = remote_value.filter(|m| *m != local_value as i64) {
+            diffs.push(format!("{}: {} -> {}", field, remote_value, local_value));
+        }
+    }
+    diffs
+}
+
+/// Compares local spans with server rows for the same window, ordered by date. Matching days are omitted.
+pub fn compare_spans(local: &[WorkSpanData], remote: &[RemoteWorkSpan]) -> Vec<DayDrift> {
+    let remote_by_date: BTreeMap<&str, &RemoteWorkSpan> = remote.iter().map(|r| (r.date.as_str(), r)).collect();
+    let mut drift = BTreeMap::new();
+
+    for span in local {
+        let kind = match remote_by_date.get(span.date.as_str()) {
+            None => DriftKind::Missing,
+            Some(row) => {
+                let diffs = differences(span, row);
+                if diffs.is_empty() {
+                    continue;
+                }
+                DriftKind::Different(diffs)
+            }
+        };
+        drift.insert(span.date.clone(), kind);
+    }
+    for row in remote {
+        if !local.iter().any(|s| s.date == row.date) {
+            drift.insert(row.date.clone(), DriftKind::ServerOnly);
+        }
+    }
+
+    drift.into_iter().map(|(date, kind)| DayDrift { date, kind }).collect()
+}
+
+/// Fetches the server rows for `from..=to` and compares them to `local` (which may cover more days).
+pub async fn reconcile_window(
+    client: &SupabaseClient,
+    user_config: &mut UserConfig,
+    local: &[WorkSpanData],
+    from: &str,
+    to: &str,
+) -> Result<Vec<DayDrift>> {
+    let remote = fetch_work_spans(client, user_config, from, to).await?;
+    let in_window: Vec<WorkSpanData> = local.iter()
+        .filter(|s| s.date.as_str() >= from && s.date.as_str() <= to)
+        .cloned()
+        .collect();
+
+    info!("Reconcile {} .. {}: {} local day(s), {} server row(s).", from, to, in_window.len(), remote.len());
+    Ok(compare_spans(&in_window, &remote))
+}
+
+pub fn print_drift(drift: &[DayDrift]) {
+    if drift.is_empty() {
+        info!("No drift: the server matches the local spans.");
+        return;
+    }
+    for day in drift {
+        match &day.kind {
+            DriftKind::Missing => warn!("Drift {}: missing on the server.", day.date),
+            DriftKind::Different(diffs) => warn!("Drift {}: {}", day.date, diffs.join(", ")),
+            DriftKind::ServerOnly => warn!("Drift {}: on the server but not computed locally (left as is).", day.date),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::DayType;
+
+    fn local(date: &str) -> WorkSpanData {
+        WorkSpanData {
+            date: date.to_string(),
+            total_span_minutes: 510,
+            total_span: "8h 30m".to_string(),
+            first_boot: "08:45:00".to_string(),
+            last_shutdown: "17:15:00".to_string(),
+            excluded_minutes: 0,
+            adjusted: false,
+            note: None,
+            day_type: DayType::Workday,
+            expected_minutes: 480,
+            deviation_minutes: 30,
+            week_balance_minutes: 30,
+            month_balance_minutes: 30,
+        }
+    }
+
+    /// The server row PostgREST returns after `local` was posted.
+    fn remote(date: &str) -> RemoteWorkSpan {
+        RemoteWorkSpan {
+            date: date.to_string(),
+            first_boot: "08:45:00".to_string(),
+            last_shutdown: "17:15:00".to_string(),
+            total_span_minutes: 510,
+            total_span: "8h 30m".to_string(),
+            adjusted: false,
+            note: None,
+            day_type: Some("workday".to_string()),
+            expected_minutes: Some(480),
+            deviation_minutes: Some(30),
+            week_balance_minutes: Some(30),
+            month_balance_minutes: Some(30),
+        }
+    }
+
+    #[test]
+    fn missing_different_and_server_only_days_are_reported_in_date_order() {
+        let mut changed = local("2026-10-13");
+        changed.last_shutdown = "18:15:00".to_string();
+        changed.total_span_minutes = 570;
+        changed.total_span = "9h 30m".to_string();
+        let locals = [local("2026-10-12"), changed, local("2026-10-14")];
+        let remotes = [remote("2026-10-11"), remote("2026-10-12"), remote("2026-10-13")];
+
+        let drift = compare_spans(&locals, &remotes);
+
+        let kinds: Vec<_> = drift.iter().map(|d| (d.date.as_str(), &d.kind)).collect();
+        assert_eq!(kinds, [
+            ("2026-10-11", &DriftKind::ServerOnly),
+            ("2026-10-13", &DriftKind::Different(vec![
+                "last_shutdown: 17:15:00 -> 18:15:00".to_string(),
+                "total_span_minutes: 510 -> 570".to_string(),
+                "total_span: 8h 30m -> 9h 30m".to_string(),
+            ])),
+            ("2026-10-14", &DriftKind::Missing),
+        ]);
+        assert_eq!(drift.iter().map(DayDrift::needs_post).collect::<Vec<_>>(), [false, true, true]);
+    }
+
+    #[test]
+    fn times_with_fractional_seconds_match() {
+        let mut row = remote("2026-10-13");
+        row.first_boot = "08:45:00.000000".to_string();
+        row.last_shutdown = "17:15:00.0".to_string();
+
+        assert!(same_time("08:45:00", "08:45:00.000000"));
+        assert!(!same_time("08:45:00", "08:45:00.5"));
+        assert!(compare_spans(&[local("2026-10-13")], &[row]).is_empty());
+    }
+
+    #[test]
+    fn null_calendar_and_balance_columns_of_older_rows_are_not_drift() {
+        let row = RemoteWorkSpan {
+            day_type: None,
+            expected_minutes: None,
+            deviation_minutes: None,
+            week_balance_minutes: None,
+            month_balance_minutes: None,
+            ..remote("2026-10-13")
+        };
+        assert!(differences(&local("2026-10-13"), &row).is_empty());
+
+        // A value that is present still has to match.
+        let row = RemoteWorkSpan { day_type: Some("holiday".to_string()), week_balance_minutes: Some(0), ..row };
+        assert_eq!(differences(&local("2026-10-13"), &row), ["day_type: holiday -> workday", "week_balance_minutes: 0 -> 30"]);
+    }
+}
@@ -0,0 +1,174 @@
+use chrono::NaiveDate;
+
+use crate::api::WorkSpanData;
+use crate::balance::format_signed_minutes;
+use crate::calendar::{DayType, WorkCalendar};
+use crate::sessions::SessionRecord;
+
+/// Width of the longest ASCII bar in `report --bars`.
+const BAR_WIDTH: i64 = 40;
+
+/// "8h 30m", the same format as total_span.
+pub fn format_minutes(minutes: i64) -> String {
+    format!("{}h {}m", minutes / 60, minutes % 60)
+}
+
+fn parse_day(span: &WorkSpanData) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(&span.date, "%Y-%m-%d").ok()
+}
+
+/// Spans whose date lies within `from..=to`, oldest first.
+pub fn spans_in_range(spans: Vec<WorkSpanData>, from: NaiveDate, to: NaiveDate) -> Vec<WorkSpanData> {
+    let mut in_range: Vec<WorkSpanData> = spans.into_iter()
+        .filter(|s| parse_day(s).is_some_and(|d| d >= from && d <= to))
+        .collect();
+    in_range.sort_by(|a, b| a.date.cmp(&b.date));
+    in_range
+}
+
+/// Total and day count for a group of days.
+#[derive(Debug, Default)]
+struct Bucket {
+    days: i64,
+    minutes: i64,
+}
+
+impl Bucket {
+    fn add(&mut self, minutes: i64) {
+        self.days += 1;
+        self.minutes += minutes;
+    }
+
+    fn describe(&self) -> String {
+        if self.days == 0 {
+            return "no days".to_string();
+        }
+        format!("{} over {} day(s), avg {}", format_minutes(self.minutes), self.days, format_minutes(self.minutes / self.days))
+    }
+}
+
+fn bar(minutes: i64, max_minutes: i64) -> String {
+    if max_minutes <= 0 {
+        return String::new();
+    }
+    "#".repeat((minutes * BAR_WIDTH / max_minutes) as usize)
+}
+
+/// Prints per-day rows and a summary (totals, averages, extremes, workday/weekend/holiday split).
+/// `spans` must already be limited to `from..=to`.
+pub fn print_report(spans: &[WorkSpanData], from: NaiveDate, to: NaiveDate, bars: bool, calendar: &WorkCalendar) {
+    let calendar_days = (to - from).num_days() + 1;
+    println!("Work span report {} .. {} ({} calendar day(s))\n", from, to, calendar_days);
+
+    if spans.is_empty() {
+        println!("No activity recorded in this range.");
+        return;
+    }
+
+    let max_minutes = spans.iter().map(|s| s.total_span_minutes as i64).max().unwrap_or(0);
+
+    println!("{:<10}  {:<3}  {:<7}  {:<8}  {:<8}  {:>8}  {:>8}  {:>8}  {:>9}  {:>9}",
+        "Date", "Day", "Type", "First", "Last", "Active", "Expected", "Excluded", "Deviation", "Week bal");
+    for span in spans {
+        let weekday = parse_day(span).map(|d| d.format("%a").to_string()).unwrap_or_default();
+        let mut line = format!("{:<10}  {:<3}  {:<7}  {:<8}  {:<8}  {:>8}  {:>8}  {:>8}  {:>9}  {:>9}",
+            span.date, weekday, span.day_type.as_str(), span.first_boot, span.last_shutdown,
+            format_minutes(span.total_span_minutes as i64), format_minutes(span.expected_minutes as i64),
+            format_minutes(span.excluded_minutes as i64), format_signed_minutes(span.deviation_minutes as i64),
+            format_signed_minutes(span.week_balance_minutes as i64));
+        if span.adjusted {
+            line.push_str(" *");
+        }
+        if bars {
+            line.push_str("  ");
+            line.push_str(&bar(span.total_span_minutes as i64, max_minutes));
+        }
+        if let Some(holiday) = parse_day(span).and_then(|d| calendar.holiday_name(d)) {
+            line.push_str(&format!("  [{}]", holiday));
+        }
+        if let Some(note) = &span.note {
+            line.push_str(&format!("  ({})", note));
+        }
+        println!("{}", line);
+    }
+
+    if spans.iter().any(|s| s.adjusted) {
+        println!("* manually adjusted");
+    }
+
+    // Days excluded with `adjust --exclude` are listed but do not count as active days.
+    let counted: Vec<&WorkSpanData> = spans.iter().filter(|s| !(s.adjusted && s.total_span_minutes == 0)).collect();
+
+    let mut all = Bucket::default();
+    let mut workdays = Bucket::default();
+    let mut weekends = Bucket::default();
+    let mut holidays = Bucket::default();
+    for span in &counted {
+        let minutes = span.total_span_minutes as i64;
+        all.add(minutes);
+        match span.day_type {
+            DayType::Workday => workdays.add(minutes),
+            DayType::Weekend => weekends.add(minutes),
+            DayType::Holiday => holidays.add(minutes),
+        }
+    }
+
+    // min_by/max_by keep the earliest of equal days.
+    let longest = counted.iter().rev().max_by_key(|s| s.total_span_minutes);
+    let shortest = counted.iter().min_by_key(|s| s.total_span_minutes);
+
+    println!("\nSummary");
+    println!("  Total:       {}", all.describe());
+    println!("  Idle/locked: {} excluded", format_minutes(spans.iter().map(|s| s.excluded_minutes as i64).sum()));
+    if let (Some(long), Some(short)) = (longest, shortest) {
+        println!("  Longest:     {} ({})", long.date, long.total_span);
+        println!("  Shortest:    {} ({})", short.date, short.total_span);
+    }
+    println!("  Workdays:    {}", workdays.describe());
+    println!("  Weekends:    {}", weekends.describe());
+    println!("  Holidays:    {}", holidays.describe());
+    println!("  No activity: {} day(s)", calendar_days - all.days);
+    println!("  Deviation:   {} in this range", format_signed_minutes(spans.iter().map(|s| s.deviation_minutes as i64).sum()));
+    if let Some(last) = spans.last() {
+        println!("  Balance:     {} this week, {} this month (as of {})",
+            format_signed_minutes(last.week_balance_minutes as i64), format_signed_minutes(last.month_balance_minutes as i64), last.date);
+    }
+
+    if bars {
+        println!("\nAverage per active day");
+        let buckets = [("Workdays", &workdays), ("Weekends", &weekends), ("Holidays", &holidays)];
+        let max_avg = buckets.iter().filter(|(_, b)| b.days > 0).map(|(_, b)| b.minutes / b.days).max().unwrap_or(0);
+        for (label, bucket) in buckets {
+            let avg = if bucket.days > 0 { bucket.minutes / bucket.days } else { 0 };
+            println!("  {:<8}  {:>8}  {}", label, format_minutes(avg), bar(avg, max_avg));
+        }
+    }
+}
+
+/// Prints a replay: the parsed sessions, then each computed day and whether a run on `today` would post it
+/// (on or after last_posted_date; today is posted but not finalized).
+pub fn print_replay(sessions: &[SessionRecord], spans: &[WorkSpanData], today: NaiveDate, last_posted: Option<NaiveDate>) {
+    println!("{} boot session(s):", sessions.len());
+    for session in sessions {
+        println!("  {}  ->  {}  ({})", session.start_time.format("%Y-%m-%d %H:%M:%S"), session.end_time.format("%Y-%m-%d %H:%M:%S"),
+            format_minutes((session.end_time - session.start_time).num_minutes()));
+    }
+
+    println!("\n{:<10}  {:<7}  {:<8}  {:<8}  {:>8}  {:>9}  Post",
+        "Date", "Type", "First", "Last", "Active", "Deviation");
+    for span in spans {
+        let post = match parse_day(span) {
+            Some(day) if last_posted.is_some_and(|last| day < last) => "no (already finalized)",
+            Some(day) if day >= today => "yes (today, not finalized)",
+            Some(_) => "yes",
+            None => "?",
+        };
+        println!("{:<10}  {:<7}  {:<8}  {:<8}  {:>8}  {:>9}  {}",
+            span.date, span.day_type.as_str(), span.first_boot, span.last_shutdown,
+            format_minutes(span.total_span_minutes as i64), format_signed_minutes(span.deviation_minutes as i64), post);
+    }
+    match last_posted {
+        Some(last) => println!("\nlast_posted_date: {}. Re-posts of changed or drifted days are not simulated.", last),
+        None => println!("\nNo last_posted_date: a run would post every day."),
+    }
+}
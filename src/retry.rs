@@ -0,0 +1,77 @@
+//! When to retry a failed request: capped exponential backoff with full jitter, `Retry-After`
+//! for 429/503 responses, and an overall run deadline that no wait may cross.
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+use crate::config::RetryConfig;
+
+/// The time by which a whole run (every profile of it) must be finished, from `[retry] run_deadline_seconds`.
+#[derive(Debug, Clone, Copy)]
+pub struct RunDeadline {
+    at: Instant,
+    seconds: u64,
+}
+
+impl RunDeadline {
+    /// Starts the deadline now, or returns None if the config sets none.
+    pub fn start(config: &RetryConfig) -> Option<Self> {
+        config.run_deadline().map(|deadline| RunDeadline { at: Instant::now() + deadline, seconds: deadline.as_secs() })
+    }
+
+    pub fn instant(&self) -> Instant {
+        self.at
+    }
+
+    /// The configured length of the deadline, for messages and the exit code.
+    pub fn seconds(&self) -> u64 {
+        self.seconds
+    }
+
+    fn time_left(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+}
+
+/// Parses `Retry-After` as delay-seconds or an HTTP-date. Dates in the past mean "now".
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or_default())
+}
+
+/// A uniformly distributed value in `0..=max` (std's randomly keyed hasher, no extra dependency).
+fn random_up_to(max: Duration) -> Duration {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos());
+    let fraction = hasher.finish() as f64 / u64::MAX as f64;
+    max.mul_f64(fraction)
+}
+
+/// Backoff before retry number `retry` (1-based): base * 2^(retry - 1), capped at max_delay_seconds,
+/// then, with jitter, a random delay between zero and that value ("full jitter").
+pub fn backoff_delay(config: &RetryConfig, retry: u32) -> Duration {
+    let exponential = config.base_delay_seconds.saturating_mul(1u64.checked_shl(retry.saturating_sub(1)).unwrap_or(u64::MAX));
+    let capped = Duration::from_secs(exponential.min(config.max_delay_seconds));
+    if config.jitter { random_up_to(capped) } else { capped }
+}
+
+/// The wait before the next attempt, after `attempts` failed ones. A server-sent `Retry-After` replaces
+/// the backoff; no wait may cross `deadline`. Err describes why there is no next attempt, e.g. "after 3 attempt(s)".
+pub fn next_delay(config: &RetryConfig, attempts: u32, retry_after: Option<Duration>, deadline: Option<RunDeadline>) -> Result<Duration, String> {
+    if attempts >= config.max_attempts.max(1) {
+        return Err(format!("after {} attempt(s)", attempts));
+    }
+    let delay = retry_after.unwrap_or_else(|| backoff_delay(config, attempts));
+    match deadline.map(|d| d.time_left()) {
+        Some(left) if delay >= left => Err(format!(
+            "after {} attempt(s): waiting {}s more would pass the run deadline", attempts, delay.as_secs())),
+        _ => Ok(delay),
+    }
+}
@@ -0,0 +1,309 @@
+//! Boot session sources: `last -x -F reboot` output and raw wtmp files.
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::path::Path;
+use std::process::Command;
+use tracing::info;
+
+use crate::error::CollectorError;
+
+// --- Data Structures & Constants ---
+
+/// One boot session: from boot until shutdown (or until now, if still running).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionRecord {
+    pub start_time: DateTime<Local>,
+    pub end_time: DateTime<Local>,
+}
+const LAST_DATE_FORMAT: &str = "%a %b %d %H:%M:%S %Y";
+
+// --- `last` Output ---
+
+/// Human-readable name of the boot session source (shown by `status`).
+pub const SESSION_SOURCE: &str = "last -x -F reboot (wtmp)";
+
+/// Executes the 'last' command and returns its raw output. -n 100 so only the most recent entries are read.
+pub async fn run_last_command() -> Result<String> {
+    let output = tokio::task::spawn_blocking(|| {
+        Command::new("last")
+            // CRITICAL CHANGE: Add -n 100 to fetch the last 100 entries.
+            // This ensures we have a broad history for the collector to filter.
+            .args(["-x", "-F", "reboot", "-n", "100"])
+            .output()
+    }).await
+    .map_err(|e| anyhow!("Task failed to join: {}", e))?
+    .map_err(|e| CollectorError::SourceUnavailable(format!("I/O Error executing 'last': {}", e)))?;
+
+    String::from_utf8(output.stdout).map_err(|e| anyhow!("UTF-8 Decoding Error: {}", e))
+}
+
+/// Parses raw `last -x -F reboot` output into structured session records.
+pub fn parse_last_output(stdout: &str) -> Result<Vec<SessionRecord>> {
+    parse_last_output_at(stdout, Local::now())
+}
+
+/// Like `parse_last_output`, but "still running" sessions end at `now`.
+pub fn parse_last_output_at(stdout: &str, now: DateTime<Local>) -> Result<Vec<SessionRecord>> {
+    let mut sessions = Vec::new();
+
+    // Regex to capture start time and optional end time.
+    // Group 2 (End Time) mirrors the robust date pattern of Group 1 (Start Time).
+    let re = Regex::new(r"reboot\s+system boot\s+.*?\s+([A-Z][a-z]{2}\s+[A-Z][a-z]{2}\s+\d+\s+\d{2}:\d{2}:\d{2}\s+\d{4})\s+(?:-\s+([A-Z][a-z]{2}\s+[A-Z][a-z]{2}\s+\d+\s+\d{2}:\d{2}:\d{2}\s+\d{4})|still running)")
+        .map_err(|e| anyhow!("Regex Error: {}", e))?;
+    // The inner group was fixed from [A-z]{2} to [A-Z][a-z]{2}. This will now correctly capture Dec 9th and Dec 8th.
+
+    for line in stdout.lines() {
+        if let Some(caps) = re.captures(line) {
+            let start_str = caps.get(1).map_or("", |m| m.as_str());
+            let end_str_opt = caps.get(2).map(|m| m.as_str());
+
+            // ... (Time parsing logic remains the same) ...
+            let start_dt_naive = NaiveDateTime::parse_from_str(start_str, LAST_DATE_FORMAT)
+                                .map_err(|e| anyhow!("Chrono Parse Error (Start): {}", e))?;
+
+            let start_dt_local = Local
+                .from_local_datetime(&start_dt_naive)
+                .single()
+                .ok_or_else(|| anyhow!("Date conversion failed for start time"))?;
+
+            // Determine End Time
+            let end_dt_local = match end_str_opt {
+                Some(end_str) => {
+                    let end_dt_naive = NaiveDateTime::parse_from_str(end_str, LAST_DATE_FORMAT)
+                        .map_err(|e| anyhow!("Chrono Parse Error (End): {}", e))?;
+
+                    Local
+                        .from_local_datetime(&end_dt_naive)
+                        .single()
+                        .ok_or_else(|| anyhow!("Date conversion failed for end time"))?
+                },
+                None => now, // "still running" means current time
+            };
+
+            if start_dt_local <= end_dt_local {
+                sessions.push(SessionRecord {
+                    start_time: start_dt_local,
+                    end_time: end_dt_local,
+                });
+            }
+        }
+    }
+
+    Ok(sessions)
+}
+
+/// Executes the 'last' command and parses raw output into structured session records.
+pub async fn fetch_last_logs() -> Result<Vec<SessionRecord>> {
+    info!("Executing 'last -x -F reboot' to fetch logs...");
+
+    let stdout = run_last_command().await?;
+    let sessions = parse_last_output(&stdout)
+        .map_err(|e| CollectorError::SourceUnavailable(e.to_string()))?;
+
+    info!("Successfully parsed {} raw sessions.", sessions.len());
+    Ok(sessions)
+}
+
+
+// --- Raw wtmp ---
+
+/// The system login records read by `last`.
+pub const WTMP_PATH: &str = "/var/log/wtmp";
+
+/// Size of one `struct utmp` record on Linux (glibc, 64-bit and 32-bit alike).
+const UTMP_RECORD_SIZE: usize = 384;
+const UT_TYPE_RUN_LVL: i32 = 1;
+const UT_TYPE_BOOT_TIME: i32 = 2;
+const UT_USER_OFFSET: usize = 44;
+const UT_USER_LEN: usize = 32;
+const UT_TV_SEC_OFFSET: usize = 340;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WtmpEvent {
+    Boot,
+    Shutdown,
+    Other,
+}
+
+fn read_i32(record: &[u8], offset: usize) -> i32 {
+    i32::from_ne_bytes([record[offset], record[offset + 1], record[offset + 2], record[offset + 3]])
+}
+
+/// Parses raw wtmp bytes into boot sessions, the same way `last -x reboot` pairs them:
+/// a boot lasts until the next shutdown record. A boot followed by another boot without a shutdown
+/// was a crash: it ends at the last record seen before the next boot, or at the next boot itself
+/// if there was none. The most recent boot is still running (`now`).
+///
+/// Records are paired in file order, which is the order they were written; their timestamps can
+/// go backwards when the clock is set at boot.
+pub fn parse_wtmp(bytes: &[u8], now: DateTime<Local>) -> Result<Vec<SessionRecord>> {
+    if !bytes.len().is_multiple_of(UTMP_RECORD_SIZE) {
+        return Err(anyhow!("wtmp data is {} bytes, not a multiple of the {}-byte utmp record", bytes.len(), UTMP_RECORD_SIZE));
+    }
+
+    let mut events: Vec<(DateTime<Local>, WtmpEvent)> = Vec::new();
+    for record in bytes.chunks_exact(UTMP_RECORD_SIZE) {
+        let user = &record[UT_USER_OFFSET..UT_USER_OFFSET + UT_USER_LEN];
+        let user = String::from_utf8_lossy(user.split(|b| *b == 0).next().unwrap_or_default()).to_string();
+        let event = match read_i32(record, 0) {
+            UT_TYPE_BOOT_TIME => WtmpEvent::Boot,
+            UT_TYPE_RUN_LVL if user == "shutdown" => WtmpEvent::Shutdown,
+            _ => WtmpEvent::Other,
+        };
+        // tv_sec is a 32-bit field in the on-disk format, even on 64-bit systems.
+        let Some(at) = Local.timestamp_opt(read_i32(record, UT_TV_SEC_OFFSET) as i64, 0).single() else {
+            continue;
+        };
+        events.push((at, event));
+    }
+
+    let mut sessions = Vec::new();
+    let mut boot: Option<DateTime<Local>> = None;
+    let mut last_seen: Option<DateTime<Local>> = None;
+    for (at, event) in events {
+        match event {
+            WtmpEvent::Boot => {
+                if let Some(start) = boot {
+                    // Crash: no shutdown record before this boot.
+                    let end = last_seen.unwrap_or(at);
+                    if end > start {
+                        sessions.push(SessionRecord { start_time: start, end_time: end });
+                    }
+                }
+                boot = Some(at);
+                last_seen = None;
+            },
+            WtmpEvent::Shutdown => {
+                if let Some(start) = boot.take() {
+                    sessions.push(SessionRecord { start_time: start, end_time: at });
+                }
+                last_seen = None;
+            },
+            WtmpEvent::Other => {
+                if boot.is_some() {
+                    last_seen = Some(at);
+                }
+            },
+        }
+    }
+    if let Some(start) = boot {
+        if now >= start {
+            sessions.push(SessionRecord { start_time: start, end_time: now });
+        }
+    }
+
+    sessions.sort_by_key(|s| s.start_time);
+    Ok(sessions)
+}
+
+/// Reads and parses a wtmp file (e.g. WTMP_PATH or a rotated /var/log/wtmp.1).
+pub fn read_wtmp(path: &Path, now: DateTime<Local>) -> Result<Vec<SessionRecord>> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| CollectorError::SourceUnavailable(format!("Cannot read {}: {}", path.display(), e)))?;
+    parse_wtmp(&bytes, now)
+}
+
+// --- Saved Files ---
+
+/// Reads boot sessions from a saved `last -x -F reboot` output or a raw wtmp file, as seen at `now`:
+/// sessions starting later are dropped, later ends are cut at `now`.
+/// Returns the sessions and the detected format ("wtmp" or "last output").
+pub fn read_sessions_file(path: &Path, now: DateTime<Local>) -> Result<(Vec<SessionRecord>, &'static str)> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| CollectorError::SourceUnavailable(format!("Cannot read {}: {}", path.display(), e)))?;
+
+    // utmp records are NUL-padded; `last` output is plain text.
+    let (sessions, format) = if bytes.contains(&0) {
+        (parse_wtmp(&bytes, now)?, "wtmp")
+    } else {
+        let text = String::from_utf8(bytes)
+            .map_err(|e| anyhow!("{} is neither wtmp nor text `last` output: {}", path.display(), e))?;
+        (parse_last_output_at(&text, now)?, "last output")
+    };
+
+    let mut sessions: Vec<SessionRecord> = sessions.into_iter()
+        .filter(|s| s.start_time <= now)
+        .map(|s| SessionRecord { start_time: s.start_time, end_time: s.end_time.min(now) })
+        .collect();
+    sessions.sort_by_key(|s| s.start_time);
+    Ok((sessions, format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UT_TYPE_USER_PROCESS: i32 = 7;
+
+    fn record(ut_type: i32, user: &str, at: DateTime<Local>) -> Vec<u8> {
+        let mut record = vec![0u8; UTMP_RECORD_SIZE];
+        record[..4].copy_from_slice(&ut_type.to_ne_bytes());
+        record[UT_USER_OFFSET..UT_USER_OFFSET + user.len()].copy_from_slice(user.as_bytes());
+        record[UT_TV_SEC_OFFSET..UT_TV_SEC_OFFSET + 4].copy_from_slice(&(at.timestamp() as i32).to_ne_bytes());
+        record
+    }
+
+    fn boot(at: DateTime<Local>) -> Vec<u8> {
+        record(UT_TYPE_BOOT_TIME, "reboot", at)
+    }
+
+    fn shutdown(at: DateTime<Local>) -> Vec<u8> {
+        record(UT_TYPE_RUN_LVL, "shutdown", at)
+    }
+
+    fn login(at: DateTime<Local>) -> Vec<u8> {
+        record(UT_TYPE_USER_PROCESS, "alice", at)
+    }
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, day, hour, minute, 0).unwrap()
+    }
+
+    fn session(start: DateTime<Local>, end: DateTime<Local>) -> SessionRecord {
+        SessionRecord { start_time: start, end_time: end }
+    }
+
+    #[test]
+    fn pairs_boots_with_shutdowns_crashes_and_now() {
+        let now = at(15, 12, 0);
+        let bytes = [
+            // Normal shutdown.
+            boot(at(12, 8, 0)), login(at(12, 8, 5)), shutdown(at(12, 17, 0)),
+            // Crash with trailing records: ends at the last one.
+            boot(at(13, 8, 0)), login(at(13, 9, 0)), login(at(13, 16, 30)),
+            // Crash directly followed by the next boot: ends at that boot.
+            boot(at(14, 8, 0)),
+            // Still running.
+            boot(at(14, 10, 0)), login(at(14, 10, 5)),
+        ].concat();
+
+        assert_eq!(parse_wtmp(&bytes, now).unwrap(), [
+            session(at(12, 8, 0), at(12, 17, 0)),
+            session(at(13, 8, 0), at(13, 16, 30)),
+            session(at(14, 8, 0), at(14, 10, 0)),
+            session(at(14, 10, 0), now),
+        ]);
+    }
+
+    #[test]
+    fn pairs_in_file_order_when_the_clock_goes_back() {
+        // The second boot was stamped before the RTC was corrected; sorting by time would
+        // turn it into a one-hour crash before the first boot and lose its shutdown.
+        let bytes = [
+            boot(at(12, 8, 0)), shutdown(at(12, 17, 0)),
+            boot(at(12, 7, 0)), shutdown(at(13, 18, 0)),
+        ].concat();
+
+        assert_eq!(parse_wtmp(&bytes, at(15, 12, 0)).unwrap(), [
+            session(at(12, 7, 0), at(13, 18, 0)),
+            session(at(12, 8, 0), at(12, 17, 0)),
+        ]);
+    }
+
+    #[test]
+    fn rejects_truncated_records() {
+        assert!(parse_wtmp(&[0u8; UTMP_RECORD_SIZE + 1], at(15, 12, 0)).is_err());
+    }
+}
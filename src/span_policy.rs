@@ -0,0 +1,191 @@
+//! `[span_policy]`: merging, minimum length and time-window filtering of boot sessions before daily
+//! spans are calculated, and rounding of each day's total.
+
+use chrono::{Duration, NaiveTime, Weekday};
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
+
+use crate::config::SpanPolicyConfig;
+use crate::sessions::SessionRecord;
+
+fn parse_window_time(value: &str, key: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .map_err(|e| anyhow!("Invalid {} '{}' in [span_policy] (use HH:MM): {}", key, value, e))
+}
+
+/// The weekdays whose days are kept, or None if `weekdays` is empty (every day is kept).
+pub fn allowed_weekdays(policy: &SpanPolicyConfig) -> Result<Option<HashSet<Weekday>>> {
+    if policy.weekdays.is_empty() {
+        return Ok(None);
+    }
+    policy.weekdays.iter()
+        .map(|day| day.trim().parse::<Weekday>().map_err(|_| anyhow!("Invalid weekday '{}' in weekdays (use mon..sun)", day)))
+        .collect::<Result<HashSet<_>>>()
+        .map(Some)
+}
+
+/// Validates the `[span_policy]` values. Called before any session is touched.
+pub fn validate_policy(policy: &SpanPolicyConfig) -> Result<()> {
+    if policy.merge_gap_minutes < 0 || policy.min_session_minutes < 0 || policy.round_total_minutes < 0 {
+        return Err(anyhow!("[span_policy] minute values must not be negative"));
+    }
+    allowed_weekdays(policy)?;
+    match (policy.window_start.as_deref(), policy.window_end.as_deref()) {
+        (None, None) => Ok(()),
+        (Some(start), Some(end)) => {
+            let (start, end) = (parse_window_time(start, "window_start")?, parse_window_time(end, "window_end")?);
+            if end <= start {
+                return Err(anyhow!("[span_policy] window_end must be after window_start"));
+            }
+            Ok(())
+        },
+        _ => Err(anyhow!("[span_policy] needs both window_start and window_end")),
+    }
+}
+
+/// Merges sessions whose gap is shorter than `gap_minutes`. Expects sessions sorted by start.
+fn merge_close_sessions(sessions: Vec<SessionRecord>, gap_minutes: i64) -> Vec<SessionRecord> {
+    let mut merged: Vec<SessionRecord> = Vec::new();
+    for session in sessions {
+        match merged.last_mut() {
+            Some(last) if session.start_time - last.end_time < Duration::minutes(gap_minutes) => {
+                if session.end_time > last.end_time {
+                    last.end_time = session.end_time;
+                }
+            },
+            _ => merged.push(session),
+        }
+    }
+    merged
+}
+
+/// True if the session overlaps the daily window on any of the days it touches.
+fn overlaps_window(session: &SessionRecord, start: NaiveTime, end: NaiveTime) -> bool {
+    let mut day = session.start_time.date_naive();
+    while day <= session.end_time.date_naive() {
+        let (window_start, window_end) = (day.and_time(start), day.and_time(end));
+        if session.start_time.naive_local() < window_end && session.end_time.naive_local() > window_start {
+            return true;
+        }
+        day += Duration::days(1);
+    }
+    false
+}
+
+/// Applies merging, the minimum session length and the time window, in that order.
+pub fn apply_session_policy(mut sessions: Vec<SessionRecord>, policy: &SpanPolicyConfig) -> Result<Vec<SessionRecord>> {
+    validate_policy(policy)?;
+    sessions.sort_by_key(|s| s.start_time);
+
+    if policy.merge_gap_minutes > 0 {
+        sessions = merge_close_sessions(sessions, policy.merge_gap_minutes);
+    }
+    if policy.min_session_minutes > 0 {
+        sessions.retain(|s| s.end_time - s.start_time >= Duration::minutes(policy.min_session_minutes));
+    }
+    if let (Some(start), Some(end)) = (policy.window_start.as_deref(), policy.window_end.as_deref()) {
+        let (start, end) = (parse_window_time(start, "window_start")?, parse_window_time(end, "window_end")?);
+        sessions.retain(|s| overlaps_window(s, start, end));
+    }
+    Ok(sessions)
+}
+
+/// Rounds a day's total seconds to the nearest multiple of `step_minutes` (half rounds up).
+pub fn round_total_seconds(total_seconds: i64, step_minutes: i64) -> i64 {
+    if step_minutes <= 0 {
+        return total_seconds;
+    }
+    let step = step_minutes * 60;
+    ((total_seconds + step / 2) / step) * step
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Local, TimeZone};
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, day, hour, minute, 0).unwrap()
+    }
+
+    fn session(start: DateTime<Local>, end: DateTime<Local>) -> SessionRecord {
+        SessionRecord { start_time: start, end_time: end }
+    }
+
+    fn bounds(sessions: &[SessionRecord]) -> Vec<(DateTime<Local>, DateTime<Local>)> {
+        sessions.iter().map(|s| (s.start_time, s.end_time)).collect()
+    }
+
+    fn window(start: &str, end: &str) -> SpanPolicyConfig {
+        SpanPolicyConfig { window_start: Some(start.to_string()), window_end: Some(end.to_string()), ..SpanPolicyConfig::default() }
+    }
+
+    #[test]
+    fn sessions_closer_than_the_gap_are_merged_but_not_at_the_threshold() {
+        let policy = SpanPolicyConfig { merge_gap_minutes: 10, ..SpanPolicyConfig::default() };
+        let sessions = vec![
+            session(at(13, 13, 0), at(13, 17, 0)),  // exactly 10 minutes after 12:50: kept apart
+            session(at(13, 8, 0), at(13, 12, 0)),
+            session(at(13, 12, 9), at(13, 12, 50)), // 9 minutes after 12:00: merged
+        ];
+
+        let merged = apply_session_policy(sessions, &policy).unwrap();
+
+        assert_eq!(bounds(&merged), [(at(13, 8, 0), at(13, 12, 50)), (at(13, 13, 0), at(13, 17, 0))]);
+    }
+
+    #[test]
+    fn merging_a_contained_session_keeps_the_later_end() {
+        let policy = SpanPolicyConfig { merge_gap_minutes: 5, ..SpanPolicyConfig::default() };
+        let merged = apply_session_policy(vec![
+            session(at(13, 8, 0), at(13, 17, 0)),
+            session(at(13, 9, 0), at(13, 10, 0)),
+        ], &policy).unwrap();
+
+        assert_eq!(bounds(&merged), [(at(13, 8, 0), at(13, 17, 0))]);
+    }
+
+    #[test]
+    fn minimum_length_applies_after_merging_and_keeps_the_exact_minimum() {
+        let policy = SpanPolicyConfig { merge_gap_minutes: 5, min_session_minutes: 10, ..SpanPolicyConfig::default() };
+        let kept = apply_session_policy(vec![
+            session(at(13, 3, 0), at(13, 3, 2)),    // 2-minute update reboot: dropped
+            session(at(13, 8, 0), at(13, 8, 10)),   // exactly 10 minutes: kept
+            session(at(13, 12, 0), at(13, 12, 6)),  // 6 + 6 minutes, merged to 14: kept
+            session(at(13, 12, 8), at(13, 12, 14)),
+        ], &policy).unwrap();
+
+        assert_eq!(bounds(&kept), [(at(13, 8, 0), at(13, 8, 10)), (at(13, 12, 0), at(13, 12, 14))]);
+    }
+
+    #[test]
+    fn window_keeps_sessions_that_overlap_it_on_any_day_they_touch() {
+        let kept = apply_session_policy(vec![
+            session(at(13, 5, 0), at(13, 6, 0)),    // ends exactly at window_start: outside
+            session(at(13, 22, 0), at(14, 2, 0)),   // starts exactly at window_end, crosses midnight: outside
+            session(at(13, 21, 0), at(14, 7, 0)),   // crosses midnight into the next day's window: kept
+            session(at(14, 5, 30), at(14, 6, 1)),   // one minute inside: kept
+        ], &window("06:00", "22:00")).unwrap();
+
+        assert_eq!(bounds(&kept), [(at(13, 21, 0), at(14, 7, 0)), (at(14, 5, 30), at(14, 6, 1))]);
+    }
+
+    #[test]
+    fn totals_round_to_the_nearest_step_with_half_rounding_up() {
+        assert_eq!(round_total_seconds(7 * 60 + 30, 15), 15 * 60);
+        assert_eq!(round_total_seconds(7 * 60 + 29, 15), 0);
+        assert_eq!(round_total_seconds(8 * 3600 + 22 * 60 + 30, 15), 8 * 3600 + 30 * 60);
+        assert_eq!(round_total_seconds(8 * 3600 + 2 * 60, 5), 8 * 3600);
+        assert_eq!(round_total_seconds(8 * 3600 + 2 * 60 + 31, 0), 8 * 3600 + 2 * 60 + 31, "0 = exact");
+    }
+
+    #[test]
+    fn invalid_policies_are_rejected() {
+        assert!(validate_policy(&SpanPolicyConfig::default()).is_ok());
+        assert!(validate_policy(&SpanPolicyConfig { merge_gap_minutes: -1, ..SpanPolicyConfig::default() }).is_err());
+        assert!(validate_policy(&window("18:00", "08:00")).is_err());
+        assert!(validate_policy(&window("8am", "17:00")).is_err());
+        assert!(validate_policy(&SpanPolicyConfig { window_start: Some("08:00".to_string()), ..SpanPolicyConfig::default() }).is_err());
+        assert!(validate_policy(&SpanPolicyConfig { weekdays: vec!["mon".to_string(), "funday".to_string()], ..SpanPolicyConfig::default() }).is_err());
+    }
+}
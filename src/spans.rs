@@ -0,0 +1,339 @@
+//! Daily span calculation: from boot sessions to one typed `WorkSpan` per day.
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime};
+use serde::Serialize;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use tracing::{error, info, warn};
+
+use crate::api::WorkSpanData;
+use crate::calendar::{DayType, WorkCalendar};
+use crate::config::{SpanPolicyConfig, UserConfig};
+use crate::gaps::{gap_minutes_within, Gap};
+use crate::overrides::{parse_span, DayOverride, Overrides};
+use crate::sessions::SessionRecord;
+use crate::span_policy::{allowed_weekdays, apply_session_policy, round_total_seconds};
+
+/// One day's work span with typed dates and times. `WorkSpanData` is its wire format.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WorkSpan {
+    pub date: NaiveDate,
+    pub first_boot: NaiveTime,
+    pub last_shutdown: NaiveTime,
+    /// Active minutes: the span minus idle/locked gaps (or the manual span), after rounding.
+    pub total_span_minutes: i64,
+    /// Idle/locked minutes subtracted from the span.
+    pub excluded_minutes: i64,
+    pub adjusted: bool,
+    pub note: Option<String>,
+    pub day_type: DayType,
+    pub expected_minutes: i64,
+    pub deviation_minutes: i64,
+    pub week_balance_minutes: i64,
+    pub month_balance_minutes: i64,
+}
+
+impl WorkSpan {
+    /// The total as posted in `total_span`, e.g. "8h 30m".
+    pub fn total_span(&self) -> String {
+        format!("{}h {}m", self.total_span_minutes / 60, self.total_span_minutes % 60)
+    }
+}
+
+impl From<&WorkSpan> for WorkSpanData {
+    fn from(span: &WorkSpan) -> Self {
+        WorkSpanData {
+            date: span.date.format("%Y-%m-%d").to_string(),
+            first_boot: span.first_boot.format("%H:%M:%S").to_string(),
+            last_shutdown: span.last_shutdown.format("%H:%M:%S").to_string(),
+            total_span: span.total_span(),
+            total_span_minutes: span.total_span_minutes as i32,
+            excluded_minutes: span.excluded_minutes as i32,
+            adjusted: span.adjusted,
+            note: span.note.clone(),
+            day_type: span.day_type,
+            expected_minutes: span.expected_minutes as i32,
+            deviation_minutes: span.deviation_minutes as i32,
+            week_balance_minutes: span.week_balance_minutes as i32,
+            month_balance_minutes: span.month_balance_minutes as i32,
+        }
+    }
+}
+
+impl TryFrom<&WorkSpanData> for WorkSpan {
+    type Error = anyhow::Error;
+
+    fn try_from(data: &WorkSpanData) -> Result<Self> {
+        let time = |value: &str| NaiveTime::parse_from_str(value, "%H:%M:%S")
+            .map_err(|e| anyhow!("Invalid time '{}' for {}: {}", value, data.date, e));
+        Ok(WorkSpan {
+            date: NaiveDate::parse_from_str(&data.date, "%Y-%m-%d").map_err(|e| anyhow!("Invalid date '{}': {}", data.date, e))?,
+            first_boot: time(&data.first_boot)?,
+            last_shutdown: time(&data.last_shutdown)?,
+            total_span_minutes: data.total_span_minutes as i64,
+            excluded_minutes: data.excluded_minutes as i64,
+            adjusted: data.adjusted,
+            note: data.note.clone(),
+            day_type: data.day_type,
+            expected_minutes: data.expected_minutes as i64,
+            deviation_minutes: data.deviation_minutes as i64,
+            week_balance_minutes: data.week_balance_minutes as i64,
+            month_balance_minutes: data.month_balance_minutes as i64,
+        })
+    }
+}
+
+/// Earliest start and latest end of a day's sessions.
+type DayBounds = (DateTime<Local>, DateTime<Local>);
+
+/// Calculates the Earliest Boot/Latest Shutdown span for each calendar day.
+/// Gaps (idle or screen-locked intervals) inside a day's span are subtracted from its active time.
+/// Manual overrides (AvadhiOverrides.toml) replace or zero out a day's span and mark it as adjusted.
+/// Each day is tagged with its day type and expected minutes from the work calendar.
+/// The [span_policy] filters sessions first and rounds each computed total.
+pub fn calculate_work_spans(
+    sessions: Vec<SessionRecord>,
+    gaps: &[Gap],
+    overrides: &Overrides,
+    calendar: &WorkCalendar,
+    policy: &SpanPolicyConfig,
+) -> Result<Vec<WorkSpan>> {
+    let sessions = apply_session_policy(sessions, policy)?;
+
+    // Key: NaiveDate | Value: (min_start, max_end), or None for a day that only has an overridden span
+    let mut daily_data: HashMap<NaiveDate, Option<DayBounds>> = HashMap::new();
+
+    for session in sessions {
+        let date = session.start_time.date_naive();
+        let end_date = session.end_time.date_naive();
+
+        // 1. Update Span for Start Date
+        let entry = daily_data.entry(date).or_default()
+            .get_or_insert((session.start_time, session.end_time));
+
+        if session.start_time < entry.0 {
+            entry.0 = session.start_time; // Earliest Start
+        }
+        if session.end_time > entry.1 {
+            entry.1 = session.end_time; // Latest End
+        }
+
+        // 2. If the session crosses midnight, update the span for the end date as well.
+        if date != end_date {
+            let end_entry = daily_data.entry(end_date).or_default()
+                .get_or_insert((session.end_time, session.end_time));
+
+            if session.end_time > end_entry.1 {
+                end_entry.1 = session.end_time;
+            }
+        }
+    }
+
+    // Overridden spans also create days that have no sessions (e.g. work on another machine).
+    for (date, day) in &overrides.days {
+        if let (Ok(date), Some(_)) = (NaiveDate::parse_from_str(date, "%Y-%m-%d"), day.span.as_ref()) {
+            daily_data.entry(date).or_default();
+        }
+    }
+
+    // Days on weekdays outside [span_policy] weekdays (e.g. billed to another profile) are not reported at all.
+    if let Some(weekdays) = allowed_weekdays(policy)? {
+        daily_data.retain(|date, _| weekdays.contains(&date.weekday()));
+    }
+
+    // --- Final Calculation ---
+    let mut results = Vec::new();
+
+    for (date, bounds) in daily_data {
+        let day_override = overrides.get(date);
+
+        let (first_boot_time, last_shutdown_time, total_seconds, excluded_minutes) = match day_override {
+            Some(o) if o.exclude => (NaiveTime::MIN, NaiveTime::MIN, 0, 0),
+            Some(DayOverride { span: Some(span), .. }) => {
+                let (start, end) = parse_span(span)?;
+                (start, end, (end - start).num_seconds(), 0)
+            },
+            _ => {
+                let Some((first_boot, last_shutdown)) = bounds.filter(|(start, end)| end > start) else {
+                    continue;
+                };
+                let span: Duration = last_shutdown - first_boot;
+                let excluded_minutes = gap_minutes_within(gaps, first_boot, last_shutdown);
+                let total_seconds = (span - Duration::minutes(excluded_minutes)).num_seconds().max(0);
+                (first_boot.time(), last_shutdown.time(), round_total_seconds(total_seconds, policy.round_total_minutes), excluded_minutes)
+            }
+        };
+        let (day_type, expected_minutes) = calendar.classify(date);
+
+        results.push(WorkSpan {
+            date,
+            first_boot: first_boot_time,
+            last_shutdown: last_shutdown_time,
+            total_span_minutes: total_seconds / 60,
+            excluded_minutes,
+            adjusted: day_override.is_some_and(|o| o.exclude || o.span.is_some()),
+            note: day_override.and_then(|o| o.note.clone()),
+            day_type,
+            expected_minutes: expected_minutes as i64,
+            // Filled in by apply_balance, which needs every day.
+            deviation_minutes: 0,
+            week_balance_minutes: 0,
+            month_balance_minutes: 0,
+        });
+    }
+
+    results.sort_by_key(|s| s.date);
+    Ok(results)
+}
+
+/// `calculate_work_spans` in the wire format posted to the work span table.
+pub fn calculate_spans(
+    sessions: Vec<SessionRecord>,
+    gaps: &[Gap],
+    overrides: &Overrides,
+    calendar: &WorkCalendar,
+    policy: &SpanPolicyConfig,
+) -> Result<Vec<WorkSpanData>> {
+    Ok(calculate_work_spans(sessions, gaps, overrides, calendar, policy)?
+        .iter()
+        .map(WorkSpanData::from)
+        .collect())
+}
+
+
+/// Filters the calculated WorkSpanData to only include entries newer than or equal to
+/// the date stored in the user configuration (last_posted_date).
+pub fn filter_data_for_posting(
+    mut historical_data: Vec<WorkSpanData>,
+    user_config: &UserConfig,
+) -> Vec<WorkSpanData> {
+    let last_posted_date = match user_config.last_posted_date.as_ref() {
+        Some(date_str) => NaiveDate::parse_from_str(date_str, "%Y-%m-%d").ok(),
+        None => None,
+    };
+
+    if last_posted_date.is_none() {
+        warn!("No last posted date found in config. Posting all calculated history.");
+        return historical_data;
+    }
+
+    let filter_date = last_posted_date.unwrap();
+
+    // Use >= instead of >
+    // This ensures we always re-post the last recorded day (the "Yesterday" data)
+    // to finalize its shutdown time.
+    historical_data.retain(|data| {
+        if let Ok(data_date) = NaiveDate::parse_from_str(&data.date, "%Y-%m-%d") {
+            // Keep data if date is equal to OR greater than the last posted date
+            data_date >= filter_date
+        } else {
+            // Log an error if date parsing fails for an entry, but keep it just in case
+            error!("Failed to parse date in historical data: {}", data.date);
+            true
+        }
+    });
+
+    info!("Filtering data to start from or after: {}.", filter_date);
+
+
+    // Sort by date to ensure we post chronologically (essential for configuration update logic)
+    historical_data.sort_by(|a, b| a.date.cmp(&b.date));
+
+    historical_data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::config::ScheduleConfig;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, day, hour, minute, 0).unwrap()
+    }
+
+    fn session(start: DateTime<Local>, end: DateTime<Local>) -> SessionRecord {
+        SessionRecord { start_time: start, end_time: end }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, day).unwrap()
+    }
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn spans(sessions: Vec<SessionRecord>, gaps: &[Gap], overrides: &Overrides, policy: &SpanPolicyConfig) -> Vec<WorkSpan> {
+        let calendar = WorkCalendar::from_config(&ScheduleConfig::default()).unwrap();
+        calculate_work_spans(sessions, gaps, overrides, &calendar, policy).unwrap()
+    }
+
+    fn overrides(days: &[(&str, DayOverride)]) -> Overrides {
+        Overrides { days: days.iter().map(|(date, day)| (date.to_string(), day.clone())).collect() }
+    }
+
+    #[test]
+    fn session_across_midnight_ends_the_first_day_and_starts_the_next() {
+        let sessions = vec![session(at(12, 20, 0), at(13, 2, 0)), session(at(13, 8, 0), at(13, 10, 0))];
+
+        let days = spans(sessions.clone(), &[], &Overrides::default(), &SpanPolicyConfig::default());
+
+        let summary: Vec<_> = days.iter().map(|d| (d.date, d.first_boot, d.last_shutdown, d.total_span_minutes)).collect();
+        assert_eq!(summary, [(date(12), time(20, 0), time(2, 0), 6 * 60), (date(13), time(2, 0), time(10, 0), 8 * 60)]);
+
+        // Without a later session the next day only has the session's end and no span of its own.
+        let days = spans(sessions[..1].to_vec(), &[], &Overrides::default(), &SpanPolicyConfig::default());
+        assert_eq!(days.iter().map(|d| d.date).collect::<Vec<_>>(), [date(12)]);
+    }
+
+    #[test]
+    fn gaps_inside_the_span_are_subtracted() {
+        let sessions = vec![session(at(13, 8, 0), at(13, 12, 0)), session(at(13, 13, 0), at(13, 17, 0))];
+        // Lunch (12:00-13:00) has no session but lies within the day's span; the gap before 08:00 does not.
+        let gaps = [Gap { start: at(13, 10, 0), end: at(13, 10, 45) }, Gap { start: at(13, 6, 0), end: at(13, 7, 30) }];
+
+        let days = spans(sessions, &gaps, &Overrides::default(), &SpanPolicyConfig::default());
+
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].excluded_minutes, 45);
+        assert_eq!(days[0].total_span_minutes, 9 * 60 - 45);
+        assert_eq!(days[0].total_span(), "8h 15m");
+        assert!(!days[0].adjusted);
+    }
+
+    #[test]
+    fn overrides_exclude_or_replace_days_and_add_days_without_sessions() {
+        let sessions = vec![session(at(12, 8, 0), at(12, 17, 0)), session(at(13, 8, 0), at(13, 17, 0))];
+        let overrides = overrides(&[
+            ("2026-10-12", DayOverride { exclude: true, ..DayOverride::default() }),
+            ("2026-10-13", DayOverride { span: Some("09:00-12:30".to_string()), note: Some("dentist".to_string()), ..DayOverride::default() }),
+            ("2026-10-14", DayOverride { span: Some("10:00-14:00".to_string()), ..DayOverride::default() }),
+            ("2026-10-15", DayOverride { note: Some("no session, no span".to_string()), ..DayOverride::default() }),
+        ]);
+        let gaps = [Gap { start: at(13, 10, 0), end: at(13, 11, 0) }];
+
+        let days = spans(sessions, &gaps, &overrides, &SpanPolicyConfig::default());
+
+        let summary: Vec<_> = days.iter().map(|d| (d.date, d.first_boot, d.last_shutdown, d.total_span_minutes, d.adjusted)).collect();
+        assert_eq!(summary, [
+            (date(12), NaiveTime::MIN, NaiveTime::MIN, 0, true),
+            (date(13), time(9, 0), time(12, 30), 210, true),
+            (date(14), time(10, 0), time(14, 0), 240, true),
+        ]);
+        assert_eq!(days[1].note.as_deref(), Some("dentist"));
+        assert_eq!(days[1].excluded_minutes, 0, "gaps are not subtracted from a manual span");
+    }
+
+    #[test]
+    fn days_outside_the_weekday_filter_are_dropped() {
+        // Oct 16, 2026 is a Friday and Oct 17 a Saturday.
+        let sessions = vec![session(at(16, 8, 0), at(16, 12, 0)), session(at(17, 9, 0), at(17, 11, 0))];
+        let policy = SpanPolicyConfig { weekdays: vec!["mon".into(), "tue".into(), "wed".into(), "thu".into(), "fri".into()], ..SpanPolicyConfig::default() };
+        let overrides = overrides(&[("2026-10-18", DayOverride { span: Some("10:00-11:00".to_string()), ..DayOverride::default() })]);
+
+        let days = spans(sessions, &[], &overrides, &policy);
+
+        assert_eq!(days.iter().map(|d| d.date).collect::<Vec<_>>(), [date(16)]);
+    }
+}
@@ -0,0 +1,72 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Local};
+use std::fs;
+use std::path::Path;
+use tracing::{error, warn};
+
+use crate::config::profile_path;
+
+pub const STATE_PATH: &str = "AvadhiState.toml";
+
+/// AvadhiState.toml, or AvadhiState.<profile>.toml for a named profile.
+pub fn state_path(profile: Option<&str>) -> String {
+    profile_path(STATE_PATH, profile)
+}
+
+/// Bookkeeping about collector runs, kept separate from the credentials in AvadhiConfig.toml.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct CollectorState {
+    pub last_run_at: Option<DateTime<Local>>,
+    /// "success" or "failure".
+    pub last_run_outcome: Option<String>,
+    /// What started the run: "timer", "manual" or "daemon".
+    pub last_run_trigger: Option<String>,
+    pub last_run_error: Option<String>,
+}
+
+pub fn load_state(profile: Option<&str>) -> CollectorState {
+    let path = state_path(profile);
+    if !Path::new(&path).exists() {
+        return CollectorState::default();
+    }
+    match fs::read_to_string(&path) {
+        Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+            warn!("Error parsing {}: {}", path, e);
+            CollectorState::default()
+        }),
+        Err(e) => {
+            warn!("Error reading {}: {}", path, e);
+            CollectorState::default()
+        }
+    }
+}
+
+pub fn save_state(state: &CollectorState, profile: Option<&str>) {
+    let path = state_path(profile);
+    match toml::to_string_pretty(state) {
+        Ok(contents) => {
+            if let Err(e) = fs::write(&path, contents) {
+                error!("Error writing to {}: {}", path, e);
+            }
+        }
+        Err(e) => error!("Error serializing collector state: {}", e),
+    }
+}
+
+/// Records the outcome of a collector run for `profile`.
+pub fn record_run(trigger: &str, started_at: DateTime<Local>, result: &anyhow::Result<()>, profile: Option<&str>) {
+    let mut state = load_state(profile);
+    state.last_run_at = Some(started_at);
+    state.last_run_trigger = Some(trigger.to_string());
+    match result {
+        Ok(_) => {
+            state.last_run_outcome = Some("success".to_string());
+            state.last_run_error = None;
+        },
+        Err(e) => {
+            state.last_run_outcome = Some("failure".to_string());
+            state.last_run_error = Some(e.to_string());
+        }
+    }
+    save_state(&state, profile);
+}
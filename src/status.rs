@@ -0,0 +1,264 @@
+use serde::Serialize;
+use chrono::{DateTime, Local};
+use std::path::Path;
+
+use crate::config::{profile_path, read_config_file, AdminConfig, UserConfig, ADMIN_CONFIG_PATH, USER_CONFIG_PATH};
+use crate::idle::{load_idle_log, IDLE_LOG_PATH};
+use crate::jwt;
+use crate::locks::{load_lock_log, LOCK_LOG_PATH};
+use crate::collector::{changed_posted_days, compute_spans, days_to_post, server_drifted_days, SpanMode};
+use crate::state::{load_state, state_path};
+use crate::sessions::SESSION_SOURCE;
+use crate::store::{history_path, HistoryStore};
+
+// --- Report Structures ---
+
+#[derive(Debug, Serialize)]
+pub struct ConfigFileStatus {
+    pub path: String,
+    pub exists: bool,
+    pub loaded: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenStatus {
+    pub user_id: Option<String>,
+    pub access_token_present: bool,
+    pub refresh_token_present: bool,
+    pub access_token_expires_at: Option<DateTime<Local>>,
+    pub access_token_expired: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token_error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LastRunStatus {
+    pub at: Option<DateTime<Local>>,
+    pub outcome: Option<String>,
+    pub trigger: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SourceStatus {
+    pub name: String,
+    pub sessions: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GapSourceStatus {
+    pub enabled: bool,
+    /// Most recent idle source ("logind"/"interrupts"); None for locks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub last_sample: Option<DateTime<Local>>,
+    pub recorded_entries: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatusReport {
+    /// The profile from [profiles] the credentials, last run and pending days belong to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    pub config_files: Vec<ConfigFileStatus>,
+    pub tokens: TokenStatus,
+    pub last_posted_date: Option<String>,
+    pub last_run: LastRunStatus,
+    /// Days that would be (re-)posted by the next run.
+    pub pending_days: Vec<String>,
+    /// Why the server could not be compared for drift ([reconcile] enabled); `pending_days` then
+    /// only has the locally known days.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reconcile_error: Option<String>,
+    pub session_source: SourceStatus,
+    pub idle: GapSourceStatus,
+    pub lock: GapSourceStatus,
+}
+
+// --- Collection ---
+
+fn config_file_status<T: serde::de::DeserializeOwned>(path: &str) -> (ConfigFileStatus, Option<T>) {
+    let exists = Path::new(path).exists();
+    let display_path = std::fs::canonicalize(path)
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| path.to_string());
+
+    match read_config_file::<T>(path) {
+        Ok(value) => (ConfigFileStatus { path: display_path, exists, loaded: true, error: None }, Some(value)),
+        Err(e) => (ConfigFileStatus { path: display_path, exists, loaded: false, error: Some(e) }, None),
+    }
+}
+
+fn token_status(user_config: &UserConfig) -> TokenStatus {
+    let mut status = TokenStatus {
+        user_id: user_config.user_id.clone(),
+        access_token_present: user_config.access_token.is_some(),
+        refresh_token_present: user_config.refresh_token.is_some(),
+        access_token_expires_at: None,
+        access_token_expired: None,
+        access_token_error: None,
+    };
+
+    if let Some(token) = user_config.access_token.as_deref() {
+        match jwt::expiry(token) {
+            Ok(exp) => {
+                status.access_token_expires_at = Some(exp);
+                status.access_token_expired = Some(exp <= Local::now());
+            },
+            Err(e) => status.access_token_error = Some(e.to_string()),
+        }
+    }
+    status
+}
+
+/// Gathers the collector's health without side effects: nothing is sampled, posted or saved.
+/// `profile` selects the credentials and span policy of a [profiles] entry (checked by the caller).
+pub async fn collect_status(profile: Option<&str>) -> StatusReport {
+    let (admin_file, admin_config) = config_file_status::<AdminConfig>(ADMIN_CONFIG_PATH);
+    let (user_file, user_config) = config_file_status::<UserConfig>(&profile_path(USER_CONFIG_PATH, profile));
+    let (state_file, _) = config_file_status::<toml::Value>(&state_path(profile));
+    let (idle_file, _) = config_file_status::<toml::Value>(IDLE_LOG_PATH);
+    let (lock_file, _) = config_file_status::<toml::Value>(LOCK_LOG_PATH);
+
+    let admin_config = admin_config.unwrap_or_default();
+    let admin_config = admin_config.for_profile(profile).unwrap_or(admin_config);
+    let user_config = user_config.unwrap_or_default();
+    let state = load_state(profile);
+    let idle_log = load_idle_log();
+    let lock_log = load_lock_log();
+    let now = Local::now();
+
+    // Boot sessions and the days the next run would post, chosen the way the run chooses them.
+    let mut session_source = SourceStatus { name: SESSION_SOURCE.to_string(), sessions: None, error: None };
+    let mut pending_days = Vec::new();
+    let mut reconcile_error = None;
+
+    match compute_spans(&admin_config, profile, SpanMode::Preview, now).await {
+        Ok((reported, spans)) => {
+            session_source.sessions = Some(reported);
+            let today = now.date_naive();
+
+            let changed = if Path::new(&history_path(profile)).exists() {
+                HistoryStore::open_profile(profile)
+                    .map(|store| changed_posted_days(&store, &spans, &user_config, today))
+                    .unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            let drifted = if admin_config.reconcile.enabled {
+                server_drifted_days(&admin_config, &user_config, &spans, today).await.unwrap_or_else(|e| {
+                    reconcile_error = Some(e.to_string());
+                    Vec::new()
+                })
+            } else {
+                Vec::new()
+            };
+
+            pending_days = days_to_post(spans, &user_config, changed, drifted).into_iter()
+                .map(|s| s.date)
+                .collect();
+        },
+        Err(e) => session_source.error = Some(e.to_string()),
+    }
+
+    StatusReport {
+        profile: profile.map(str::to_string),
+        config_files: vec![admin_file, user_file, state_file, idle_file, lock_file],
+        tokens: token_status(&user_config),
+        last_posted_date: user_config.last_posted_date.clone(),
+        last_run: LastRunStatus {
+            at: state.last_run_at,
+            outcome: state.last_run_outcome,
+            trigger: state.last_run_trigger,
+            error: state.last_run_error,
+        },
+        pending_days,
+        reconcile_error,
+        session_source,
+        idle: GapSourceStatus {
+            enabled: admin_config.idle.enabled,
+            source: idle_log.last_source.clone(),
+            last_sample: idle_log.last_sample,
+            recorded_entries: idle_log.periods.len(),
+        },
+        lock: GapSourceStatus {
+            enabled: admin_config.lock.enabled,
+            source: None,
+            last_sample: lock_log.events.last().map(|e| e.at),
+            recorded_entries: lock_log.events.len(),
+        },
+    }
+}
+
+// --- Output ---
+
+fn or_dash<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string())
+}
+
+pub fn print_status(report: &StatusReport) {
+    println!("Avadhi Collector status");
+    println!("=======================");
+    if let Some(profile) = &report.profile {
+        println!("Profile: {}", profile);
+    }
+
+    println!("\nConfiguration files:");
+    for file in &report.config_files {
+        let mark = if file.loaded { "loaded" } else if file.exists { "INVALID" } else { "missing" };
+        println!("  [{:<7}] {}", mark, file.path);
+        if let (true, Some(error)) = (file.exists, file.error.as_ref()) {
+            println!("            {}", error);
+        }
+    }
+
+    let tokens = &report.tokens;
+    println!("\nCredentials:");
+    println!("  User ID:        {}", or_dash(tokens.user_id.as_ref()));
+    println!("  Access token:   {}", if tokens.access_token_present { "present" } else { "MISSING" });
+    if let Some(exp) = tokens.access_token_expires_at {
+        let state = if tokens.access_token_expired == Some(true) { "EXPIRED (will be refreshed on next post)" } else { "valid" };
+        println!("  Token expiry:   {} ({})", exp.format("%Y-%m-%d %H:%M:%S"), state);
+    }
+    if let Some(error) = &tokens.access_token_error {
+        println!("  Token expiry:   unknown ({})", error);
+    }
+    println!("  Refresh token:  {}", if tokens.refresh_token_present { "present" } else { "MISSING" });
+
+    println!("\nPosting:");
+    println!("  Last posted date: {}", or_dash(report.last_posted_date.as_ref()));
+    println!("  Pending days:     {}", if report.pending_days.is_empty() { "none".to_string() } else { report.pending_days.join(", ") });
+    if let Some(error) = &report.reconcile_error {
+        println!("                    (server not compared: {})", error);
+    }
+
+    let run = &report.last_run;
+    println!("\nLast run:");
+    println!("  At:       {}", or_dash(run.at.map(|t| t.format("%Y-%m-%d %H:%M:%S"))));
+    println!("  Trigger:  {}", or_dash(run.trigger.as_ref()));
+    println!("  Outcome:  {}", or_dash(run.outcome.as_ref()));
+    if let Some(error) = &run.error {
+        println!("  Error:    {}", error);
+    }
+
+    println!("\nSession sources:");
+    let source = &report.session_source;
+    match (&source.sessions, &source.error) {
+        (Some(n), _) => println!("  Boot sessions: {} ({} sessions)", source.name, n),
+        (None, Some(e)) => println!("  Boot sessions: {} (ERROR: {})", source.name, e),
+        _ => println!("  Boot sessions: {}", source.name),
+    }
+    println!("  Idle:          {} via {}, {} recorded period(s), last sample {}",
+        if report.idle.enabled { "enabled" } else { "disabled" },
+        or_dash(report.idle.source.as_ref()),
+        report.idle.recorded_entries,
+        or_dash(report.idle.last_sample.map(|t| t.format("%Y-%m-%d %H:%M"))));
+    println!("  Screen lock:   {}, {} recorded event(s), last event {}",
+        if report.lock.enabled { "enabled" } else { "disabled" },
+        report.lock.recorded_entries,
+        or_dash(report.lock.last_sample.map(|t| t.format("%Y-%m-%d %H:%M"))));
+}
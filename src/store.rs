@@ -0,0 +1,480 @@
+use chrono::{DateTime, Local};
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde_json::Value;
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+
+use crate::api::WorkSpanData;
+use crate::config::profile_path;
+use crate::sessions::SessionRecord;
+
+pub const HISTORY_DB_PATH: &str = "AvadhiHistory.sqlite";
+
+/// AvadhiHistory.sqlite, or AvadhiHistory.<profile>.sqlite for a named profile.
+pub fn history_path(profile: Option<&str>) -> String {
+    profile_path(HISTORY_DB_PATH, profile)
+}
+
+const SCHEMA: &str = "
+-- One row per invocation. The remaining columns (RUN_COLUMNS) are added by migrate().
+CREATE TABLE IF NOT EXISTS runs (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at  TEXT NOT NULL
+);
+
+-- Raw boot sessions as seen by each run. 'still running' sessions are stored with the run's 'now'.
+CREATE TABLE IF NOT EXISTS sessions (
+    run_id      INTEGER NOT NULL REFERENCES runs(id),
+    start_time  TEXT NOT NULL,
+    end_time    TEXT NOT NULL,
+    PRIMARY KEY (run_id, start_time)
+);
+CREATE INDEX IF NOT EXISTS idx_sessions_start ON sessions (start_time);
+
+-- Latest computed span per day.
+CREATE TABLE IF NOT EXISTS spans (
+    date                TEXT PRIMARY KEY,
+    first_boot          TEXT NOT NULL,
+    last_shutdown       TEXT NOT NULL,
+    total_span          TEXT NOT NULL,
+    total_span_minutes  INTEGER NOT NULL,
+    excluded_minutes    INTEGER NOT NULL,
+    computed_at         TEXT NOT NULL,
+    run_id              INTEGER NOT NULL REFERENCES runs(id)
+);
+
+-- Every post attempt: the exact payload, when, and the HTTP status (NULL if no response).
+CREATE TABLE IF NOT EXISTS posts (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_id       INTEGER NOT NULL REFERENCES runs(id),
+    date         TEXT NOT NULL,
+    posted_at    TEXT NOT NULL,
+    http_status  INTEGER,
+    success      INTEGER NOT NULL,
+    error        TEXT,
+    payload      TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_posts_date ON posts (date);
+
+-- Access token refreshes during a run. Failed refreshes keep the error.
+CREATE TABLE IF NOT EXISTS token_refreshes (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_id      INTEGER NOT NULL REFERENCES runs(id),
+    at          TEXT NOT NULL,
+    success     INTEGER NOT NULL,
+    error       TEXT
+);
+";
+
+/// Columns added to `runs` after the first release: (name, type). Added on open if missing.
+const RUN_COLUMNS: [(&str, &str); 8] = [
+    ("kind", "TEXT"),
+    ("trigger", "TEXT"),
+    ("finished_at", "TEXT"),
+    ("session_source", "TEXT"),
+    ("session_count", "INTEGER"),
+    ("outcome", "TEXT"),
+    ("error", "TEXT"),
+    ("last_posted_date", "TEXT"),
+];
+
+/// One invocation as recorded in `runs`, with its post counts.
+#[derive(Debug, Clone)]
+pub struct RunRecord {
+    pub id: i64,
+    pub started_at: DateTime<Local>,
+    pub finished_at: Option<DateTime<Local>>,
+    /// "run", "backfill" or "reconcile". None for runs recorded before this was tracked.
+    pub kind: Option<String>,
+    pub trigger: Option<String>,
+    pub session_source: Option<String>,
+    pub session_count: Option<i64>,
+    /// "success" or "failure"; None if the run never finished (crash, kill).
+    pub outcome: Option<String>,
+    pub error: Option<String>,
+    /// last_posted_date after the run.
+    pub last_posted_date: Option<String>,
+    pub days_posted: i64,
+    pub days_failed: i64,
+}
+
+/// One post attempt from `posts`.
+#[derive(Debug, Clone)]
+pub struct PostRecord {
+    pub run_id: i64,
+    pub date: String,
+    pub posted_at: DateTime<Local>,
+    pub http_status: Option<u16>,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// One token refresh from `token_refreshes`.
+#[derive(Debug, Clone)]
+pub struct RefreshRecord {
+    pub at: DateTime<Local>,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// The latest computed span of a day from `spans`.
+#[derive(Debug, Clone)]
+pub struct StoredSpan {
+    pub first_boot: String,
+    pub last_shutdown: String,
+    pub total_span: String,
+    pub excluded_minutes: i64,
+    pub computed_at: DateTime<Local>,
+    pub run_id: i64,
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Local>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Local))
+        .map_err(|e| anyhow!("Invalid timestamp '{}' in local history: {}", value, e))
+}
+
+/// Reads an RFC 3339 column inside a row mapper.
+fn time_column(row: &Row, idx: usize) -> rusqlite::Result<DateTime<Local>> {
+    let value: String = row.get(idx)?;
+    DateTime::parse_from_rfc3339(&value)
+        .map(|t| t.with_timezone(&Local))
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(idx, Type::Text, Box::new(e)))
+}
+
+/// Durable local history of sessions, computed spans and post attempts (SQLite).
+pub struct HistoryStore {
+    conn: Connection,
+}
+
+impl HistoryStore {
+    pub fn open(path: &str) -> Result<Self> {
+        let conn = Connection::open(path)
+            .map_err(|e| anyhow!("Cannot open local history {}: {}", path, e))?;
+        conn.execute_batch(SCHEMA)?;
+        let store = HistoryStore { conn };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    /// Adds the `runs` columns that databases created by older versions lack.
+    fn migrate(&self) -> Result<()> {
+        let existing: Vec<String> = self.conn.prepare("SELECT name FROM pragma_table_info('runs')")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        for (name, kind) in RUN_COLUMNS {
+            if !existing.iter().any(|c| c == name) {
+                self.conn.execute_batch(&format!("ALTER TABLE runs ADD COLUMN {} {}", name, kind))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens the history of `profile`; None is the single-account AvadhiHistory.sqlite.
+    pub fn open_profile(profile: Option<&str>) -> Result<Self> {
+        Self::open(&history_path(profile))
+    }
+
+    /// Registers a run. `kind` is "run", "backfill" or "reconcile"; `trigger` is "timer", "manual" or "daemon".
+    pub fn begin_run(&self, started_at: DateTime<Local>, kind: &str, trigger: &str) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO runs (started_at, kind, trigger) VALUES (?1, ?2, ?3)",
+            params![started_at.to_rfc3339(), kind, trigger],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Records where the run's boot sessions came from and how many were parsed.
+    pub fn record_session_source(&self, run_id: i64, source: &str, count: usize) -> Result<()> {
+        self.conn.execute(
+            "UPDATE runs SET session_source = ?2, session_count = ?3 WHERE id = ?1",
+            params![run_id, source, count as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn record_token_refresh(&self, run_id: i64, error: Option<&str>) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO token_refreshes (run_id, at, success, error) VALUES (?1, ?2, ?3, ?4)",
+            params![run_id, Local::now().to_rfc3339(), error.is_none(), error],
+        )?;
+        Ok(())
+    }
+
+    /// Records how the run ended and the last_posted_date it left behind.
+    pub fn finish_run(&self, run_id: i64, error: Option<&str>, last_posted_date: Option<&str>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE runs SET finished_at = ?2, outcome = ?3, error = ?4, last_posted_date = ?5 WHERE id = ?1",
+            params![run_id, Local::now().to_rfc3339(), if error.is_none() { "success" } else { "failure" }, error, last_posted_date],
+        )?;
+        Ok(())
+    }
+
+    pub fn record_sessions(&mut self, run_id: i64, sessions: &[SessionRecord]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        for session in sessions {
+            tx.execute(
+                "INSERT OR REPLACE INTO sessions (run_id, start_time, end_time) VALUES (?1, ?2, ?3)",
+                params![run_id, session.start_time.to_rfc3339(), session.end_time.to_rfc3339()],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Every session ever seen, one per boot: the latest known end time wins
+    /// (a session stored while "still running" is superseded once it has ended).
+    /// Timestamps carry the UTC offset of the run that stored them, so they are compared
+    /// after parsing; comparing the strings would misorder them around a DST change.
+    pub fn historical_sessions(&self) -> Result<Vec<SessionRecord>> {
+        let mut stmt = self.conn.prepare("SELECT start_time, end_time FROM sessions")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut sessions = Vec::new();
+        for row in rows {
+            let (start, end) = row?;
+            sessions.push(SessionRecord { start_time: parse_time(&start)?, end_time: parse_time(&end)? });
+        }
+        Ok(merge_sessions(Vec::new(), sessions))
+    }
+
+    pub fn record_spans(&mut self, run_id: i64, spans: &[WorkSpanData], computed_at: DateTime<Local>) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        for span in spans {
+            tx.execute(
+                "INSERT OR REPLACE INTO spans (date, first_boot, last_shutdown, total_span, total_span_minutes, excluded_minutes, computed_at, run_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![span.date, span.first_boot, span.last_shutdown, span.total_span,
+                    span.total_span_minutes, span.excluded_minutes, computed_at.to_rfc3339(), run_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn record_post(&self, run_id: i64, date: &str, payload: &Value, http_status: Option<u16>, error: Option<&str>) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO posts (run_id, date, posted_at, http_status, success, error, payload) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![run_id, date, Local::now().to_rfc3339(), http_status, error.is_none(), error, payload.to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// The payload of the most recent successful post for `date`, if any.
+    pub fn last_successful_payload(&self, date: &str) -> Result<Option<Value>> {
+        let payload: Option<String> = self.conn.query_row(
+            "SELECT payload FROM posts WHERE date = ?1 AND success = 1 ORDER BY id DESC LIMIT 1",
+            params![date],
+            |row| row.get(0),
+        ).optional()?;
+
+        payload.map(|p| serde_json::from_str(&p).map_err(|e| anyhow!("Corrupt payload for {} in local history: {}", date, e)))
+            .transpose()
+    }
+
+    fn query_runs(&self, filter: &str, value: i64) -> Result<Vec<RunRecord>> {
+        let sql = format!(
+            "SELECT r.id, r.started_at, r.finished_at, r.kind, r.trigger, r.session_source, r.session_count,
+                    r.outcome, r.error, r.last_posted_date,
+                    (SELECT COUNT(*) FROM posts p WHERE p.run_id = r.id AND p.success = 1),
+                    (SELECT COUNT(*) FROM posts p WHERE p.run_id = r.id AND p.success = 0)
+             FROM runs r {}", filter);
+        let mut stmt = self.conn.prepare(&sql)?;
+        let runs = stmt.query_map(params![value], |row| Ok(RunRecord {
+            id: row.get(0)?,
+            started_at: time_column(row, 1)?,
+            finished_at: row.get::<_, Option<String>>(2)?.map(|_| time_column(row, 2)).transpose()?,
+            kind: row.get(3)?,
+            trigger: row.get(4)?,
+            session_source: row.get(5)?,
+            session_count: row.get(6)?,
+            outcome: row.get(7)?,
+            error: row.get(8)?,
+            last_posted_date: row.get(9)?,
+            days_posted: row.get(10)?,
+            days_failed: row.get(11)?,
+        }))?.collect::<rusqlite::Result<_>>()?;
+        Ok(runs)
+    }
+
+    /// The most recent `limit` runs, newest first.
+    pub fn recent_runs(&self, limit: usize) -> Result<Vec<RunRecord>> {
+        self.query_runs("ORDER BY r.id DESC LIMIT ?1", limit as i64)
+    }
+
+    pub fn run(&self, run_id: i64) -> Result<Option<RunRecord>> {
+        Ok(self.query_runs("WHERE r.id = ?1", run_id)?.pop())
+    }
+
+    fn query_posts(&self, filter: &str, value: &dyn rusqlite::ToSql) -> Result<Vec<PostRecord>> {
+        let sql = format!("SELECT run_id, date, posted_at, http_status, success, error FROM posts {} ORDER BY id", filter);
+        let mut stmt = self.conn.prepare(&sql)?;
+        let posts = stmt.query_map([value], |row| Ok(PostRecord {
+            run_id: row.get(0)?,
+            date: row.get(1)?,
+            posted_at: time_column(row, 2)?,
+            http_status: row.get(3)?,
+            success: row.get(4)?,
+            error: row.get(5)?,
+        }))?.collect::<rusqlite::Result<_>>()?;
+        Ok(posts)
+    }
+
+    /// Every day a run tried to post, in order, with its result.
+    pub fn posts_for_run(&self, run_id: i64) -> Result<Vec<PostRecord>> {
+        self.query_posts("WHERE run_id = ?1", &run_id)
+    }
+
+    /// Every attempt to post `date` (YYYY-MM-DD), across all runs.
+    pub fn posts_for_date(&self, date: &str) -> Result<Vec<PostRecord>> {
+        self.query_posts("WHERE date = ?1", &date)
+    }
+
+    pub fn refreshes_for_run(&self, run_id: i64) -> Result<Vec<RefreshRecord>> {
+        let mut stmt = self.conn.prepare("SELECT at, success, error FROM token_refreshes WHERE run_id = ?1 ORDER BY id")?;
+        let refreshes = stmt.query_map(params![run_id], |row| Ok(RefreshRecord {
+            at: time_column(row, 0)?,
+            success: row.get(1)?,
+            error: row.get(2)?,
+        }))?.collect::<rusqlite::Result<_>>()?;
+        Ok(refreshes)
+    }
+
+    /// The latest computed span of `date` (YYYY-MM-DD), if any run computed one.
+    pub fn stored_span(&self, date: &str) -> Result<Option<StoredSpan>> {
+        Ok(self.conn.query_row(
+            "SELECT first_boot, last_shutdown, total_span, excluded_minutes, computed_at, run_id FROM spans WHERE date = ?1",
+            params![date],
+            |row| Ok(StoredSpan {
+                first_boot: row.get(0)?,
+                last_shutdown: row.get(1)?,
+                total_span: row.get(2)?,
+                excluded_minutes: row.get(3)?,
+                computed_at: time_column(row, 4)?,
+                run_id: row.get(5)?,
+            }),
+        ).optional()?)
+    }
+}
+
+/// Combines freshly parsed sessions with the durable history, one record per boot (latest end wins).
+/// This keeps days recomputable after `last -n 100` has rolled over.
+pub fn merge_sessions(fresh: Vec<SessionRecord>, historical: Vec<SessionRecord>) -> Vec<SessionRecord> {
+    let mut by_start: BTreeMap<DateTime<Local>, DateTime<Local>> = BTreeMap::new();
+    for session in historical.into_iter().chain(fresh) {
+        let end = by_start.entry(session.start_time).or_insert(session.end_time);
+        if session.end_time > *end {
+            *end = session.end_time;
+        }
+    }
+    by_start.into_iter()
+        .map(|(start_time, end_time)| SessionRecord { start_time, end_time })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::DayType;
+    use serde_json::json;
+
+    fn span(date: &str, first_boot: &str, last_shutdown: &str, total_span: &str, excluded_minutes: i32) -> WorkSpanData {
+        WorkSpanData {
+            date: date.to_string(),
+            total_span_minutes: 0,
+            total_span: total_span.to_string(),
+            first_boot: first_boot.to_string(),
+            last_shutdown: last_shutdown.to_string(),
+            excluded_minutes,
+            adjusted: false,
+            note: None,
+            day_type: DayType::Workday,
+            expected_minutes: 0,
+            deviation_minutes: 0,
+            week_balance_minutes: 0,
+            month_balance_minutes: 0,
+        }
+    }
+
+    #[test]
+    fn last_successful_payload_ignores_failed_attempts() {
+        let store = HistoryStore::open(":memory:").unwrap();
+        let run = store.begin_run(Local::now(), "run", "timer").unwrap();
+        assert_eq!(store.last_successful_payload("2026-10-13").unwrap(), None);
+
+        store.record_post(run, "2026-10-13", &json!({"total_span": "8h 0m"}), Some(201), None).unwrap();
+        store.record_post(run, "2026-10-13", &json!({"total_span": "8h 30m"}), Some(200), None).unwrap();
+        store.record_post(run, "2026-10-13", &json!({"total_span": "9h 0m"}), Some(500), Some("HTTP 500")).unwrap();
+        store.record_post(run, "2026-10-13", &json!({"total_span": "9h 15m"}), None, Some("connection refused")).unwrap();
+        store.record_post(run, "2026-10-14", &json!({"total_span": "7h 0m"}), Some(201), None).unwrap();
+
+        assert_eq!(store.last_successful_payload("2026-10-13").unwrap(), Some(json!({"total_span": "8h 30m"})));
+        assert_eq!(store.last_successful_payload("2026-10-15").unwrap(), None);
+
+        let run = store.run(run).unwrap().unwrap();
+        assert_eq!((run.days_posted, run.days_failed), (3, 2));
+    }
+
+    #[test]
+    fn token_refreshes_are_listed_per_run() {
+        let store = HistoryStore::open(":memory:").unwrap();
+        let first = store.begin_run(Local::now(), "run", "timer").unwrap();
+        let second = store.begin_run(Local::now(), "run", "timer").unwrap();
+
+        store.record_token_refresh(first, None).unwrap();
+        store.record_token_refresh(first, Some("invalid refresh token")).unwrap();
+
+        let refreshes = store.refreshes_for_run(first).unwrap();
+        assert_eq!(refreshes.iter().map(|r| (r.success, r.error.as_deref())).collect::<Vec<_>>(),
+            vec![(true, None), (false, Some("invalid refresh token"))]);
+        assert!(store.refreshes_for_run(second).unwrap().is_empty());
+    }
+
+    #[test]
+    fn date_lookup_returns_latest_span_and_every_attempt() {
+        let mut store = HistoryStore::open(":memory:").unwrap();
+        let first = store.begin_run(Local::now(), "run", "timer").unwrap();
+        let second = store.begin_run(Local::now(), "backfill", "manual").unwrap();
+        assert!(store.stored_span("2026-10-13").unwrap().is_none());
+        assert!(store.posts_for_date("2026-10-13").unwrap().is_empty());
+
+        store.record_spans(first, &[span("2026-10-13", "08:00:00", "12:00:00", "4h 0m", 0)], Local::now()).unwrap();
+        store.record_post(first, "2026-10-13", &json!({}), Some(503), Some("HTTP 503")).unwrap();
+        store.record_spans(second, &[
+            span("2026-10-13", "08:00:00", "17:30:00", "9h 0m", 30),
+            span("2026-10-14", "09:00:00", "17:00:00", "8h 0m", 0),
+        ], Local::now()).unwrap();
+        store.record_post(second, "2026-10-13", &json!({}), Some(201), None).unwrap();
+        store.record_post(second, "2026-10-14", &json!({}), Some(201), None).unwrap();
+
+        let stored = store.stored_span("2026-10-13").unwrap().unwrap();
+        assert_eq!((stored.first_boot.as_str(), stored.last_shutdown.as_str(), stored.total_span.as_str()), ("08:00:00", "17:30:00", "9h 0m"));
+        assert_eq!((stored.excluded_minutes, stored.run_id), (30, second));
+
+        let posts = store.posts_for_date("2026-10-13").unwrap();
+        assert_eq!(posts.iter().map(|p| (p.run_id, p.http_status, p.success)).collect::<Vec<_>>(),
+            vec![(first, Some(503), false), (second, Some(201), true)]);
+        assert_eq!(posts[0].error.as_deref(), Some("HTTP 503"));
+        assert!(posts.iter().all(|p| p.date == "2026-10-13"));
+    }
+
+    #[test]
+    fn latest_end_is_chosen_by_instant_not_by_string() {
+        let store = HistoryStore::open(":memory:").unwrap();
+        let first = store.begin_run(Local::now(), "run", "manual").unwrap();
+        let second = store.begin_run(Local::now(), "run", "manual").unwrap();
+        // The same boot seen before and after the autumn DST change in Europe/Berlin:
+        // 02:10+01:00 (01:10 UTC) is later than 02:30+02:00 (00:30 UTC) but sorts first as text.
+        for (run_id, start, end) in [
+            (first, "2026-10-24T22:00:00+02:00", "2026-10-25T02:30:00+02:00"),
+            (second, "2026-10-24T22:00:00+02:00", "2026-10-25T02:10:00+01:00"),
+        ] {
+            store.conn.execute("INSERT INTO sessions (run_id, start_time, end_time) VALUES (?1, ?2, ?3)", params![run_id, start, end]).unwrap();
+        }
+
+        let sessions = store.historical_sessions().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].end_time, DateTime::parse_from_rfc3339("2026-10-25T01:10:00Z").unwrap());
+    }
+}
@@ -0,0 +1,74 @@
+//! The `[http]` settings of the run's client: unusable certificate paths and `no_proxy`.
+
+mod support;
+
+use avadhi_collector::api::SupabaseClient;
+use avadhi_collector::config::HttpConfig;
+use avadhi_collector::error::{exit_code, CollectorError};
+use reqwest::StatusCode;
+use support::{MockSupabase, Workdir};
+
+/// A proxy nobody listens on: every request sent through it fails to connect.
+const DEAD_PROXY: &str = "http://127.0.0.1:9";
+
+fn client_with(server: &MockSupabase, http: HttpConfig) -> anyhow::Result<SupabaseClient> {
+    let mut admin = server.admin_config();
+    admin.http = http;
+    SupabaseClient::from_admin_config(&admin)
+}
+
+#[tokio::test]
+async fn missing_ca_bundle_is_a_config_error() {
+    let _dir = Workdir::enter().await;
+    let server = MockSupabase::start().await;
+
+    let error = client_with(&server, HttpConfig { ca_bundle: Some("no-such-ca.pem".to_string()), ..HttpConfig::default() }).unwrap_err();
+
+    assert_eq!(exit_code(&error), CollectorError::EXIT_CONFIG_MISSING);
+    assert!(error.to_string().contains("CA bundle no-such-ca.pem"), "{}", error);
+}
+
+#[tokio::test]
+async fn unusable_client_identity_is_a_config_error() {
+    let dir = Workdir::enter().await;
+    let server = MockSupabase::start().await;
+    std::fs::write(dir.path().join("client.pem"), "not a certificate").unwrap();
+    std::fs::write(dir.path().join("client.key"), "not a key").unwrap();
+
+    let missing = client_with(&server, HttpConfig {
+        client_cert: Some("client.pem".to_string()),
+        client_key: Some("no-such.key".to_string()),
+        ..HttpConfig::default()
+    }).unwrap_err();
+    let invalid = client_with(&server, HttpConfig {
+        client_cert: Some("client.pem".to_string()),
+        client_key: Some("client.key".to_string()),
+        ..HttpConfig::default()
+    }).unwrap_err();
+    let half = client_with(&server, HttpConfig { client_cert: Some("client.pem".to_string()), ..HttpConfig::default() }).unwrap_err();
+
+    for (error, expected) in [(&missing, "client key no-such.key"), (&invalid, "client certificate client.pem / key client.key"), (&half, "set together")] {
+        assert_eq!(exit_code(error), CollectorError::EXIT_CONFIG_MISSING, "{}", error);
+        assert!(error.to_string().contains(expected), "{}", error);
+    }
+}
+
+#[tokio::test]
+async fn no_proxy_hosts_bypass_the_proxy() {
+    let _dir = Workdir::enter().await;
+    let server = MockSupabase::start().await;
+
+    let bypassed = client_with(&server, HttpConfig {
+        proxy: Some(DEAD_PROXY.to_string()),
+        no_proxy: Some("localhost,127.0.0.1".to_string()),
+        ..HttpConfig::default()
+    }).unwrap();
+    let (status, _) = bypassed.auth_settings().await.unwrap();
+    assert_eq!(status, StatusCode::OK);
+
+    // The same request through the proxy never reaches the server.
+    let proxied = client_with(&server, HttpConfig { proxy: Some(DEAD_PROXY.to_string()), ..HttpConfig::default() }).unwrap();
+    let error = proxied.auth_settings().await.unwrap_err();
+    assert_eq!(exit_code(&error), CollectorError::EXIT_NETWORK, "{:#}", error);
+    assert_eq!(server.requests().len(), 1);
+}
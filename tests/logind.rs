@@ -0,0 +1,67 @@
+//! Idle and lock sampling against a fake logind on a private bus. Skipped without `dbus-daemon`.
+
+mod support;
+
+use avadhi_collector::config::{IdleConfig, IdleSourceKind, LockConfig};
+use avadhi_collector::gaps::Gap;
+use avadhi_collector::idle::{load_idle_log, sample_idle};
+use avadhi_collector::locks::{load_lock_log, sample_locked_hint, LockLog};
+use chrono::{Duration, Local, TimeZone};
+use support::logind::FakeLogind;
+use support::Workdir;
+
+fn last_state(log: &LockLog, session: &str) -> Option<(bool, String)> {
+    log.events.iter().rev().find(|e| e.session == session).map(|e| (e.locked, e.source.clone()))
+}
+
+#[tokio::test]
+async fn idle_sample_records_the_period_logind_reports() {
+    let _dir = Workdir::enter().await;
+    let Some(logind) = FakeLogind::start(&[]).await else {
+        eprintln!("dbus-daemon not available, skipping");
+        return;
+    };
+    let config = IdleConfig { source: IdleSourceKind::Logind, dbus_address: Some(logind.address().to_string()), ..IdleConfig::default() };
+    // Whole seconds, so the round trip through microseconds is exact.
+    let now = Local.timestamp_opt(Local::now().timestamp(), 0).unwrap();
+    let (idle_from, back_at) = (now - Duration::minutes(40), now - Duration::minutes(10));
+
+    logind.set_idle(true, idle_from.timestamp_micros() as u64).await;
+    let log = sample_idle(&config).await.unwrap();
+    assert_eq!(log.idle_since, Some(idle_from));
+    assert_eq!(log.last_source.as_deref(), Some("logind"));
+
+    logind.set_idle(false, back_at.timestamp_micros() as u64).await;
+    sample_idle(&config).await.unwrap();
+
+    let log = load_idle_log();
+    assert_eq!(log.idle_since, None);
+    assert_eq!(log.periods, [Gap { start: idle_from, end: back_at }]);
+}
+
+#[tokio::test]
+async fn lock_poll_records_hints_and_closes_locks_of_vanished_sessions() {
+    let _dir = Workdir::enter().await;
+    let Some(logind) = FakeLogind::start(&["1", "2", "3"]).await else {
+        eprintln!("dbus-daemon not available, skipping");
+        return;
+    };
+    let config = LockConfig { dbus_address: Some(logind.address().to_string()), ..LockConfig::default() };
+
+    logind.set_locked("1", true).await;
+    sample_locked_hint(&config).await.unwrap();
+    let log = load_lock_log();
+    assert_eq!(last_state(&log, "1"), Some((true, "poll".to_string())));
+    assert_eq!(last_state(&log, "2"), Some((false, "poll".to_string())));
+
+    // Session 1 is logged out while locked; session 2 disappears between listing and reading.
+    logind.remove_session("1").await;
+    logind.unexport_session("2").await;
+    logind.set_locked("3", true).await;
+    sample_locked_hint(&config).await.unwrap();
+
+    let log = load_lock_log();
+    assert_eq!(last_state(&log, "1"), Some((false, "session_gone".to_string())));
+    assert_eq!(last_state(&log, "2"), Some((false, "poll".to_string())), "unreadable session is skipped");
+    assert_eq!(last_state(&log, "3"), Some((true, "poll".to_string())), "later sessions are still sampled");
+}
@@ -0,0 +1,221 @@
+//! The posting pipeline against the in-process mock Supabase: headers and payload, token refresh,
+//! retries and the failures that map to exit codes.
+
+mod support;
+
+use std::time::{Duration, Instant};
+
+use avadhi_collector::api::{api_status, fetch_work_spans, post_work_span};
+use avadhi_collector::config::{UserConfig, USER_CONFIG_PATH};
+use avadhi_collector::error::{exit_code, CollectorError};
+use avadhi_collector::retry::RunDeadline;
+use reqwest::StatusCode;
+use serde_json::json;
+use support::{user_config, work_span, MockResponse, MockSupabase, Workdir, ANON_KEY, USER_ID};
+
+#[tokio::test]
+async fn upsert_sends_auth_headers_and_payload() {
+    let _dir = Workdir::enter().await;
+    let server = MockSupabase::start().await;
+    let mut user = user_config();
+
+    let status = post_work_span(work_span("2026-10-13"), &server.client(), &server.admin_config(), &mut user).await.unwrap();
+
+    assert_eq!(status, StatusCode::CREATED);
+    let upserts = server.upserts();
+    assert_eq!(upserts.len(), 1);
+    let request = &upserts[0];
+    assert_eq!(request.path(), "/rest/v1/daily_work_span");
+    assert_eq!(request.header("authorization"), Some("Bearer access-1"));
+    assert_eq!(request.header("apikey"), Some(ANON_KEY));
+    assert_eq!(request.header("prefer"), Some("resolution=merge-duplicates"));
+    assert!(request.header("user-agent").unwrap().starts_with("avadhi-collector/"));
+
+    let body = request.json();
+    assert_eq!(body["user_id"], json!(USER_ID));
+    assert_eq!(body["date"], json!("2026-10-13"));
+    assert_eq!(body["total_span_minutes"], json!(510));
+    assert_eq!(body["first_boot"], json!("08:45:00"));
+    assert!(body.get("excluded_minutes").is_none(), "local-only fields must not be posted");
+}
+
+#[tokio::test]
+async fn expired_token_is_refreshed_saved_and_the_post_retried() {
+    let dir = Workdir::enter().await;
+    let server = MockSupabase::start().await;
+    server.push_rest(MockResponse::json(401, json!({ "message": "JWT expired" })));
+    let mut user = user_config();
+
+    let status = post_work_span(work_span("2026-10-13"), &server.client(), &server.admin_config(), &mut user).await.unwrap();
+
+    assert_eq!(status, StatusCode::CREATED);
+    let refreshes = server.refreshes();
+    assert_eq!(refreshes.len(), 1);
+    assert_eq!(refreshes[0].json(), json!({ "refresh_token": "refresh-1" }));
+    assert_eq!(refreshes[0].header("apikey"), Some(ANON_KEY));
+
+    let upserts = server.upserts();
+    assert_eq!(upserts.len(), 2);
+    assert_eq!(upserts[1].header("authorization"), Some("Bearer access-2"));
+
+    // The single-use refresh token must be on disk before anything else can fail.
+    assert_eq!(user.access_token.as_deref(), Some("access-2"));
+    assert_eq!(user.refresh_token.as_deref(), Some("refresh-2"));
+    let saved: UserConfig = toml::from_str(&dir.read(USER_CONFIG_PATH).expect("AvadhiConfig.toml written")).unwrap();
+    assert_eq!(saved.refresh_token.as_deref(), Some("refresh-2"));
+    assert_eq!(saved.last_posted_date.as_deref(), Some("2026-10-12"));
+}
+
+#[tokio::test]
+async fn consumed_refresh_token_fails_without_prompting() {
+    let dir = Workdir::enter().await;
+    let server = MockSupabase::start().await;
+    server.push_rest(MockResponse::json(401, json!({ "message": "JWT expired" })));
+    server.push_auth(MockResponse::refresh_token_already_used());
+    let mut user = user_config();
+
+    let error = post_work_span(work_span("2026-10-13"), &server.client(), &server.admin_config(), &mut user).await.unwrap_err();
+
+    assert!(matches!(error.downcast_ref::<CollectorError>(), Some(CollectorError::RefreshTokenConsumed)), "{:#}", error);
+    assert_eq!(exit_code(&error), CollectorError::EXIT_REFRESH_TOKEN_CONSUMED);
+    assert_eq!(server.upserts().len(), 1);
+    assert_eq!(user.refresh_token.as_deref(), Some("refresh-1"));
+    assert!(dir.read(USER_CONFIG_PATH).is_none(), "nothing to save after a failed refresh");
+}
+
+#[tokio::test]
+async fn rejected_refreshed_token_fails_after_one_refresh() {
+    let dir = Workdir::enter().await;
+    let server = MockSupabase::start().await;
+    server.push_rest(MockResponse::json(401, json!({ "message": "JWT expired" })))
+        .push_rest(MockResponse::json(401, json!({ "message": "invalid JWT" })));
+    let mut user = user_config();
+
+    let error = post_work_span(work_span("2026-10-13"), &server.client(), &server.admin_config(), &mut user).await.unwrap_err();
+
+    assert!(matches!(error.downcast_ref::<CollectorError>(), Some(CollectorError::AuthExpired(_))), "{:#}", error);
+    assert_eq!(exit_code(&error), CollectorError::EXIT_AUTH_EXPIRED);
+    assert_eq!(server.refreshes().len(), 1);
+    assert_eq!(server.upserts().len(), 2);
+    // The refresh itself succeeded, so its single-use token is kept.
+    let saved: UserConfig = toml::from_str(&dir.read(USER_CONFIG_PATH).expect("AvadhiConfig.toml written")).unwrap();
+    assert_eq!(saved.refresh_token.as_deref(), Some("refresh-2"));
+}
+
+#[tokio::test]
+async fn server_error_burst_is_retried_until_success() {
+    let _dir = Workdir::enter().await;
+    let server = MockSupabase::start().await;
+    server.push_rest(MockResponse::new(503, "upstream unavailable"))
+        .push_rest(MockResponse::new(500, "internal error"));
+    let mut user = user_config();
+
+    let status = post_work_span(work_span("2026-10-13"), &server.client(), &server.admin_config(), &mut user).await.unwrap();
+
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(server.upserts().len(), 3);
+    assert!(server.refreshes().is_empty());
+}
+
+#[tokio::test]
+async fn persistent_server_errors_give_up_after_max_attempts() {
+    let _dir = Workdir::enter().await;
+    let server = MockSupabase::start().await;
+    for _ in 0..5 {
+        server.push_rest(MockResponse::new(502, "bad gateway"));
+    }
+    let mut user = user_config();
+    let admin = server.admin_config();
+
+    let error = post_work_span(work_span("2026-10-13"), &server.client(), &admin, &mut user).await.unwrap_err();
+
+    assert_eq!(exit_code(&error), CollectorError::EXIT_SERVER);
+    assert_eq!(api_status(&error), Some(StatusCode::BAD_GATEWAY));
+    assert_eq!(server.upserts().len(), admin.retry.max_attempts as usize);
+}
+
+#[tokio::test]
+async fn rate_limit_waits_for_retry_after() {
+    let _dir = Workdir::enter().await;
+    let server = MockSupabase::start().await;
+    server.push_rest(MockResponse::json(429, json!({ "message": "rate limited" })).header("Retry-After", "1"));
+    let mut user = user_config();
+
+    let started = Instant::now();
+    let status = post_work_span(work_span("2026-10-13"), &server.client(), &server.admin_config(), &mut user).await.unwrap();
+
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(server.upserts().len(), 2);
+    assert!(started.elapsed() >= Duration::from_secs(1), "Retry-After replaces the zero backoff");
+}
+
+#[tokio::test]
+async fn retry_after_past_the_run_deadline_fails_at_once() {
+    let _dir = Workdir::enter().await;
+    let server = MockSupabase::start().await;
+    server.push_rest(MockResponse::json(429, json!({ "message": "rate limited" })).header("Retry-After", "30"));
+    let mut admin = server.admin_config();
+    admin.retry.run_deadline_seconds = 5;
+    let client = server.client().with_deadline(RunDeadline::start(&admin.retry));
+    let mut user = user_config();
+
+    let started = Instant::now();
+    let error = post_work_span(work_span("2026-10-13"), &client, &admin, &mut user).await.unwrap_err();
+
+    assert!(started.elapsed() < Duration::from_secs(5), "no wait crosses the deadline");
+    assert!(format!("{:#}", error).contains("run deadline"), "{:#}", error);
+    assert_eq!(server.upserts().len(), 1);
+}
+
+#[tokio::test]
+async fn rls_denial_is_not_retried() {
+    let _dir = Workdir::enter().await;
+    let server = MockSupabase::start().await;
+    server.push_rest(MockResponse::rls_denied());
+    let mut user = user_config();
+
+    let error = post_work_span(work_span("2026-10-13"), &server.client(), &server.admin_config(), &mut user).await.unwrap_err();
+
+    assert_eq!(exit_code(&error), CollectorError::EXIT_RLS_DENIED);
+    assert_eq!(api_status(&error), Some(StatusCode::FORBIDDEN));
+    assert_eq!(server.upserts().len(), 1);
+}
+
+#[tokio::test]
+async fn other_client_errors_are_rejected_without_retry() {
+    let _dir = Workdir::enter().await;
+    let server = MockSupabase::start().await;
+    server.push_rest(MockResponse::json(400, json!({ "message": "invalid input syntax for type date" })));
+    let mut user = user_config();
+
+    let error = post_work_span(work_span("2026-10-13"), &server.client(), &server.admin_config(), &mut user).await.unwrap_err();
+
+    assert_eq!(exit_code(&error), CollectorError::EXIT_REJECTED);
+    assert_eq!(server.upserts().len(), 1);
+}
+
+#[tokio::test]
+async fn server_rows_are_read_for_the_user_and_range() {
+    let _dir = Workdir::enter().await;
+    let server = MockSupabase::start().await;
+    server.push_rest(MockResponse::json(401, json!({ "message": "JWT expired" })));
+    server.push_rest(MockResponse::json(200, json!([{
+        "date": "2026-10-13",
+        "first_boot": "08:45:00",
+        "last_shutdown": "17:15:00",
+        "total_span_minutes": 510,
+        "total_span": "8h 30m",
+    }])));
+    let mut user = user_config();
+
+    let rows = fetch_work_spans(&server.client(), &mut user, "2026-10-01", "2026-10-17").await.unwrap();
+
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].date, "2026-10-13");
+    let reads: Vec<_> = server.requests().into_iter().filter(|r| r.method == "GET").collect();
+    assert_eq!(reads.len(), 2);
+    let query = &reads[1].target;
+    assert!(query.contains(&format!("user_id=eq.{}", USER_ID)), "{}", query);
+    assert!(query.contains("date=gte.2026-10-01") && query.contains("date=lte.2026-10-17"), "{}", query);
+    assert_eq!(reads[1].header("authorization"), Some("Bearer access-2"));
+}
@@ -0,0 +1,145 @@
+//! Named profiles: per-profile backend and filters on top of Config.toml, per-profile credential
+//! files, and which profiles a command covers.
+
+mod support;
+
+use avadhi_collector::api::post_work_span;
+use avadhi_collector::collector::{adjust_day, replay_spans};
+use avadhi_collector::config::{profile_path, AdminConfig, UserConfig, USER_CONFIG_PATH};
+use avadhi_collector::error::{exit_code, CollectorError};
+use avadhi_collector::overrides::load_overrides;
+use avadhi_collector::sessions::SessionRecord;
+use avadhi_collector::state::{load_state, record_run};
+use chrono::{Local, NaiveDate, TimeZone};
+use serde_json::json;
+use support::{user_config, work_span, MockResponse, MockSupabase, Workdir};
+
+const CONFIG: &str = r#"
+web_app_url = "https://app.example.com/login"
+supabase_url = "https://shared.supabase.co"
+supabase_anon_key = "shared-key"
+
+[span_policy]
+round_total_minutes = 15
+
+[profiles.acme]
+supabase_url = "https://acme.supabase.co"
+supabase_anon_key = "acme-key"
+window_start = "08:00"
+window_end = "13:00"
+
+[profiles.globex]
+weekdays = ["thu", "fri"]
+"#;
+
+fn admin_config() -> AdminConfig {
+    toml::from_str(CONFIG).unwrap()
+}
+
+#[test]
+fn profile_overrides_backend_and_filters_and_inherits_the_rest() {
+    let admin = admin_config();
+
+    let acme = admin.for_profile(Some("acme")).unwrap();
+    assert_eq!(acme.supabase_url.as_deref(), Some("https://acme.supabase.co"));
+    assert_eq!(acme.supabase_anon_key.as_deref(), Some("acme-key"));
+    assert_eq!(acme.web_app_url.as_deref(), Some("https://app.example.com/login"));
+    assert_eq!(acme.span_policy.window_start.as_deref(), Some("08:00"));
+    assert_eq!(acme.span_policy.round_total_minutes, 15);
+    assert!(acme.span_policy.weekdays.is_empty());
+
+    let globex = admin.for_profile(Some("globex")).unwrap();
+    assert_eq!(globex.supabase_url.as_deref(), Some("https://shared.supabase.co"));
+    assert_eq!(globex.span_policy.window_start, None);
+    assert_eq!(globex.span_policy.weekdays, ["thu", "fri"]);
+
+    let top_level = admin.for_profile(None).unwrap();
+    assert_eq!(top_level.supabase_url.as_deref(), Some("https://shared.supabase.co"));
+}
+
+#[test]
+fn run_covers_every_profile_unless_one_is_selected() {
+    let admin = admin_config();
+
+    assert_eq!(admin.selected_profiles(None).unwrap(), [Some("acme".to_string()), Some("globex".to_string())]);
+    assert_eq!(admin.selected_profiles(Some("globex")).unwrap(), [Some("globex".to_string())]);
+    assert_eq!(AdminConfig::default().selected_profiles(None).unwrap(), [None]);
+
+    let unknown = anyhow::Error::from(admin.selected_profiles(Some("initech")).unwrap_err());
+    assert_eq!(exit_code(&unknown), CollectorError::EXIT_CONFIG_MISSING);
+    assert!(unknown.to_string().contains("acme, globex"), "{}", unknown);
+    assert!(AdminConfig::default().selected_profiles(Some("acme")).is_err());
+    assert!(admin.for_profile(Some("../acme")).is_err(), "profile names end up in file names");
+
+    // Single-account commands must be told which account to use.
+    assert!(admin.single_profile(None).is_err());
+    assert_eq!(admin.single_profile(Some("acme")).unwrap().as_deref(), Some("acme"));
+    assert_eq!(AdminConfig::default().single_profile(None).unwrap(), None);
+}
+
+#[test]
+fn profile_files_sit_next_to_the_default_ones() {
+    assert_eq!(profile_path(USER_CONFIG_PATH, None), "AvadhiConfig.toml");
+    assert_eq!(profile_path(USER_CONFIG_PATH, Some("acme")), "AvadhiConfig.acme.toml");
+    assert_eq!(profile_path("AvadhiHistory.sqlite", Some("acme")), "AvadhiHistory.acme.sqlite");
+}
+
+#[tokio::test]
+async fn refreshed_tokens_are_saved_to_the_profile_file() {
+    let dir = Workdir::enter().await;
+    let server = MockSupabase::start().await;
+    server.push_rest(MockResponse::json(401, json!({ "message": "JWT expired" })));
+    let mut user = UserConfig { profile: Some("acme".to_string()), ..user_config() };
+
+    post_work_span(work_span("2026-10-13"), &server.client(), &server.admin_config(), &mut user).await.unwrap();
+
+    let saved: UserConfig = toml::from_str(&dir.read("AvadhiConfig.acme.toml").expect("AvadhiConfig.acme.toml written")).unwrap();
+    assert_eq!(saved.refresh_token.as_deref(), Some("refresh-2"));
+    assert!(dir.read(USER_CONFIG_PATH).is_none(), "the default account is left alone");
+}
+
+#[test]
+fn weekday_filter_keeps_only_the_profiles_days() {
+    // Mon Oct 12 .. Fri Oct 16 2026, 09:00 - 17:00 every day.
+    let sessions: Vec<SessionRecord> = (12..=16)
+        .map(|day| SessionRecord {
+            start_time: Local.with_ymd_and_hms(2026, 10, day, 9, 0, 0).unwrap(),
+            end_time: Local.with_ymd_and_hms(2026, 10, day, 17, 0, 0).unwrap(),
+        })
+        .collect();
+
+    let globex = admin_config().for_profile(Some("globex")).unwrap();
+    let spans = replay_spans(&globex, sessions.clone()).unwrap();
+    let days: Vec<&str> = spans.iter().map(|s| s.date.as_str()).collect();
+    assert_eq!(days, ["2026-10-15", "2026-10-16"]);
+
+    let everyone = replay_spans(&admin_config().for_profile(None).unwrap(), sessions).unwrap();
+    assert_eq!(everyone.len(), 5);
+}
+
+#[tokio::test]
+async fn run_outcomes_are_kept_per_profile() {
+    let dir = Workdir::enter().await;
+    let started_at = Local.with_ymd_and_hms(2026, 10, 14, 10, 0, 0).unwrap();
+
+    record_run("timer", started_at, &Ok(()), Some("acme"));
+    record_run("timer", started_at, &Err(anyhow::anyhow!("globex is down")), Some("globex"));
+
+    assert!(dir.read("AvadhiState.acme.toml").is_some());
+    assert_eq!(load_state(Some("acme")).last_run_outcome.as_deref(), Some("success"));
+    assert_eq!(load_state(Some("globex")).last_run_error.as_deref(), Some("globex is down"));
+    assert_eq!(load_state(None).last_run_at, None);
+}
+
+#[tokio::test]
+async fn adjustments_are_kept_per_profile() {
+    let dir = Workdir::enter().await;
+    let date = NaiveDate::from_ymd_opt(2026, 10, 13).unwrap();
+
+    adjust_day(Some("acme"), date, true, None, None, false).unwrap();
+
+    assert!(dir.read("AvadhiOverrides.acme.toml").is_some());
+    assert!(dir.read("AvadhiOverrides.toml").is_none(), "the default account is left alone");
+    assert!(load_overrides(Some("acme")).unwrap().get(date).is_some_and(|day| day.exclude));
+    assert!(load_overrides(None).unwrap().get(date).is_none());
+}
@@ -0,0 +1,40 @@
+//! Replay mode: sessions from a saved `last -x -F reboot` output, pinned to a fixed "now".
+
+use avadhi_collector::collector::replay_spans;
+use avadhi_collector::config::AdminConfig;
+use avadhi_collector::sessions::read_sessions_file;
+use chrono::{Local, NaiveDate, TimeZone};
+
+const LAST_OUTPUT: &str = "\
+reboot   system boot  6.8.0-45-generic Tue Oct 13 08:41:10 2026   still running
+reboot   system boot  6.8.0-45-generic Mon Oct 12 08:45:00 2026 - Mon Oct 12 17:15:00 2026  (08:30)
+reboot   system boot  6.8.0-45-generic Thu Oct 15 09:00:00 2026 - Thu Oct 15 10:00:00 2026  (01:00)
+
+wtmp begins Mon Oct 12 08:45:00 2026
+";
+
+#[test]
+fn replay_of_last_output_is_pinned_to_now() {
+    let path = std::env::temp_dir().join(format!("avadhi-collector-replay-{}.txt", std::process::id()));
+    std::fs::write(&path, LAST_OUTPUT).unwrap();
+    let now = Local.with_ymd_and_hms(2026, 10, 13, 12, 0, 0).unwrap();
+
+    let (sessions, format) = read_sessions_file(&path, now).unwrap();
+    let spans = replay_spans(&AdminConfig::default(), sessions.clone()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(format, "last output");
+    // The Oct 15 session lies after "now" and is dropped; the running one ends at "now".
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0].start_time.date_naive(), NaiveDate::from_ymd_opt(2026, 10, 12).unwrap());
+    assert_eq!(sessions[1].end_time, now);
+
+    let days: Vec<(&str, &str, &str, i32)> = spans.iter()
+        .map(|s| (s.date.as_str(), s.first_boot.as_str(), s.last_shutdown.as_str(), s.total_span_minutes))
+        .collect();
+    assert_eq!(days, [
+        ("2026-10-12", "08:45:00", "17:15:00", 510),
+        ("2026-10-13", "08:41:10", "12:00:00", 198),
+    ]);
+    assert_eq!(spans[0].deviation_minutes, 30);
+}
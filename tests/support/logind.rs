@@ -0,0 +1,117 @@
+//! A fake logind (Manager idle hints, sessions with `LockedHint`) on a private `dbus-daemon`, for
+//! exercising idle and lock sampling without a system bus. Mirrors examples/fake_logind.rs.
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+
+use zbus::zvariant::OwnedObjectPath;
+
+const MANAGER_PATH: &str = "/org/freedesktop/login1";
+
+fn session_path(id: &str) -> String {
+    format!("/org/freedesktop/login1/session/_3{}", id)
+}
+
+struct FakeManager {
+    idle_hint: bool,
+    idle_since_hint: u64,
+    sessions: Vec<String>,
+}
+
+#[zbus::interface(name = "org.freedesktop.login1.Manager")]
+impl FakeManager {
+    #[zbus(property)]
+    fn idle_hint(&self) -> bool {
+        self.idle_hint
+    }
+
+    #[zbus(property)]
+    fn idle_since_hint(&self) -> u64 {
+        self.idle_since_hint
+    }
+
+    fn list_sessions(&self) -> Vec<(String, u32, String, String, OwnedObjectPath)> {
+        self.sessions.iter()
+            .map(|id| (id.clone(), 1000, "tester".to_string(), "seat0".to_string(), OwnedObjectPath::try_from(session_path(id)).unwrap()))
+            .collect()
+    }
+}
+
+struct FakeSession {
+    locked_hint: bool,
+}
+
+#[zbus::interface(name = "org.freedesktop.login1.Session")]
+impl FakeSession {
+    #[zbus(property)]
+    fn locked_hint(&self) -> bool {
+        self.locked_hint
+    }
+}
+
+/// logind on a private bus, stopped when dropped. Sessions are seated sessions of user "tester".
+pub struct FakeLogind {
+    address: String,
+    daemon: Child,
+    connection: zbus::Connection,
+}
+
+impl FakeLogind {
+    /// Starts `dbus-daemon` and the fake service. None if no `dbus-daemon` binary is available.
+    pub async fn start(sessions: &[&str]) -> Option<Self> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take()?).read_line(&mut address).ok()?;
+        let address = address.trim().to_string();
+
+        let manager = FakeManager { idle_hint: false, idle_since_hint: 0, sessions: sessions.iter().map(|s| s.to_string()).collect() };
+        let mut builder = zbus::connection::Builder::address(address.as_str()).ok()?
+            .name("org.freedesktop.login1").ok()?
+            .serve_at(MANAGER_PATH, manager).ok()?;
+        for id in sessions {
+            builder = builder.serve_at(session_path(id), FakeSession { locked_hint: false }).ok()?;
+        }
+        let connection = builder.build().await.expect("fake logind on the private bus");
+        Some(FakeLogind { address, daemon, connection })
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub async fn set_idle(&self, idle: bool, since_usec: u64) {
+        let manager = self.connection.object_server().interface::<_, FakeManager>(MANAGER_PATH).await.unwrap();
+        let mut manager = manager.get_mut().await;
+        manager.idle_hint = idle;
+        manager.idle_since_hint = since_usec;
+    }
+
+    pub async fn set_locked(&self, session: &str, locked: bool) {
+        let session = self.connection.object_server().interface::<_, FakeSession>(session_path(session)).await.unwrap();
+        session.get_mut().await.locked_hint = locked;
+    }
+
+    /// Drops `session` from ListSessions and the bus, like logind after a logout.
+    pub async fn remove_session(&self, session: &str) {
+        self.unexport_session(session).await;
+        let manager = self.connection.object_server().interface::<_, FakeManager>(MANAGER_PATH).await.unwrap();
+        manager.get_mut().await.sessions.retain(|s| s != session);
+    }
+
+    /// Keeps `session` in ListSessions but removes its object, as if it vanished mid-poll.
+    pub async fn unexport_session(&self, session: &str) {
+        self.connection.object_server().remove::<FakeSession, _>(session_path(session)).await.unwrap();
+    }
+}
+
+impl Drop for FakeLogind {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
@@ -0,0 +1,333 @@
+//! In-process stand-in for the Supabase endpoints the collector talks to (PostgREST `/rest/v1/*`
+//! and GoTrue `/auth/v1/*`), plus a scratch working directory for the config files a run writes.
+//!
+//! Responses are scripted per endpoint and served in order; once a queue is empty the endpoint
+//! answers like a healthy backend. Every request is recorded for assertions.
+
+#![allow(dead_code)]
+
+pub mod logind;
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use avadhi_collector::api::{SupabaseClient, WorkSpanData, NONINTERACTIVE_ENV};
+use avadhi_collector::config::{AdminConfig, RetryConfig, UserConfig};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+pub const ANON_KEY: &str = "anon-key";
+pub const USER_ID: &str = "00000000-0000-0000-0000-000000000001";
+
+/// A canned HTTP response.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl MockResponse {
+    pub fn new(status: u16, body: impl Into<String>) -> Self {
+        MockResponse { status, headers: Vec::new(), body: body.into() }
+    }
+
+    pub fn json(status: u16, body: Value) -> Self {
+        MockResponse::new(status, body.to_string())
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// GoTrue's answer to a refresh token that was already exchanged.
+    pub fn refresh_token_already_used() -> Self {
+        MockResponse::json(400, json!({
+            "code": 400,
+            "error_code": "refresh_token_already_used",
+            "msg": "Invalid Refresh Token: Already Used",
+        }))
+    }
+
+    /// PostgREST's answer to an insert rejected by a Row Level Security policy.
+    pub fn rls_denied() -> Self {
+        MockResponse::json(403, json!({
+            "code": "42501",
+            "message": "new row violates row-level security policy for table \"daily_work_span\"",
+        }))
+    }
+}
+
+/// One request as received by the mock.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Path including the query string, e.g. `/auth/v1/token?grant_type=refresh_token`.
+    pub target: String,
+    /// Header names are lowercase.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    pub fn path(&self) -> &str {
+        self.target.split('?').next().unwrap_or_default()
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        self.headers.iter().find(|(n, _)| *n == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap_or_else(|e| panic!("{} {} body is not JSON ({}): {}", self.method, self.target, e, self.body))
+    }
+
+    pub fn is_upsert(&self) -> bool {
+        self.method == "POST" && self.path().starts_with("/rest/v1/")
+    }
+
+    pub fn is_refresh(&self) -> bool {
+        self.method == "POST" && self.target == "/auth/v1/token?grant_type=refresh_token"
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    rest: VecDeque<MockResponse>,
+    auth: VecDeque<MockResponse>,
+    requests: Vec<RecordedRequest>,
+    refreshes: usize,
+}
+
+impl MockState {
+    fn respond(&mut self, request: &RecordedRequest) -> MockResponse {
+        if request.path().starts_with("/rest/v1/") {
+            if let Some(response) = self.rest.pop_front() {
+                return response;
+            }
+            return match request.method.as_str() {
+                "GET" => MockResponse::json(200, json!([])),
+                _ => MockResponse::new(201, ""),
+            };
+        }
+        if request.path().starts_with("/auth/v1/token") {
+            if let Some(response) = self.auth.pop_front() {
+                return response;
+            }
+            self.refreshes += 1;
+            return MockResponse::json(200, json!({
+                "access_token": format!("access-{}", self.refreshes + 1),
+                "refresh_token": format!("refresh-{}", self.refreshes + 1),
+                "token_type": "bearer",
+                "expires_in": 3600,
+            }));
+        }
+        if request.path() == "/auth/v1/settings" {
+            return MockResponse::json(200, json!({ "external": {}, "disable_signup": true }));
+        }
+        MockResponse::json(404, json!({ "message": "not found" }))
+    }
+}
+
+/// A fake PostgREST + GoTrue server on 127.0.0.1, running until dropped with the test's runtime.
+///
+/// Unscripted requests succeed: upserts get 201, reads get `[]` and each refresh hands out the
+/// next token pair (`access-2`/`refresh-2`, then `access-3`/`refresh-3`, ...).
+pub struct MockSupabase {
+    url: String,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockSupabase {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind mock server");
+        let url = format!("http://{}", listener.local_addr().expect("mock server address"));
+        let state = Arc::new(Mutex::new(MockState::default()));
+
+        let server_state = state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_connection(stream, server_state.clone()));
+            }
+        });
+        MockSupabase { url, state }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Queues a response for the next `/rest/v1/*` request.
+    pub fn push_rest(&self, response: MockResponse) -> &Self {
+        self.state.lock().unwrap().rest.push_back(response);
+        self
+    }
+
+    /// Queues a response for the next `/auth/v1/token` request.
+    pub fn push_auth(&self, response: MockResponse) -> &Self {
+        self.state.lock().unwrap().auth.push_back(response);
+        self
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn upserts(&self) -> Vec<RecordedRequest> {
+        self.requests().into_iter().filter(RecordedRequest::is_upsert).collect()
+    }
+
+    pub fn refreshes(&self) -> Vec<RecordedRequest> {
+        self.requests().into_iter().filter(RecordedRequest::is_refresh).collect()
+    }
+
+    /// Config.toml pointing at this server. Retries are immediate and deterministic.
+    pub fn admin_config(&self) -> AdminConfig {
+        AdminConfig {
+            supabase_url: Some(self.url.clone()),
+            supabase_anon_key: Some(ANON_KEY.to_string()),
+            retry: RetryConfig {
+                base_delay_seconds: 0,
+                jitter: false,
+                ..RetryConfig::default()
+            },
+            ..AdminConfig::default()
+        }
+    }
+
+    pub fn client(&self) -> SupabaseClient {
+        SupabaseClient::from_admin_config(&self.admin_config()).expect("client for mock server")
+    }
+}
+
+async fn serve_connection(stream: TcpStream, state: Arc<Mutex<MockState>>) {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).await.unwrap_or(0) == 0 {
+        return;
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+            return;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    let length = headers.iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    if reader.read_exact(&mut body).await.is_err() {
+        return;
+    }
+
+    let request = RecordedRequest { method, target, headers, body: String::from_utf8_lossy(&body).into_owned() };
+    let response = {
+        let mut state = state.lock().unwrap();
+        let response = state.respond(&request);
+        state.requests.push(request);
+        response
+    };
+
+    let mut out = format!("HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status, response.body.len());
+    for (name, value) in &response.headers {
+        out.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    out.push_str("\r\n");
+    out.push_str(&response.body);
+
+    let mut stream = reader.into_inner();
+    let _ = stream.write_all(out.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Serializes tests that change the process-wide working directory.
+static WORKDIR_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+static WORKDIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A fresh, empty working directory for one test. Config files written by the collector
+/// (AvadhiConfig.toml, ...) land here. The previous directory is restored on drop.
+pub struct Workdir {
+    path: PathBuf,
+    previous: PathBuf,
+    _guard: tokio::sync::MutexGuard<'static, ()>,
+}
+
+impl Workdir {
+    pub async fn enter() -> Self {
+        let guard = WORKDIR_LOCK.lock().await;
+        // No test may block on the setup prompts.
+        std::env::set_var(NONINTERACTIVE_ENV, "1");
+
+        let path = std::env::temp_dir().join(format!(
+            "avadhi-collector-test-{}-{}", std::process::id(), WORKDIR_COUNTER.fetch_add(1, Ordering::Relaxed)));
+        std::fs::create_dir_all(&path).expect("create test working directory");
+        let previous = std::env::current_dir().expect("current directory");
+        std::env::set_current_dir(&path).expect("enter test working directory");
+        Workdir { path, previous, _guard: guard }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn read(&self, file: &str) -> Option<String> {
+        std::fs::read_to_string(self.path.join(file)).ok()
+    }
+}
+
+impl Drop for Workdir {
+    fn drop(&mut self) {
+        let _ = std::env::set_current_dir(&self.previous);
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// AvadhiConfig.toml contents of a logged-in user.
+pub fn user_config() -> UserConfig {
+    UserConfig {
+        user_id: Some(USER_ID.to_string()),
+        access_token: Some("access-1".to_string()),
+        refresh_token: Some("refresh-1".to_string()),
+        last_posted_date: Some("2026-10-12".to_string()),
+        profile: None,
+    }
+}
+
+/// A finalized 8h 30m workday.
+pub fn work_span(date: &str) -> WorkSpanData {
+    WorkSpanData {
+        date: date.to_string(),
+        total_span_minutes: 510,
+        total_span: "8h 30m".to_string(),
+        first_boot: "08:45:00".to_string(),
+        last_shutdown: "17:15:00".to_string(),
+        excluded_minutes: 0,
+        adjusted: false,
+        note: None,
+        day_type: Default::default(),
+        expected_minutes: 480,
+        deviation_minutes: 30,
+        week_balance_minutes: 30,
+        month_balance_minutes: 30,
+    }
+}